@@ -0,0 +1,442 @@
+use reqwest::{
+    Client, Request, RequestBuilder, Response, StatusCode,
+    header::{AUTHORIZATION, HeaderValue, WWW_AUTHENTICATE},
+};
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+use crate::types::{AuthScheme, DigestChallenge, HashAlgorithm, HttpAuth};
+use crate::utils::{header_to_string_lossy, split_outside_quotes, unquote};
+use crate::verify::{HashAccumulator, to_hex};
+
+/// Credentials set once from the command line and used by every request.
+static HTTP_AUTH: OnceLock<HttpAuth> = OnceLock::new();
+
+/// Installs the credentials used by [`crate::utils::send_traced`].
+pub fn init(auth: HttpAuth) {
+    debug!(user = %auth.user, host = %auth.host, scheme = ?auth.scheme, "HTTP authentication enabled");
+    if HTTP_AUTH.set(auth).is_err() {
+        warn!("HTTP authentication already configured, ignoring");
+    }
+}
+
+/// Returns the credentials for `req`, if any were given and it goes to their host.
+pub fn for_request(req: &Request) -> Option<&'static HttpAuth> {
+    HTTP_AUTH
+        .get()
+        .filter(|auth| req.url().host_str() == Some(auth.host.as_str()))
+}
+
+/// Adds the `Authorization` header for `auth` to `req`.
+///
+/// Basic credentials are always sent. Digest credentials need a challenge first, so the
+/// first request goes without them; later ones answer the last challenge preemptively.
+pub fn authorize(client: &Client, req: Request, auth: &HttpAuth) -> reqwest::Result<Request> {
+    match auth.scheme {
+        AuthScheme::Basic => RequestBuilder::from_parts(client.clone(), req)
+            .basic_auth(&auth.user, Some(&auth.password))
+            .build(),
+        AuthScheme::Digest => {
+            let mut req = req;
+            let mut digest = auth.digest.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(challenge) = digest.as_mut()
+                && let Ok(value) = HeaderValue::from_str(&digest_response(challenge, &req, auth))
+            {
+                req.headers_mut().insert(AUTHORIZATION, value);
+            }
+            Ok(req)
+        }
+        // Rejected when the options are validated
+        AuthScheme::Ntlm | AuthScheme::Negotiate => Ok(req),
+    }
+}
+
+/// Records the Digest challenge of a 401 response.
+///
+/// # Returns
+///
+/// * `true` - If the request should be sent again with credentials for the new challenge.
+/// * `false` - If the scheme is not Digest, no usable challenge was offered, or the
+///   server rejected credentials for the same nonce (wrong user or password).
+pub fn accept_challenge(auth: &HttpAuth, resp: &Response) -> bool {
+    if auth.scheme != AuthScheme::Digest || resp.status() != StatusCode::UNAUTHORIZED {
+        return false;
+    }
+
+    let challenges: Vec<(DigestChallenge, bool)> = resp
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .flat_map(|v| parse_challenges(&header_to_string_lossy(v)))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("digest"))
+        .filter_map(|(_, params)| digest_challenge(&params))
+        .collect();
+    // Prefer SHA-256 when the server offers both
+    let Some((challenge, stale)) = challenges
+        .iter()
+        .find(|(c, _)| c.algorithm == HashAlgorithm::Sha256)
+        .or_else(|| challenges.first())
+        .cloned()
+    else {
+        debug!(url = %resp.url(), "No supported Digest challenge in 401 response");
+        return false;
+    };
+
+    let mut digest = auth.digest.lock().unwrap_or_else(|e| e.into_inner());
+    if !stale
+        && let Some(previous) = digest.as_ref()
+        && previous.nonce == challenge.nonce
+    {
+        warn!(realm = %challenge.realm, "Digest credentials rejected");
+        return false;
+    }
+    debug!(
+        realm = %challenge.realm,
+        algorithm = %challenge.algorithm_name,
+        qop_auth = challenge.qop_auth,
+        stale = stale,
+        "Digest challenge received"
+    );
+    *digest = Some(challenge);
+    true
+}
+
+/// Builds a `DigestChallenge` from the challenge parameters, together with its `stale` flag.
+///
+/// Returns `None` for unsupported algorithms and for challenges offering only `qop=auth-int`.
+fn digest_challenge(params: &[(String, String)]) -> Option<(DigestChallenge, bool)> {
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+
+    let algorithm_name = param("algorithm").unwrap_or("MD5").to_string();
+    let upper = algorithm_name.to_ascii_uppercase();
+    let (base, session) = match upper.strip_suffix("-SESS") {
+        Some(base) => (base, true),
+        None => (upper.as_str(), false),
+    };
+    let algorithm = match base {
+        "MD5" => HashAlgorithm::Md5,
+        "SHA-256" => HashAlgorithm::Sha256,
+        _ => {
+            debug!(algorithm = %algorithm_name, "Unsupported Digest algorithm");
+            return None;
+        }
+    };
+
+    let qop_auth = match param("qop") {
+        Some(qop) => {
+            if !qop
+                .split(',')
+                .any(|q| q.trim().eq_ignore_ascii_case("auth"))
+            {
+                debug!(qop = %qop, "Unsupported Digest qop");
+                return None;
+            }
+            true
+        }
+        None => false,
+    };
+
+    let challenge = DigestChallenge {
+        realm: param("realm").unwrap_or_default().to_string(),
+        nonce: param("nonce")?.to_string(),
+        opaque: param("opaque").map(str::to_string),
+        algorithm_name,
+        algorithm,
+        session,
+        qop_auth,
+        nonce_count: 0,
+    };
+    let stale = param("stale").is_some_and(|s| s.eq_ignore_ascii_case("true"));
+    Some((challenge, stale))
+}
+
+/// Computes the `Authorization: Digest ...` value for `req` (RFC 7616, section 3.4).
+fn digest_response(challenge: &mut DigestChallenge, req: &Request, auth: &HttpAuth) -> String {
+    digest_response_with_cnonce(challenge, req, auth, &new_cnonce())
+}
+
+/// [`digest_response`] with a given client nonce.
+fn digest_response_with_cnonce(
+    challenge: &mut DigestChallenge,
+    req: &Request,
+    auth: &HttpAuth,
+    cnonce: &str,
+) -> String {
+    challenge.nonce_count += 1;
+    let nc = format!("{:08x}", challenge.nonce_count);
+    let url = req.url();
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let hash = |parts: &[&str]| {
+        let mut acc = HashAccumulator::new(challenge.algorithm);
+        acc.update(parts.join(":").as_bytes());
+        acc.finish_hex()
+    };
+    let mut ha1 = hash(&[&auth.user, &challenge.realm, &auth.password]);
+    if challenge.session {
+        ha1 = hash(&[&ha1, &challenge.nonce, cnonce]);
+    }
+    let ha2 = hash(&[req.method().as_str(), &uri]);
+    let response = if challenge.qop_auth {
+        hash(&[&ha1, &challenge.nonce, &nc, cnonce, "auth", &ha2])
+    } else {
+        hash(&[&ha1, &challenge.nonce, &ha2])
+    };
+
+    let mut value = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        quote(&auth.user),
+        quote(&challenge.realm),
+        quote(&challenge.nonce),
+        quote(&uri),
+        challenge.algorithm_name,
+        response
+    );
+    if challenge.qop_auth {
+        value.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+    }
+    if let Some(opaque) = &challenge.opaque {
+        value.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+    }
+    value
+}
+
+/// Random client nonce, 16 bytes as hex.
+fn new_cnonce() -> String {
+    let mut bytes = [0u8; 16];
+    // The system RNG only fails if the OS cannot provide randomness at all
+    aws_lc_rs::rand::fill(&mut bytes).expect("system random number generator");
+    to_hex(&bytes)
+}
+
+/// Escapes `"` and `\` for a quoted-string.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Splits a `WWW-Authenticate` value into challenges: scheme and parameters.
+///
+/// One header may carry several comma-separated challenges; a list item starting with a
+/// bare token (no `=` before the first space) begins a new one. Quoted values are unescaped.
+///
+/// # Examples
+///
+/// * `Digest realm="a, b", nonce=x, Basic realm=c` ->
+///   `[("Digest", [("realm", "a, b"), ("nonce", "x")]), ("Basic", [("realm", "c")])]`
+fn parse_challenges(value: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut challenges: Vec<(String, Vec<(String, String)>)> = Vec::new();
+
+    for item in split_outside_quotes(value, ',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let param = match item.find([' ', '=']) {
+            Some(i) if item.as_bytes()[i] == b' ' => {
+                challenges.push((item[..i].to_string(), Vec::new()));
+                item[i..].trim_start()
+            }
+            Some(_) => item,
+            None => {
+                challenges.push((item.to_string(), Vec::new()));
+                continue;
+            }
+        };
+        if let Some((_, params)) = challenges.last_mut()
+            && let Some((key, val)) = param.split_once('=')
+        {
+            params.push((key.trim().to_string(), unquote(val.trim())));
+        }
+    }
+    challenges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestServer, response};
+    use std::sync::Mutex;
+
+    fn digest_auth(user: &str, password: &str) -> HttpAuth {
+        HttpAuth {
+            scheme: AuthScheme::Digest,
+            user: user.to_string(),
+            password: password.to_string(),
+            host: "127.0.0.1".to_string(),
+            digest: Mutex::new(None),
+        }
+    }
+
+    fn challenge(header: &str) -> DigestChallenge {
+        let (_, params) = parse_challenges(header).remove(0);
+        digest_challenge(&params).unwrap().0
+    }
+
+    /// Parameters of an `Authorization: Digest ...` value.
+    fn params(value: &str) -> Vec<(String, String)> {
+        let (scheme, params) = parse_challenges(value).remove(0);
+        assert_eq!(scheme, "Digest");
+        params
+    }
+
+    fn param<'a>(params: &'a [(String, String)], name: &str) -> &'a str {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_else(|| panic!("no {name} in the response"))
+    }
+
+    fn get(url: &str) -> Request {
+        Client::new().get(url).build().unwrap()
+    }
+
+    #[test]
+    fn rfc_2617_md5_example() {
+        let mut challenge = challenge(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        );
+        let auth = digest_auth("Mufasa", "Circle Of Life");
+        let value = digest_response_with_cnonce(
+            &mut challenge,
+            &get("http://www.nowhere.org/dir/index.html"),
+            &auth,
+            "0a4f113b",
+        );
+
+        let params = params(&value);
+        assert_eq!(
+            param(&params, "response"),
+            "6629fae49393a05397450978507c4ef1"
+        );
+        assert_eq!(param(&params, "uri"), "/dir/index.html");
+        assert_eq!(param(&params, "nc"), "00000001");
+        assert_eq!(param(&params, "opaque"), "5ccc069c403ebaf9f0171e9517f40e41");
+    }
+
+    #[test]
+    fn rfc_7616_examples() {
+        let cases = [
+            ("MD5", "8ca523f5e9506fed4657c9700eebdbec"),
+            (
+                "SHA-256",
+                "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+            ),
+        ];
+        for (algorithm, expected) in cases {
+            let mut challenge = challenge(&format!(
+                "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+                 algorithm={}, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                 opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+                algorithm
+            ));
+            let value = digest_response_with_cnonce(
+                &mut challenge,
+                &get("http://www.example.org/dir/index.html"),
+                &digest_auth("Mufasa", "Circle of Life"),
+                "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            );
+            let params = params(&value);
+            assert_eq!(param(&params, "response"), expected, "{algorithm}");
+            assert_eq!(param(&params, "algorithm"), algorithm);
+        }
+    }
+
+    #[test]
+    fn unsupported_challenges_are_skipped() {
+        for header in [
+            "Digest realm=\"r\", nonce=\"n\", algorithm=SHA-512-256",
+            "Digest realm=\"r\", nonce=\"n\", qop=\"auth-int\"",
+            "Digest realm=\"r\"",
+        ] {
+            let (_, params) = parse_challenges(header).remove(0);
+            assert!(digest_challenge(&params).is_none(), "{header}");
+        }
+    }
+
+    /// Server accepting `Mufasa` / `Circle Of Life` with Digest MD5 on any path.
+    fn digest_server(req: &crate::test_server::Request) -> Vec<u8> {
+        const NONCE: &str = "dcd98b7102dd2f0e8b11d0f600bfb0c093";
+        let challenge = format!(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth\", nonce=\"{}\", opaque=\"xyz\"",
+            NONCE
+        );
+        let unauthorized = response(401, &[("WWW-Authenticate", &challenge)], b"");
+        let Some(value) = req.header("authorization") else {
+            return unauthorized;
+        };
+
+        let params = params(value);
+        let hash = |s: String| {
+            let mut acc = HashAccumulator::new(HashAlgorithm::Md5);
+            acc.update(s.as_bytes());
+            acc.finish_hex()
+        };
+        let ha1 = hash("Mufasa:testrealm@host.com:Circle Of Life".to_string());
+        let ha2 = hash(format!("{}:{}", req.method, req.path));
+        let expected = hash(format!(
+            "{}:{}:{}:{}:auth:{}",
+            ha1,
+            NONCE,
+            param(&params, "nc"),
+            param(&params, "cnonce"),
+            ha2
+        ));
+        if param(&params, "uri") == req.path
+            && param(&params, "opaque") == "xyz"
+            && param(&params, "response") == expected
+        {
+            response(200, &[], b"secret")
+        } else {
+            unauthorized
+        }
+    }
+
+    #[tokio::test]
+    async fn digest_challenge_is_answered() {
+        let server = TestServer::start(digest_server).await;
+        let client = Client::builder().no_proxy().build().unwrap();
+        let auth = digest_auth("Mufasa", "Circle Of Life");
+        let url = format!("{}/dir/index.html?x=1", server.url);
+
+        let req = authorize(&client, get(&url), &auth).unwrap();
+        assert!(req.headers().get(AUTHORIZATION).is_none());
+        let resp = client.execute(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(accept_challenge(&auth, &resp));
+
+        // The retry and the requests after it answer the recorded challenge
+        for nc in ["00000001", "00000002"] {
+            let req = authorize(&client, get(&url), &auth).unwrap();
+            let value = header_to_string_lossy(req.headers().get(AUTHORIZATION).unwrap());
+            assert_eq!(param(&params(&value), "nc"), nc);
+            let resp = client.execute(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn wrong_password_is_not_retried() {
+        let server = TestServer::start(digest_server).await;
+        let client = Client::builder().no_proxy().build().unwrap();
+        let auth = digest_auth("Mufasa", "wrong");
+        let url = format!("{}/dir/index.html", server.url);
+
+        let resp = client.execute(get(&url)).await.unwrap();
+        assert!(accept_challenge(&auth, &resp));
+        let req = authorize(&client, get(&url), &auth).unwrap();
+        let resp = client.execute(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        // Same nonce again: the credentials are wrong, not stale
+        assert!(!accept_challenge(&auth, &resp));
+    }
+}
@@ -0,0 +1,363 @@
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime, pem::PemObject};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme, SupportedCipherSuite,
+    SupportedProtocolVersion,
+};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, trace};
+
+use crate::error::ProgramError;
+use crate::types::{HttpVersion, TlsVersion};
+
+/// OpenSSL names of the TLS 1.2 suites offered by the aws-lc-rs provider,
+/// next to the rustls (IANA) names they stand for.
+const OPENSSL_NAMES: [(&str, &str); 6] = [
+    (
+        "ECDHE-ECDSA-AES256-GCM-SHA384",
+        "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+    ),
+    (
+        "ECDHE-ECDSA-AES128-GCM-SHA256",
+        "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+    ),
+    (
+        "ECDHE-ECDSA-CHACHA20-POLY1305",
+        "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+    ),
+    (
+        "ECDHE-RSA-AES256-GCM-SHA384",
+        "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+    ),
+    (
+        "ECDHE-RSA-AES128-GCM-SHA256",
+        "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+    ),
+    (
+        "ECDHE-RSA-CHACHA20-POLY1305",
+        "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+    ),
+];
+
+/// Builds the rustls client configuration used for `--cipher-list` and `--tls-hostname`.
+///
+/// reqwest exposes neither cipher suite selection nor the name certificates are checked
+/// against, so the whole TLS configuration is rebuilt here and handed over with
+/// `ClientBuilder::tls_backend_preconfigured`. It covers the other TLS options as well,
+/// since reqwest ignores them for a preconfigured backend.
+///
+/// # Arguments
+///
+/// * `cipher_list` - Suites separated by `:` or `,`, as OpenSSL (`ECDHE-RSA-AES128-GCM-SHA256`)
+///   or IANA names (`TLS_AES_128_GCM_SHA256`, case-insensitive); `None` keeps all of them.
+/// * `tls_hostname` - Name the server certificate is verified against instead of the URL host.
+/// * `min_version` / `max_version` - Accepted TLS versions.
+/// * `cacert` - PEM bundle replacing the platform verifier (`--cacert`).
+/// * `insecure` - Skip certificate verification (`--insecure`).
+/// * `http_version` - Selects the ALPN protocols.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` - If a suite is unknown, none fits the versions,
+///   or `tls_hostname` is not a valid DNS name or IP address.
+pub fn rustls_config(
+    cipher_list: Option<&str>,
+    tls_hostname: Option<&str>,
+    min_version: TlsVersion,
+    max_version: TlsVersion,
+    cacert: Option<&Path>,
+    insecure: bool,
+    http_version: HttpVersion,
+) -> Result<ClientConfig, ProgramError> {
+    let mut provider = aws_lc_rs::default_provider();
+    let available = std::mem::take(&mut provider.cipher_suites);
+
+    let mut suites = Vec::new();
+    match cipher_list {
+        Some(list) => {
+            for name in list
+                .split([':', ','])
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+            {
+                let suite = find_suite(&available, name).ok_or_else(|| {
+                    ProgramError::ArgNotValid(format!(
+                        "unknown or unsupported cipher suite {} (supported: {})",
+                        name,
+                        available
+                            .iter()
+                            .map(|s| suite_name(*s))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                })?;
+                if !suites.contains(&suite) {
+                    suites.push(suite);
+                }
+            }
+        }
+        None => suites = available,
+    }
+
+    let versions: Vec<&'static SupportedProtocolVersion> = [
+        (TlsVersion::Tls12, &rustls::version::TLS12),
+        (TlsVersion::Tls13, &rustls::version::TLS13),
+    ]
+    .into_iter()
+    .filter(|(v, _)| (min_version..=max_version).contains(v))
+    .map(|(_, v)| v)
+    .collect();
+    suites.retain(|s| versions.iter().any(|v| s.version() == *v));
+    if suites.is_empty() {
+        return Err(ProgramError::ArgNotValid(
+            "no cipher suite in cipher-list can be used with the selected TLS versions".to_string(),
+        ));
+    }
+    if cipher_list.is_some() {
+        debug!(
+            cipher_suites = ?suites.iter().map(|s| suite_name(*s)).collect::<Vec<_>>(),
+            "Restricted TLS cipher suites"
+        );
+    }
+
+    provider.cipher_suites = suites;
+    let provider = Arc::new(provider);
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&versions)
+        .map_err(|e| ProgramError::ArgNotValid(format!("invalid TLS configuration: {}", e)))?;
+
+    let verifier: Arc<dyn ServerCertVerifier> = if insecure {
+        Arc::new(NoVerifier(provider))
+    } else {
+        let verifier: Arc<dyn ServerCertVerifier> = if let Some(path) = cacert {
+            WebPkiServerVerifier::builder_with_provider(Arc::new(load_root_store(path)?), provider)
+                .build()
+                .map_err(|e| {
+                    ProgramError::ArgNotValid(format!(
+                        "invalid CA bundle {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?
+        } else {
+            Arc::new(
+                rustls_platform_verifier::Verifier::new(provider).map_err(|e| {
+                    ProgramError::Other(format!("cannot load system certificates: {}", e))
+                })?,
+            )
+        };
+        match tls_hostname {
+            Some(hostname) => Arc::new(HostnameOverride::new(verifier, hostname)?),
+            None => verifier,
+        }
+    };
+
+    let mut config = builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth();
+    config.alpn_protocols = match http_version {
+        HttpVersion::Http10 | HttpVersion::Http11 => vec![b"http/1.1".to_vec()],
+        HttpVersion::Http2 => vec![b"h2".to_vec()],
+        HttpVersion::Auto | HttpVersion::Http3 => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+    };
+    Ok(config)
+}
+
+/// Looks up a suite by its rustls/IANA name, its TLS 1.3 IANA alias or its OpenSSL name.
+fn find_suite(available: &[SupportedCipherSuite], name: &str) -> Option<SupportedCipherSuite> {
+    let name = name.to_ascii_uppercase();
+    let name = OPENSSL_NAMES
+        .iter()
+        .find(|(openssl, _)| *openssl == name)
+        .map_or(name.clone(), |(_, iana)| iana.to_string());
+    // rustls names TLS 1.3 suites TLS13_*, IANA and OpenSSL use TLS_*
+    let tls13_name = name.replacen("TLS_", "TLS13_", 1);
+
+    available
+        .iter()
+        .copied()
+        .find(|s| suite_name(*s) == name || suite_name(*s) == tls13_name)
+}
+
+fn suite_name(suite: SupportedCipherSuite) -> &'static str {
+    suite.suite().as_str().unwrap_or("unknown")
+}
+
+/// Reads a PEM bundle into a rustls root store (`--cacert`).
+fn load_root_store(path: &Path) -> Result<RootCertStore, ProgramError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        ProgramError::ArgNotValid(format!("invalid CA bundle {}: {}", path.display(), e))
+    };
+
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path).map_err(|e| invalid(&e))? {
+        roots
+            .add(cert.map_err(|e| invalid(&e))?)
+            .map_err(|e| invalid(&e))?;
+    }
+    if roots.is_empty() {
+        return Err(ProgramError::ArgNotValid(format!(
+            "no certificates found in CA bundle {}",
+            path.display()
+        )));
+    }
+    Ok(roots)
+}
+
+/// Certificate verifier accepting every server certificate (`--insecure`).
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Verifier checking server certificates against a fixed name (`--tls-hostname`).
+///
+/// The chain is still verified by the wrapped verifier; only the name it must be issued
+/// for is replaced. The SNI sent by the connector is not affected and stays the URL host.
+#[derive(Debug)]
+struct HostnameOverride {
+    inner: Arc<dyn ServerCertVerifier>,
+    hostname: ServerName<'static>,
+}
+
+impl HostnameOverride {
+    fn new(inner: Arc<dyn ServerCertVerifier>, hostname: &str) -> Result<Self, ProgramError> {
+        let hostname = ServerName::try_from(hostname.to_string()).map_err(|e| {
+            ProgramError::ArgNotValid(format!("invalid tls-hostname {}: {}", hostname, e))
+        })?;
+        debug!(hostname = ?hostname, "Verifying server certificates against TLS hostname");
+        Ok(Self { inner, hostname })
+    }
+}
+
+impl ServerCertVerifier for HostnameOverride {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        trace!(
+            sni = ?server_name,
+            verified_as = ?self.hostname,
+            "Checking certificate against TLS hostname override"
+        );
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            &self.hostname,
+            ocsp_response,
+            now,
+        )
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(cipher_list: &str, max_version: TlsVersion) -> Result<ClientConfig, ProgramError> {
+        rustls_config(
+            Some(cipher_list),
+            None,
+            TlsVersion::Tls12,
+            max_version,
+            None,
+            true,
+            HttpVersion::Auto,
+        )
+    }
+
+    fn suite_names(config: &ClientConfig) -> Vec<&'static str> {
+        let suites = &config.crypto_provider().cipher_suites;
+        suites.iter().map(|s| suite_name(*s)).collect()
+    }
+
+    #[test]
+    fn cipher_list_accepts_openssl_and_iana_names() {
+        let config = config(
+            "ECDHE-RSA-AES128-GCM-SHA256, tls_aes_128_gcm_sha256:ECDHE-RSA-AES128-GCM-SHA256",
+            TlsVersion::Tls13,
+        )
+        .unwrap();
+        assert_eq!(
+            suite_names(&config),
+            [
+                "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+                "TLS13_AES_128_GCM_SHA256"
+            ]
+        );
+    }
+
+    #[test]
+    fn cipher_list_rejects_unknown_and_unusable_suites() {
+        let err = config("RC4-MD5", TlsVersion::Tls13).unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+        assert!(err.to_string().contains("RC4-MD5"), "{err}");
+
+        // A TLS 1.3 suite cannot be used when TLS 1.2 is the highest version
+        let err = config("TLS_AES_128_GCM_SHA256", TlsVersion::Tls12).unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+    }
+}
@@ -0,0 +1,140 @@
+use reqwest::{
+    Client, Response,
+    header::{CONTENT_TYPE, RANGE},
+};
+use std::future::Future;
+#[cfg(test)]
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use crate::error::ProgramError;
+use crate::types::PostBody;
+use crate::utils::send_traced;
+
+/// Minimal HTTP client interface used by the download functions.
+///
+/// Abstracting over `reqwest::Client` lets the download logic run against
+/// alternative implementations (e.g. a mock returning canned responses).
+pub trait HttpClient {
+    /// Sends a GET request, optionally restricted to the inclusive byte range `(start, end)`.
+    fn get(
+        &self,
+        url: &str,
+        range: Option<(u64, u64)>,
+    ) -> impl Future<Output = Result<Response, ProgramError>> + Send;
+
+    /// Sends a POST request with the given body.
+    fn post(
+        &self,
+        url: &str,
+        body: &PostBody,
+    ) -> impl Future<Output = Result<Response, ProgramError>> + Send;
+}
+
+impl HttpClient for Client {
+    async fn get(&self, url: &str, range: Option<(u64, u64)>) -> Result<Response, ProgramError> {
+        let mut req = Client::get(self, url);
+        if let Some((start, end)) = range {
+            req = req.header(RANGE, format!("bytes={}-{}", start, end));
+        }
+        Ok(send_traced(req).await?)
+    }
+
+    async fn post(&self, url: &str, body: &PostBody) -> Result<Response, ProgramError> {
+        let req = Client::post(self, url)
+            .header(CONTENT_TYPE, body.content_type)
+            .body(body.data.clone());
+        Ok(send_traced(req).await?)
+    }
+}
+
+/// A canned response of [`MockHttpClient`].
+#[cfg(test)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+    /// Time to wait before the response is returned
+    delay: Duration,
+}
+
+#[cfg(test)]
+impl MockResponse {
+    pub fn new(status: u16, body: &[u8]) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_vec(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// URL and range of a request received by [`MockHttpClient`]
+#[cfg(test)]
+type MockRequest = (String, Option<(u64, u64)>);
+
+/// Client answering requests with canned responses, in order, without any network.
+///
+/// Requests after the last canned response fail with `ProgramError::Other`.
+#[cfg(test)]
+pub struct MockHttpClient {
+    responses: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+#[cfg(test)]
+impl MockHttpClient {
+    pub fn new(responses: impl IntoIterator<Item = MockResponse>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into_iter().collect()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// URL and range of each request received so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    async fn respond(
+        &self,
+        url: &str,
+        range: Option<(u64, u64)>,
+    ) -> Result<Response, ProgramError> {
+        self.requests.lock().unwrap().push((url.to_string(), range));
+        let next = self.responses.lock().unwrap().pop_front();
+        let canned =
+            next.ok_or_else(|| ProgramError::Other(format!("unexpected request to {}", url)))?;
+        tokio::time::sleep(canned.delay).await;
+
+        let mut builder = http::Response::builder().status(canned.status);
+        for (name, value) in &canned.headers {
+            builder = builder.header(*name, value);
+        }
+        let resp = builder
+            .body(canned.body)
+            .map_err(|e| ProgramError::Other(e.to_string()))?;
+        Ok(Response::from(resp))
+    }
+}
+
+#[cfg(test)]
+impl HttpClient for MockHttpClient {
+    async fn get(&self, url: &str, range: Option<(u64, u64)>) -> Result<Response, ProgramError> {
+        self.respond(url, range).await
+    }
+
+    async fn post(&self, url: &str, _body: &PostBody) -> Result<Response, ProgramError> {
+        self.respond(url, None).await
+    }
+}
@@ -0,0 +1,153 @@
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an RFC 2822 (including HTTP-date) or RFC 3339 date into seconds since the Unix epoch.
+///
+/// # Examples
+///
+/// * `Wed, 14 Oct 2026 14:00:00 +0200` -> `1791979200`
+/// * `Wed, 14 Oct 2026 12:00:00 GMT` -> `1791979200`
+/// * `2026-10-14T12:00:00Z` -> `1791979200`
+pub fn parse_date(s: &str) -> Option<i64> {
+    let s = s.trim();
+    parse_rfc3339(s).or_else(|| parse_rfc2822(s))
+}
+
+/// Formats seconds since the Unix epoch as an IMF-fixdate, as used in HTTP headers.
+pub fn format_http_date(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86_400);
+    let secs = timestamp.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[days.rem_euclid(7) as usize],
+        day,
+        MONTH_NAMES[(month - 1) as usize],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Parses `YYYY-MM-DDTHH:MM:SS[.frac](Z|±HH:MM)`.
+fn parse_rfc3339(s: &str) -> Option<i64> {
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+
+    let mut ymd = date.splitn(3, '-');
+    let year = ymd.next()?.parse().ok()?;
+    let month = ymd.next()?.parse().ok()?;
+    let day = ymd.next()?.parse().ok()?;
+
+    let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else {
+        let pos = time.rfind(['+', '-'])?;
+        (&time[..pos], parse_offset(&time[pos..])?)
+    };
+    // Fractional seconds are irrelevant for HTTP dates
+    let clock = clock.split('.').next()?;
+
+    let mut hms = clock.splitn(3, ':');
+    let hour = hms.next()?.parse().ok()?;
+    let minute = hms.next()?.parse().ok()?;
+    let second = hms.next()?.parse().ok()?;
+
+    to_timestamp(year, month, day, hour, minute, second).map(|t| t - offset)
+}
+
+/// Parses `[Day,] DD Mon YYYY HH:MM[:SS] (±HHMM|GMT|UT|Z)`.
+fn parse_rfc2822(s: &str) -> Option<i64> {
+    let s = match s.split_once(',') {
+        Some((_, rest)) => rest,
+        None => s,
+    };
+
+    let mut fields = s.split_whitespace();
+    let day = fields.next()?.parse().ok()?;
+    let month_name = fields.next()?;
+    let month = MONTH_NAMES
+        .iter()
+        .position(|m| m.eq_ignore_ascii_case(month_name))? as u32
+        + 1;
+    let year = fields.next()?.parse().ok()?;
+    let clock = fields.next()?;
+    let offset = match fields.next() {
+        None | Some("GMT" | "UT" | "UTC" | "Z") => 0,
+        Some(zone) => parse_offset(zone)?,
+    };
+
+    let mut hms = clock.splitn(3, ':');
+    let hour = hms.next()?.parse().ok()?;
+    let minute = hms.next()?.parse().ok()?;
+    let second = match hms.next() {
+        Some(sec) => sec.parse().ok()?,
+        None => 0,
+    };
+
+    to_timestamp(year, month, day, hour, minute, second).map(|t| t - offset)
+}
+
+/// Parses a UTC offset (`+0200`, `-05:30`) into seconds east of UTC.
+fn parse_offset(s: &str) -> Option<i64> {
+    let (sign, digits) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let digits: String = digits.chars().filter(|&c| c != ':').collect();
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Converts a UTC calendar date and time of day into a Unix timestamp.
+fn to_timestamp(
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: i64,
+    minute: i64,
+    second: i64,
+) -> Option<i64> {
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of [`days_from_civil`]: returns `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,904 @@
+use reqwest::{Response, header::CONTENT_ENCODING};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use tokio::io::AsyncWrite;
+use tracing::{debug, warn};
+
+use crate::error::ProgramError;
+use crate::utils::header_to_string_lossy;
+
+/// History a DEFLATE back-reference may reach into (RFC 1951, section 2).
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// Decompressed bytes buffered before they are written out, so that highly
+/// compressed input does not expand into memory all at once.
+const MAX_PENDING: usize = 256 * 1024;
+
+/// Base lengths of the length symbols 257..=285, and their extra bits.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances of the distance symbols 0..=29, and their extra bits.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which the code length code lengths are stored in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// CRC-32 (IEEE, reflected) lookup table, as used by the gzip trailer.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// `Content-Encoding` values `--decompress` can undo.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContentEncoding {
+    /// `gzip` / `x-gzip` (RFC 1952)
+    Gzip,
+    /// `deflate`: zlib-wrapped (RFC 1950) or, as some servers send it, raw DEFLATE
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Parses a `Content-Encoding` value; `identity` entries are ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `None` - If no coding, an unsupported one (e.g. `br`, `xz`, `bzip2`) or more
+    ///   than one coding is applied.
+    pub fn from_header(value: &str) -> Option<Self> {
+        let mut codings = value
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty() && !c.eq_ignore_ascii_case("identity"));
+        let coding = codings.next()?;
+        if codings.next().is_some() {
+            return None;
+        }
+        if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            Some(Self::Gzip)
+        } else if coding.eq_ignore_ascii_case("deflate") {
+            Some(Self::Deflate)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    /// Returns the coding of `resp` to undo, if any.
+    ///
+    /// # Errors
+    ///
+    /// * `ProgramError::Other` - If the body has a coding `--decompress` cannot undo
+    ///   (e.g. `xz`, `br` or `bzip2`), rather than saving it still compressed.
+    pub fn for_response(resp: &Response) -> Result<Option<Self>, ProgramError> {
+        let Some(value) = resp.headers().get(CONTENT_ENCODING) else {
+            return Ok(None);
+        };
+        let value = header_to_string_lossy(value);
+        if is_identity(&value) {
+            return Ok(None);
+        }
+        match Self::from_header(&value) {
+            Some(encoding) => {
+                debug!(encoding = ?encoding, "Decompressing response body");
+                Ok(Some(encoding))
+            }
+            None => Err(ProgramError::Other(format!(
+                "Content-Encoding '{}' is not supported by --decompress (only gzip and deflate)",
+                value
+            ))),
+        }
+    }
+}
+
+/// Returns true if a `Content-Encoding` value applies no coding (empty or only `identity`).
+pub fn is_identity(value: &str) -> bool {
+    value
+        .split(',')
+        .map(str::trim)
+        .all(|c| c.is_empty() || c.eq_ignore_ascii_case("identity"))
+}
+
+/// Writer decompressing everything written to it into `inner` (`--decompress`).
+///
+/// Data is inflated as it arrives; [`AsyncWrite::poll_shutdown`] fails with
+/// `UnexpectedEof` if the compressed stream was cut short, and corrupt input or a
+/// checksum mismatch in the trailer is reported as `InvalidData`.
+pub struct DecompressWriter<W> {
+    inner: W,
+    inflater: Inflater,
+}
+
+impl<W: AsyncWrite + Unpin> DecompressWriter<W> {
+    pub fn new(inner: W, encoding: ContentEncoding) -> Self {
+        Self {
+            inner,
+            inflater: Inflater::new(encoding),
+        }
+    }
+
+    /// Number of decompressed bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.inflater.total_out
+    }
+
+    /// Writes out the decompressed data until all input received so far is processed.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            let pending = self.inflater.pending();
+            if !pending.is_empty() {
+                let n = ready!(Pin::new(&mut self.inner).poll_write(cx, pending))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                self.inflater.advance(n);
+            } else if self.inflater.more_output {
+                self.inflater.inflate()?;
+            } else {
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for DecompressWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.inflater.bits.data.extend_from_slice(buf);
+        this.inflater.started |= !buf.is_empty();
+        this.inflater.inflate()?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.inflater.check_complete()?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Why decoding stopped before the current step was complete.
+enum Halt {
+    /// The step needs input that has not arrived yet; it is retried from its start.
+    NeedInput,
+    Corrupt(&'static str),
+}
+
+/// Position in the compressed stream.
+enum State {
+    Header,
+    BlockHeader,
+    /// Inside a stored block, with the bytes left in it
+    Stored(usize),
+    /// Inside a Huffman-coded block, with its literal/length and distance codes
+    Codes(Box<(Huffman, Huffman)>),
+    Trailer,
+    Done,
+}
+
+/// Little-endian bit reader over the input received so far.
+struct BitReader {
+    data: Vec<u8>,
+    /// Position of the next bit in `data`
+    pos: usize,
+}
+
+impl BitReader {
+    /// Reads `n` (at most 32) bits, least significant first; consumes nothing on `NeedInput`.
+    fn bits(&mut self, n: u32) -> Result<u32, Halt> {
+        if self.pos + n as usize > self.data.len() * 8 {
+            return Err(Halt::NeedInput);
+        }
+        let mut value = 0u32;
+        for i in 0..n as usize {
+            let p = self.pos + i;
+            value |= u32::from((self.data[p / 8] >> (p % 8)) & 1) << i;
+        }
+        self.pos += n as usize;
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary.
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+
+    /// Drops the bytes that have been read completely.
+    fn compact(&mut self) {
+        let consumed = self.pos / 8;
+        self.data.drain(..consumed);
+        self.pos -= consumed * 8;
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time (as in zlib's `puff.c`).
+struct Huffman {
+    /// Number of codes of each length 0..=15
+    count: [u16; 16],
+    /// Symbols ordered by code
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the code from the code length of each symbol (0 = unused).
+    ///
+    /// Incomplete codes are accepted; a code that is never assigned fails when decoded.
+    fn new(lengths: &[u8]) -> Result<Self, Halt> {
+        let mut count = [0u16; 16];
+        for &len in lengths {
+            count[usize::from(len)] += 1;
+        }
+        let mut left: i32 = 1;
+        for &n in &count[1..] {
+            left = (left << 1) - i32::from(n);
+            if left < 0 {
+                return Err(Halt::Corrupt("over-subscribed Huffman code"));
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + count[len];
+        }
+        let mut symbol = vec![0u16; lengths.len()];
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbol[usize::from(offsets[usize::from(len)])] = sym as u16;
+                offsets[usize::from(len)] += 1;
+            }
+        }
+        Ok(Self { count, symbol })
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u16, Halt> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for &count in &self.count[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - count < first {
+                return Ok(self.symbol[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Halt::Corrupt("invalid Huffman code"))
+    }
+}
+
+/// The fixed literal/length and distance codes of block type 1.
+fn fixed_codes() -> Result<(Huffman, Huffman), Halt> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Reads the code definitions at the start of a dynamic block (block type 2).
+fn dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), Halt> {
+    let nlen = bits.bits(5)? as usize + 257;
+    let ndist = bits.bits(5)? as usize + 1;
+    let ncode = bits.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(Halt::Corrupt("too many length or distance codes"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &idx in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[idx] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let sym = code_length_code.decode(bits)?;
+        let (value, repeat) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+                let previous = *i
+                    .checked_sub(1)
+                    .and_then(|p| lengths.get(p))
+                    .ok_or(Halt::Corrupt("repeated code length without a previous one"))?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(Halt::Corrupt("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(Halt::Corrupt("no end-of-block code"));
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+/// Streaming DEFLATE decoder with the gzip and zlib wrappers.
+///
+/// Decoding advances in steps (a header, a symbol, a stretch of a stored block) that
+/// either complete or are retried from their start once more input has arrived.
+struct Inflater {
+    encoding: ContentEncoding,
+    state: State,
+    last_block: bool,
+    /// The `deflate` stream has a zlib header and Adler-32 trailer
+    zlib: bool,
+    bits: BitReader,
+    /// Recent output: the window for back-references, then the bytes not yet written out
+    out: Vec<u8>,
+    /// Start of the bytes in `out` not yet written out
+    unflushed: usize,
+    /// Start of the bytes in `out` not yet added to the checksum
+    checked: usize,
+    crc: u32,
+    adler: (u32, u32),
+    /// Output length of the current gzip member, for the `ISIZE` trailer field
+    member_len: u64,
+    total_out: u64,
+    /// Decoding stopped at `MAX_PENDING` and can go on without new input
+    more_output: bool,
+    started: bool,
+    trailing_ignored: bool,
+}
+
+impl Inflater {
+    fn new(encoding: ContentEncoding) -> Self {
+        Self {
+            encoding,
+            state: State::Header,
+            last_block: false,
+            zlib: false,
+            bits: BitReader {
+                data: Vec::new(),
+                pos: 0,
+            },
+            out: Vec::new(),
+            unflushed: 0,
+            checked: 0,
+            crc: !0,
+            adler: (1, 0),
+            member_len: 0,
+            total_out: 0,
+            more_output: false,
+            started: false,
+            trailing_ignored: false,
+        }
+    }
+
+    fn pending(&self) -> &[u8] {
+        &self.out[self.unflushed..]
+    }
+
+    /// Marks `n` pending bytes as written and trims the window.
+    fn advance(&mut self, n: usize) {
+        self.unflushed += n;
+        if self.unflushed == self.out.len() && self.out.len() > 2 * WINDOW_SIZE {
+            self.update_check();
+            let drop = self.out.len() - WINDOW_SIZE;
+            self.out.drain(..drop);
+            self.unflushed -= drop;
+            self.checked -= drop;
+        }
+    }
+
+    /// Decodes as much of the buffered input as possible.
+    fn inflate(&mut self) -> io::Result<()> {
+        self.more_output = false;
+        loop {
+            if self.out.len() - self.unflushed >= MAX_PENDING {
+                self.more_output = true;
+                break;
+            }
+            let checkpoint = self.bits.pos;
+            match self.step() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(Halt::NeedInput) => {
+                    self.bits.pos = checkpoint;
+                    break;
+                }
+                Err(Halt::Corrupt(reason)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("corrupt {} data: {}", self.encoding.name(), reason),
+                    ));
+                }
+            }
+        }
+        self.bits.compact();
+        Ok(())
+    }
+
+    /// Decodes one step; `Ok(false)` once the stream is complete and all input is used.
+    fn step(&mut self) -> Result<bool, Halt> {
+        match &mut self.state {
+            State::Header => {
+                self.header()?;
+                self.state = State::BlockHeader;
+            }
+            State::BlockHeader => {
+                let last = self.bits.bits(1)? == 1;
+                let next = match self.bits.bits(2)? {
+                    0 => {
+                        self.bits.align();
+                        let len = self.bits.bits(16)?;
+                        if self.bits.bits(16)? != !len & 0xFFFF {
+                            return Err(Halt::Corrupt("stored block length mismatch"));
+                        }
+                        State::Stored(len as usize)
+                    }
+                    1 => State::Codes(Box::new(fixed_codes()?)),
+                    2 => State::Codes(Box::new(dynamic_codes(&mut self.bits)?)),
+                    _ => return Err(Halt::Corrupt("invalid block type")),
+                };
+                self.last_block = last;
+                self.state = next;
+            }
+            State::Stored(0) => {
+                self.state = self.after_block();
+            }
+            State::Stored(remaining) => {
+                let start = self.bits.pos / 8;
+                let n = (*remaining)
+                    .min(self.bits.data.len() - start)
+                    .min(MAX_PENDING);
+                if n == 0 {
+                    return Err(Halt::NeedInput);
+                }
+                self.out
+                    .extend_from_slice(&self.bits.data[start..start + n]);
+                self.bits.pos += n * 8;
+                *remaining -= n;
+            }
+            State::Codes(codes) => {
+                let (lit, dist) = &**codes;
+                let sym = usize::from(lit.decode(&mut self.bits)?);
+                if sym < 256 {
+                    self.out.push(sym as u8);
+                } else if sym == 256 {
+                    self.state = self.after_block();
+                } else {
+                    let sym = sym - 257;
+                    if sym >= LENGTH_BASE.len() {
+                        return Err(Halt::Corrupt("invalid length symbol"));
+                    }
+                    let len = usize::from(LENGTH_BASE[sym])
+                        + self.bits.bits(u32::from(LENGTH_EXTRA[sym]))? as usize;
+                    let dsym = usize::from(dist.decode(&mut self.bits)?);
+                    if dsym >= DIST_BASE.len() {
+                        return Err(Halt::Corrupt("invalid distance symbol"));
+                    }
+                    let distance = usize::from(DIST_BASE[dsym])
+                        + self.bits.bits(u32::from(DIST_EXTRA[dsym]))? as usize;
+                    if distance > self.out.len() {
+                        return Err(Halt::Corrupt("distance too far back"));
+                    }
+                    for _ in 0..len {
+                        self.out.push(self.out[self.out.len() - distance]);
+                    }
+                }
+            }
+            State::Trailer => {
+                self.trailer()?;
+                self.state = State::Done;
+            }
+            State::Done => {
+                self.bits.align();
+                let start = self.bits.pos / 8;
+                let Some(&next) = self.bits.data.get(start) else {
+                    return Ok(false);
+                };
+                // Concatenated gzip members decompress to the concatenation of their data
+                if self.encoding == ContentEncoding::Gzip && next == 0x1F {
+                    self.state = State::Header;
+                } else {
+                    if !self.trailing_ignored {
+                        warn!("Ignoring data after the end of the compressed stream");
+                        self.trailing_ignored = true;
+                    }
+                    self.bits.pos = self.bits.data.len() * 8;
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn after_block(&self) -> State {
+        if self.last_block {
+            State::Trailer
+        } else {
+            State::BlockHeader
+        }
+    }
+
+    /// Reads the gzip member header or the zlib header (RFC 1952 section 2.3, RFC 1950).
+    fn header(&mut self) -> Result<(), Halt> {
+        let bits = &mut self.bits;
+        match self.encoding {
+            ContentEncoding::Gzip => {
+                if bits.bits(8)? != 0x1F || bits.bits(8)? != 0x8B {
+                    return Err(Halt::Corrupt("not in gzip format"));
+                }
+                if bits.bits(8)? != 8 {
+                    return Err(Halt::Corrupt("unknown compression method"));
+                }
+                let flags = bits.bits(8)?;
+                // MTIME, XFL, OS
+                bits.bits(32)?;
+                bits.bits(16)?;
+                if flags & 0x04 != 0 {
+                    let extra_len = bits.bits(16)?;
+                    for _ in 0..extra_len {
+                        bits.bits(8)?;
+                    }
+                }
+                // FNAME, FCOMMENT: zero-terminated
+                for flag in [0x08, 0x10] {
+                    if flags & flag != 0 {
+                        while bits.bits(8)? != 0 {}
+                    }
+                }
+                if flags & 0x02 != 0 {
+                    bits.bits(16)?;
+                }
+                self.update_check();
+                self.crc = !0;
+                self.member_len = 0;
+            }
+            ContentEncoding::Deflate => {
+                let cmf = bits.bits(8)?;
+                let flg = bits.bits(8)?;
+                self.zlib = cmf & 0x0F == 8 && ((cmf << 8) | flg) % 31 == 0;
+                if !self.zlib {
+                    bits.pos -= 16;
+                    debug!("deflate body has no zlib header, decoding raw DEFLATE");
+                } else if flg & 0x20 != 0 {
+                    return Err(Halt::Corrupt("preset dictionaries are not supported"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads and checks the gzip (CRC-32, `ISIZE`) or zlib (Adler-32) trailer.
+    fn trailer(&mut self) -> Result<(), Halt> {
+        self.bits.align();
+        self.update_check();
+        match self.encoding {
+            ContentEncoding::Gzip => {
+                let crc = self.bits.bits(32)?;
+                let size = self.bits.bits(32)?;
+                if crc != !self.crc {
+                    return Err(Halt::Corrupt("CRC-32 mismatch"));
+                }
+                if size != self.member_len as u32 {
+                    return Err(Halt::Corrupt("length mismatch"));
+                }
+            }
+            ContentEncoding::Deflate if self.zlib => {
+                let adler = self.bits.bits(32)?.swap_bytes();
+                if adler != (self.adler.1 << 16 | self.adler.0) {
+                    return Err(Halt::Corrupt("Adler-32 mismatch"));
+                }
+            }
+            ContentEncoding::Deflate => {}
+        }
+        Ok(())
+    }
+
+    /// Adds the output not yet covered to the running checksum and byte counts.
+    fn update_check(&mut self) {
+        let new = &self.out[self.checked..];
+        match self.encoding {
+            ContentEncoding::Gzip => {
+                for &b in new {
+                    self.crc =
+                        CRC32_TABLE[((self.crc ^ u32::from(b)) & 0xFF) as usize] ^ (self.crc >> 8);
+                }
+            }
+            ContentEncoding::Deflate => {
+                let (mut a, mut b) = self.adler;
+                for &byte in new {
+                    a = (a + u32::from(byte)) % 65521;
+                    b = (b + a) % 65521;
+                }
+                self.adler = (a, b);
+            }
+        }
+        self.member_len += new.len() as u64;
+        self.total_out += new.len() as u64;
+        self.checked = self.out.len();
+    }
+
+    /// Fails unless the stream ended cleanly (an empty body counts as clean).
+    fn check_complete(&self) -> io::Result<()> {
+        match self.state {
+            State::Done => Ok(()),
+            State::Header if !self.started => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "compressed stream ended early",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    /// `hello, world\n` compressed by zlib (fixed Huffman block, or stored at level 0).
+    const HELLO: &[u8] = b"hello, world\n";
+    const HELLO_GZIP: &str = "1f8b0800000000000203cb48cdc9c9d75128cf2fca49e10200537424f40d000000";
+    const HELLO_GZIP_NAMED: &str =
+        "1f8b08080000000002ff68656c6c6f2e74787400cb48cdc9c9d75128cf2fca49e10200537424f40d000000";
+    const HELLO_ZLIB: &str = "78dacb48cdc9c9d75128cf2fca49e1020021e70493";
+    const HELLO_RAW: &str = "cb48cdc9c9d75128cf2fca49e10200";
+    const HELLO_ZLIB_STORED: &str = "7801010d00f2ff68656c6c6f2c20776f726c640a21e70493";
+    /// [`fox_lines`] compressed by gzip into one dynamic Huffman block
+    const FOX_GZIP: &str = "1f8b0800000000000203edd0b71180301044d19c2ab6047c3f18e1e14048b8eaa1850d99b9f8bfe8bbce60f37d35a2b4722e68e4c2e0e775871cc6c27d792a9e1bb5b4080347e888d231a5134aa794ce289d535a0fea413da807f5e05f0fbe2a4e81d330070000";
+    const SECOND_GZIP: &str =
+        "1f8b08000000000002032b4e4dcecf4b51c84dcd4d4a2de2020036184b0e0e000000";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn fox_lines() -> Vec<u8> {
+        (0..40)
+            .flat_map(|i| {
+                format!("the quick brown fox jumps over the lazy dog {}\n", i % 7).into_bytes()
+            })
+            .collect()
+    }
+
+    /// Feeds `input` to a [`DecompressWriter`] `chunk` bytes at a time.
+    async fn decompress(
+        encoding: ContentEncoding,
+        input: &[u8],
+        chunk: usize,
+    ) -> io::Result<Vec<u8>> {
+        let mut writer = DecompressWriter::new(Vec::new(), encoding);
+        for piece in input.chunks(chunk) {
+            writer.write_all(piece).await?;
+        }
+        writer.shutdown().await?;
+        assert_eq!(writer.total_out(), writer.inner.len() as u64);
+        Ok(writer.inner)
+    }
+
+    /// Wraps `data` in a gzip member made of stored blocks.
+    fn stored_gzip(data: &[u8]) -> Vec<u8> {
+        let mut out = hex("1f8b0800000000000003");
+        let blocks: Vec<&[u8]> = data.chunks(0xFFFF).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            let len = block.len() as u16;
+            out.push(u8::from(i == blocks.len() - 1));
+            out.extend_from_slice(&len.to_le_bytes());
+            out.extend_from_slice(&(!len).to_le_bytes());
+            out.extend_from_slice(block);
+        }
+        let crc = data.iter().fold(!0u32, |crc, &b| {
+            CRC32_TABLE[((crc ^ u32::from(b)) & 0xFF) as usize] ^ (crc >> 8)
+        });
+        out.extend_from_slice(&(!crc).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn content_encoding_header() {
+        assert_eq!(
+            ContentEncoding::from_header("gzip"),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            ContentEncoding::from_header("X-Gzip"),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            ContentEncoding::from_header("identity, deflate"),
+            Some(ContentEncoding::Deflate)
+        );
+        for value in ["", "identity", "br", "xz", "bzip2", "gzip, br"] {
+            assert_eq!(ContentEncoding::from_header(value), None, "{value}");
+        }
+        assert!(is_identity("") && is_identity("identity") && is_identity(" Identity , "));
+        assert!(!is_identity("gzip") && !is_identity("identity, br"));
+    }
+
+    #[test]
+    fn unsupported_encodings_are_rejected() {
+        let response = |encoding: &str| {
+            Response::from(
+                http::Response::builder()
+                    .header(CONTENT_ENCODING, encoding)
+                    .body(Vec::new())
+                    .unwrap(),
+            )
+        };
+        for value in ["xz", "br", "bzip2", "gzip, br"] {
+            let err = ContentEncoding::for_response(&response(value)).unwrap_err();
+            assert!(err.to_string().contains(value), "{err}");
+        }
+        assert_eq!(
+            ContentEncoding::for_response(&response("identity")).unwrap(),
+            None
+        );
+        assert_eq!(
+            ContentEncoding::for_response(&response("gzip")).unwrap(),
+            Some(ContentEncoding::Gzip)
+        );
+    }
+
+    #[tokio::test]
+    async fn known_vectors() {
+        let cases = [
+            (ContentEncoding::Gzip, HELLO_GZIP),
+            (ContentEncoding::Gzip, HELLO_GZIP_NAMED),
+            (ContentEncoding::Deflate, HELLO_ZLIB),
+            (ContentEncoding::Deflate, HELLO_RAW),
+            (ContentEncoding::Deflate, HELLO_ZLIB_STORED),
+        ];
+        for (encoding, input) in cases {
+            // Byte by byte as well, so every step is retried after running out of input
+            for chunk in [usize::MAX, 1] {
+                let out = decompress(encoding, &hex(input), chunk).await.unwrap();
+                assert_eq!(out, HELLO, "{input} in chunks of {chunk}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dynamic_block() {
+        for chunk in [usize::MAX, 7] {
+            let out = decompress(ContentEncoding::Gzip, &hex(FOX_GZIP), chunk)
+                .await
+                .unwrap();
+            assert_eq!(out, fox_lines());
+        }
+    }
+
+    #[tokio::test]
+    async fn large_stored_blocks() {
+        // Larger than MAX_PENDING and the window, so output is drained and trimmed
+        let data: Vec<u8> = (0..700_000u32).map(|i| (i % 251) as u8).collect();
+        let out = decompress(ContentEncoding::Gzip, &stored_gzip(&data), 10_000)
+            .await
+            .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[tokio::test]
+    async fn concatenated_gzip_members() {
+        let mut input = hex(HELLO_GZIP);
+        input.extend(hex(SECOND_GZIP));
+        let out = decompress(ContentEncoding::Gzip, &input, 5).await.unwrap();
+        assert_eq!(out, b"hello, world\nsecond member\n");
+    }
+
+    #[tokio::test]
+    async fn empty_body() {
+        let out = decompress(ContentEncoding::Gzip, b"", 1).await.unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn truncated_input() {
+        let cases = [
+            (ContentEncoding::Gzip, HELLO_GZIP),
+            (ContentEncoding::Deflate, HELLO_ZLIB),
+            (ContentEncoding::Gzip, FOX_GZIP),
+        ];
+        for (encoding, input) in cases {
+            let input = hex(input);
+            for len in [1, 10, input.len() / 2, input.len() - 1] {
+                let err = decompress(encoding, &input[..len], 3).await.unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "cut at {len}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupt_checksums() {
+        let mut gzip = hex(HELLO_GZIP);
+        let crc = gzip.len() - 8;
+        gzip[crc] ^= 1;
+        let err = decompress(ContentEncoding::Gzip, &gzip, usize::MAX)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("CRC-32 mismatch"), "{err}");
+
+        let mut zlib = hex(HELLO_ZLIB);
+        *zlib.last_mut().unwrap() ^= 1;
+        let err = decompress(ContentEncoding::Deflate, &zlib, usize::MAX)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Adler-32 mismatch"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn corrupt_data() {
+        let err = decompress(ContentEncoding::Gzip, b"not gzip at all", usize::MAX)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A block of the reserved type 3
+        let mut gzip = hex(HELLO_GZIP);
+        gzip[10] |= 0b110;
+        let err = decompress(ContentEncoding::Gzip, &gzip, usize::MAX)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid block type"), "{err}");
+    }
+}
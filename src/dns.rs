@@ -0,0 +1,64 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::net::lookup_host;
+use tracing::trace;
+
+/// Resolved addresses of one host and when they were looked up.
+struct CacheEntry {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+/// DNS resolver that caches OS lookups for a fixed TTL (`--dns-cache-ttl`).
+///
+/// The system resolver does not expose record TTLs, so every entry lives for the
+/// configured duration; the first new connection after it expires re-resolves the host.
+/// Connections already open (or pooled) keep the address they were made with.
+pub struct CachingResolver {
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+}
+
+impl CachingResolver {
+    /// Creates a resolver whose entries expire after `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let ttl = self.ttl;
+        let cache = self.cache.clone();
+
+        Box::pin(async move {
+            if let Some(entry) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&host)
+                && entry.resolved_at.elapsed() < ttl
+            {
+                trace!(host = %host, addrs = ?entry.addrs, "DNS cache hit");
+                return Ok(Box::new(entry.addrs.clone().into_iter()) as Addrs);
+            }
+
+            // Port 0 is replaced by reqwest with the URL's port
+            let addrs: Vec<SocketAddr> = lookup_host((host.as_str(), 0)).await?.collect();
+            trace!(host = %host, addrs = ?addrs, ttl_secs = ttl.as_secs(), "DNS resolved");
+            cache.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                host,
+                CacheEntry {
+                    addrs: addrs.clone(),
+                    resolved_at: Instant::now(),
+                },
+            );
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -1,24 +1,37 @@
 use futures::stream::{self, StreamExt};
 use indicatif::ProgressBar;
-use reqwest::{Client, header::RANGE};
+use reqwest::{Client, Response, StatusCode};
 use std::{
     path::Path,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicU64, Ordering},
     },
     time::Duration,
 };
 use tokio::{
-    fs::{self, File},
-    io::AsyncWriteExt,
+    fs::{self, File, OpenOptions},
+    io::{AsyncWrite, AsyncWriteExt},
     time::sleep,
 };
-use tracing::{debug, error, instrument, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, warn};
 
+use crate::client_trait::HttpClient;
+use crate::decompress::{ContentEncoding, DecompressWriter};
 use crate::error::ProgramError;
-use crate::progress::style_download_bar;
-use crate::types::Part;
+use crate::http::serialize_headers;
+use crate::progress::{new_download_progress, new_progress_bar, style_download_bar};
+use crate::throttle::{TokenBucket, throttle};
+use crate::types::{
+    HashAlgorithm, HeadersFormat, OutputMode, ParallelOutcome, Part, PartialResult, PostBody,
+    ProgressOptions, RetryPolicy,
+};
+use crate::utils::open_output_file;
+use crate::verify::{HashAccumulator, update_from_file};
+
+/// Fixed delay between connection attempts of one part request.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_secs(1);
 
 /// Performs a single-threaded download for the entire file.
 ///
@@ -31,74 +44,263 @@ use crate::types::Part;
 /// * `url` - The URL of the file to download.
 /// * `output` - The path where the downloaded file should be saved.
 /// * `total_size` - The total size of the file in bytes (used for the progress bar).
+///   `0` means unknown (e.g. chunked encoding) and shows a spinner with the received bytes.
+/// * `progress` - Whether to draw the progress bar, and in which units.
+/// * `post` - Body to POST instead of sending a GET request.
+/// * `bucket` - Rate limiter shared with other downloads (`--rate-limit`).
+/// * `mode` - How the output file is opened (`--output-mode`).
+/// * `decompress` - Undo a gzip or deflate `Content-Encoding` while writing (`--decompress`).
+/// * `headers` - Write the response headers in this format before the data (`--append-headers`).
 ///
 /// # Returns
 ///
-/// * `Ok(())` if the download completes successfully.
+/// * `Ok(u64)` with the number of bytes written if the download completes successfully.
 /// * `Err(ProgramError)` if an HTTP or I/O error occurs.
-#[instrument(skip(client), fields(url = %url, output = ?output))]
-pub async fn single_download(
-    client: &Client,
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(client, post, bucket), fields(url = %url, output = ?output))]
+pub async fn single_download<C: HttpClient>(
+    client: &C,
     url: &str,
     output: &Path,
     total_size: u64,
-) -> Result<(), ProgramError> {
+    progress: ProgressOptions,
+    post: Option<&PostBody>,
+    bucket: Option<&Mutex<TokenBucket>>,
+    mode: OutputMode,
+    decompress: bool,
+    headers: Option<HeadersFormat>,
+) -> Result<u64, ProgramError> {
     debug!("Starting single download");
 
-    let resp = client.get(url).send().await?;
-    if !resp.status().is_success() {
-        return Err(ProgramError::Other(format!(
-            "GET failed with status {}",
-            resp.status()
-        )));
+    let resp = match post {
+        Some(body) => client.post(url, body).await?,
+        None => client.get(url, None).await?,
+    };
+    check_status(&resp, url, false)?;
+
+    let pb = new_download_progress(total_size, progress);
+
+    let encoding = if decompress {
+        ContentEncoding::for_response(&resp)?
+    } else {
+        None
+    };
+    let on_chunk = |chunk: &[u8]| pb.inc(chunk.len() as u64);
+
+    let mut out = open_output_file(output, mode).await?;
+    if let Some(format) = headers {
+        let serialized = serialize_headers(&resp, format);
+        debug!(format = ?format, bytes = serialized.len(), "Writing response headers");
+        out.write_all(&serialized).await?;
     }
+    let written = match encoding {
+        Some(encoding) => {
+            let mut out = DecompressWriter::new(out, encoding);
+            let written = write_body(resp, &mut out, bucket, None, on_chunk).await?;
+            out.shutdown().await?;
+            info!(
+                encoding = encoding.name(),
+                received = written,
+                decompressed = out.total_out(),
+                "Response body decompressed"
+            );
+            written
+        }
+        None => write_body(resp, &mut out, bucket, None, on_chunk).await?,
+    };
+
+    pb.finish_with_message("Download completed");
+    Ok(written)
+}
+
+/// Downloads a URL, or only the inclusive byte `range` of it, into any async writer.
+///
+/// This is the streaming primitive behind the file-based download functions and can
+/// write to a pipe, a socket or any other `AsyncWrite` sink.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client to use for the request.
+/// * `url` - The URL to download.
+/// * `range` - Inclusive `(start, end)` byte range; the server must answer 206.
+/// * `writer` - Destination of the response body.
+/// * `pb` - Progress bar advanced by the number of bytes written.
+/// * `bucket` - Rate limiter shared with other downloads (`--rate-limit`).
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The number of bytes written.
+/// * `Err(ProgramError::HttpStatus)` - If the server does not answer 206 for a range
+///   request, or with a success status otherwise.
+#[instrument(skip(client, writer, pb, bucket), fields(url = %url, range = ?range))]
+pub async fn download_to_writer<C: HttpClient>(
+    client: &C,
+    url: &str,
+    range: Option<(u64, u64)>,
+    writer: &mut (dyn AsyncWrite + Unpin),
+    pb: Option<&ProgressBar>,
+    bucket: Option<&Mutex<TokenBucket>>,
+) -> Result<u64, ProgramError> {
+    let resp = client.get(url, range).await?;
+    check_status(&resp, url, range.is_some())?;
+
+    write_body(resp, writer, bucket, None, |chunk| {
+        if let Some(pb) = pb {
+            pb.inc(chunk.len() as u64);
+        }
+    })
+    .await
+}
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(style_download_bar());
-    pb.set_message("Downloading");
+/// Helper to reject unexpected response codes: 206 for range requests, 2xx otherwise.
+fn check_status(resp: &Response, url: &str, partial: bool) -> Result<(), ProgramError> {
+    let status = resp.status();
+    let ok = if partial {
+        status == StatusCode::PARTIAL_CONTENT
+    } else {
+        status.is_success()
+    };
+    if !ok {
+        return Err(ProgramError::HttpStatus {
+            code: status.as_u16(),
+            url: url.to_string(),
+        });
+    }
+    Ok(())
+}
 
-    let mut out = File::create(output).await?;
+/// Streams a response body into `writer`, calling `on_chunk` for every chunk written.
+///
+/// Waits after each chunk when a token bucket is given, and flushes the writer at the end.
+/// When `cancel` fires, the chunk being written is finished and the writer flushed
+/// before returning [`cancelled`].
+async fn write_body(
+    resp: Response,
+    writer: &mut (dyn AsyncWrite + Unpin),
+    bucket: Option<&Mutex<TokenBucket>>,
+    cancel: Option<&CancellationToken>,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> Result<u64, ProgramError> {
     let mut stream = resp.bytes_stream();
+    let mut written = 0;
 
-    while let Some(chunk_result) = stream.next().await {
+    loop {
+        let next = match cancel {
+            Some(cancel) => match cancel.run_until_cancelled(stream.next()).await {
+                Some(next) => next,
+                None => {
+                    writer.flush().await?;
+                    return Err(cancelled());
+                }
+            },
+            None => stream.next().await,
+        };
+        let Some(chunk_result) = next else {
+            break;
+        };
         let chunk = chunk_result?;
-        out.write_all(&chunk).await?;
-        pb.inc(chunk.len() as u64);
+        writer.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        on_chunk(&chunk);
+        if let Some(bucket) = bucket {
+            throttle(bucket, chunk.len() as u64).await;
+        }
     }
+    writer.flush().await?;
 
-    pb.finish_with_message("Download completed");
-    Ok(())
+    Ok(written)
+}
+
+/// Error returned by work stopped through a [`CancellationToken`].
+fn cancelled() -> ProgramError {
+    ProgramError::Other("download cancelled".to_string())
 }
 
 /// Orchestrates the parallel download of multiple file parts.
 ///
+/// Equivalent to [`download_parts_parallel_with_cancel`] with a token that is never
+/// cancelled and all parts running at once.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Part>)` with the parts (in completion order) and the hash of the data
+///   received for each of them, if all parts are downloaded successfully.
+/// * `Err(ProgramError)` if any part fails after all retries.
+pub async fn download_parts_parallel(
+    client: Client,
+    url: String,
+    parts: Vec<Part>,
+    total_size: u64,
+    retry: &RetryPolicy,
+    progress: ProgressOptions,
+    bucket: Option<Arc<Mutex<TokenBucket>>>,
+) -> Result<Vec<Part>, ProgramError> {
+    let cancel = CancellationToken::new();
+    let max_concurrent = parts.len();
+    match download_parts_parallel_with_cancel(
+        client,
+        url,
+        parts,
+        total_size,
+        retry,
+        progress,
+        bucket,
+        max_concurrent,
+        &cancel,
+    )
+    .await?
+    {
+        ParallelOutcome::Completed(parts) => Ok(parts),
+        ParallelOutcome::Cancelled(_) => Err(cancelled()),
+    }
+}
+
+/// Orchestrates the parallel download of multiple file parts, stopping when `cancel` fires.
+///
 /// This function manages the concurrent download of file chunks. It initializes a shared
 /// progress bar and spawns a background monitor task to update it. It then launches
 /// asynchronous tasks for each part, handling retries internally.
+/// With `retry.fail_fast`, the first failed part cancels the others. The part files
+/// are kept either way, so a later run resumes the parts that completed.
+///
+/// On cancellation every task finishes the chunk it is writing, records its part as
+/// completed or interrupted in a shared [`PartialResult`] and stops. Part files are kept,
+/// so a later run can skip the completed parts.
 ///
 /// # Arguments
 ///
 /// * `client` - The HTTP client (cloned for each task).
-/// * `url` - The URL of the file.
+/// * `url` - The URL of the file, for logging; each part is fetched from its [`Part::url`].
 /// * `parts` - A vector of `Part` structs defining the ranges to download.
 /// * `total_size` - The total size of the file (for progress display).
-/// * `max_retries` - Maximum number of retry attempts per part.
-/// * `retry_delay_ms` - Base delay in milliseconds for exponential backoff.
+/// * `retry` - Per-part retry settings.
+/// * `progress` - Whether to draw the progress bar, and in which units.
+/// * `bucket` - Rate limiter shared by all parts (`--rate-limit`).
+/// * `max_concurrent` - Parts downloaded at once; the rest wait for a free slot. Each
+///   running part holds one response (and its buffered chunks), so this bounds memory use.
+/// * `cancel` - Token stopping the download (e.g. on SIGINT).
 ///
 /// # Returns
 ///
-/// * `Ok(())` if all parts are downloaded successfully.
+/// * `Ok(ParallelOutcome::Completed)` if all parts are downloaded successfully.
+/// * `Ok(ParallelOutcome::Cancelled)` with the completed and unfinished parts if `cancel` fired.
 /// * `Err(ProgramError)` if any part fails after all retries.
-#[instrument(skip(client, parts), fields(url = %url, num_parts = parts.len()))]
-pub async fn download_parts_parallel(
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(client, parts, retry, bucket, cancel), fields(url = %url, num_parts = parts.len()))]
+pub async fn download_parts_parallel_with_cancel(
     client: Client,
     url: String,
     parts: Vec<Part>,
     total_size: u64,
-    max_retries: u32,
-    retry_delay_ms: u64,
-) -> Result<(), ProgramError> {
+    retry: &RetryPolicy,
+    progress: ProgressOptions,
+    bucket: Option<Arc<Mutex<TokenBucket>>>,
+    max_concurrent: usize,
+    cancel: &CancellationToken,
+) -> Result<ParallelOutcome, ProgramError> {
     let num_parts = parts.len();
+    let max_concurrent = max_concurrent.clamp(1, num_parts.max(1));
+    debug!(max_concurrent = max_concurrent, "Starting part downloads");
 
     // Shared state for tracking progress of each part to allow "rewinding" on retry
     let part_progress = Arc::new(
@@ -106,9 +308,12 @@ pub async fn download_parts_parallel(
             .map(|_| AtomicU64::new(0))
             .collect::<Vec<_>>(),
     );
+    let partial = Arc::new(Mutex::new(PartialResult::default()));
+    // Also fired by the first failed part with fail-fast, without marking a cancellation
+    let abort = cancel.child_token();
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(style_download_bar());
+    let pb = new_progress_bar(total_size, progress.show);
+    pb.set_style(style_download_bar(progress.use_si));
     pb.set_message("Downloading parallel");
 
     // Spawn a background task to update the progress bar from the atomic counters
@@ -135,80 +340,156 @@ pub async fn download_parts_parallel(
         }
     });
 
-    let results: Vec<Result<(), ProgramError>> = stream::iter(parts)
-        .map(|part| {
+    // Counters are indexed by position, as parts may be a subset of the split (`--output-part`)
+    let results: Vec<Result<Part, ProgramError>> = stream::iter(parts.iter().enumerate())
+        .map(|(slot, part)| {
             let client = client.clone();
-            let url = url.clone();
             let counters = part_progress.clone();
+            let bucket = bucket.clone();
+            let partial = partial.clone();
+            let abort = &abort;
             async move {
-                download_one_part_with_retry(
+                let result = download_one_part_with_retry(
                     &client,
-                    &url,
-                    &part,
-                    &counters,
-                    max_retries,
-                    retry_delay_ms,
+                    part,
+                    &counters[slot],
+                    retry,
+                    bucket.as_deref(),
+                    abort,
                 )
-                .await
+                .await;
+
+                let mut state = partial.lock().unwrap_or_else(|e| e.into_inner());
+                match &result {
+                    Ok(_) => state.completed_parts.push(part.idx),
+                    Err(_) if cancel.is_cancelled() => state.interrupted_parts.push(part.idx),
+                    Err(_) => {
+                        state.failed_parts.push(part.idx);
+                        if retry.fail_fast && !abort.is_cancelled() {
+                            debug!(part = part.idx, "Part failed, aborting remaining parts");
+                            abort.cancel();
+                        }
+                    }
+                }
+                result
             }
         })
-        .buffer_unordered(num_parts)
+        .buffer_unordered(max_concurrent)
         .collect()
         .await;
-
-    // Stop monitor
     monitor_handle.abort();
 
-    // Check results
+    if cancel.is_cancelled() {
+        let mut partial = std::mem::take(&mut *partial.lock().unwrap_or_else(|e| e.into_inner()));
+        partial.completed_parts.sort_unstable();
+        partial.interrupted_parts.sort_unstable();
+        partial.failed_parts.sort_unstable();
+        partial.bytes_downloaded = part_progress
+            .iter()
+            .map(|a| a.load(Ordering::Relaxed))
+            .sum();
+        pb.abandon_with_message("Download cancelled");
+        return Ok(ParallelOutcome::Cancelled(partial));
+    }
+
+    // Report the part that failed first rather than the ones it aborted
+    let mut downloaded = Vec::with_capacity(num_parts);
+    let mut first_error = None;
     for result in results {
-        result?;
+        match result {
+            Ok(part) => downloaded.push(part),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if let Some(e) = first_error {
+        return Err(e);
     }
 
     pb.finish_with_message("Download completed");
-    Ok(())
+    Ok(ParallelOutcome::Completed(downloaded))
 }
 
 /// Downloads a single part with automatic retries and exponential backoff.
 ///
-/// If a download fails, it waits for a delay (exponentially increasing) and retries.
-/// It also resets the progress counter for this part and deletes the partial file
-/// before retrying to ensure data integrity.
+/// If a download fails, it waits for a delay (doubling each time, up to
+/// `retry.retry_delay_max_ms`) and retries.
+/// Each retry continues from the bytes already in the part file (see [`download_one_part`]).
 ///
 /// # Arguments
 ///
 /// * `client` - The HTTP client.
-/// * `url` - The URL.
-/// * `part` - The specific part to download.
-/// * `counters` - Shared atomic counters for progress tracking.
-/// * `max_retries` - Maximum retry attempts.
-/// * `retry_delay_ms` - Base retry delay.
-#[instrument(skip(client, counters), fields(part = part.idx))]
-async fn download_one_part_with_retry(
-    client: &Client,
-    url: &str,
+/// * `part` - The specific part to download, from [`Part::url`].
+/// * `counter` - Shared atomic progress counter of this part.
+/// * `retry` - Retry settings. HTTP error codes outside `retry.retry_on` fail immediately;
+///   up to `retry.max_timeouts_per_part` timeouts are retried without using an attempt.
+/// * `bucket` - Shared rate limiter, if any.
+/// * `cancel` - Stops the current attempt and any further retries.
+///
+/// # Returns
+///
+/// * `Ok(Part)` - The part with its hash and resume marker, see [`download_one_part`].
+#[instrument(skip(client, counter, retry, bucket, cancel), fields(part = part.idx))]
+async fn download_one_part_with_retry<C: HttpClient>(
+    client: &C,
     part: &Part,
-    counters: &[AtomicU64],
-    max_retries: u32,
-    retry_delay_ms: u64,
-) -> Result<(), ProgramError> {
+    counter: &AtomicU64,
+    retry: &RetryPolicy,
+    bucket: Option<&Mutex<TokenBucket>>,
+    cancel: &CancellationToken,
+) -> Result<Part, ProgramError> {
+    let RetryPolicy {
+        max_retries,
+        retry_delay_ms,
+        retry_delay_max_ms,
+        max_timeouts_per_part,
+        ref retry_on,
+        ..
+    } = *retry;
     let mut last_error = ProgramError::Other("no attempts made".to_string());
+    let mut attempt = 1;
+    let mut timeouts = 0;
 
-    for attempt in 1..=max_retries {
-        if attempt > 1 {
-            debug!(attempt = attempt, "Retrying part download");
-            // Reset progress for this part
-            counters[part.idx].store(0, Ordering::Relaxed);
-            let _ = fs::remove_file(&part.path).await;
+    while attempt <= max_retries {
+        if attempt > 1 || timeouts > 0 {
+            debug!(
+                attempt = attempt,
+                timeouts = timeouts,
+                "Retrying part download"
+            );
         }
 
-        match download_one_part(client, url, part, counters).await {
-            Ok(()) => return Ok(()),
+        match download_one_part(client, part, counter, retry, bucket, cancel).await {
+            Ok(downloaded) => return Ok(downloaded),
+            Err(e) if cancel.is_cancelled() => {
+                debug!(part = part.idx, "Part interrupted");
+                return Err(e);
+            }
+            // Timeouts are usually transient (e.g. a stalled connection) and retried right away
+            Err(e @ ProgramError::Timeout { .. }) if timeouts < max_timeouts_per_part => {
+                timeouts += 1;
+                warn!(
+                    part = part.idx,
+                    timeouts = timeouts,
+                    error = %e,
+                    "Part timed out, retrying without using a retry attempt"
+                );
+                last_error = e;
+            }
             Err(e) => {
                 last_error = e;
                 if attempt >= max_retries {
                     break;
                 }
-                let backoff = retry_delay_ms * 2u64.pow(attempt - 1);
+                if let ProgramError::HttpStatus { code, .. } = last_error
+                    && code >= 400
+                    && !retry_on.contains(&code)
+                {
+                    debug!(code = code, "HTTP status not in retry list, giving up");
+                    break;
+                }
+                let backoff = backoff_ms(attempt, retry_delay_ms, retry_delay_max_ms);
 
                 // Only log warn if it's not the final failure
                 warn!(
@@ -217,7 +498,14 @@ async fn download_one_part_with_retry(
                     error = %last_error,
                     "Part failed, retrying in {}ms", backoff
                 );
-                sleep(Duration::from_millis(backoff)).await;
+                if cancel
+                    .run_until_cancelled(sleep(Duration::from_millis(backoff)))
+                    .await
+                    .is_none()
+                {
+                    return Err(cancelled());
+                }
+                attempt += 1;
             }
         }
     }
@@ -226,52 +514,142 @@ async fn download_one_part_with_retry(
     Err(last_error)
 }
 
-/// Executes the HTTP Range request and streams data to a file for a single part.
+/// Delay before retrying after failed attempt `attempt` (1-based): `base_ms` doubled for
+/// each earlier attempt, capped at `max_ms` (`--retry-delay-max`).
+fn backoff_ms(attempt: u32, base_ms: u64, max_ms: u64) -> u64 {
+    let uncapped = base_ms.saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)));
+    let backoff = uncapped.min(max_ms);
+    if backoff < uncapped {
+        debug!("backoff clamped from {}ms to {}ms", uncapped, backoff);
+    }
+    backoff
+}
+
+/// Turns a reqwest connect or read timeout of a part into [`ProgramError::Timeout`].
+fn part_timeout(e: ProgramError, part: &Part, retry: &RetryPolicy) -> ProgramError {
+    match e {
+        ProgramError::Http(ref err) if err.is_timeout() => {
+            let (operation, duration) = if err.is_connect() {
+                ("connect", retry.connect_timeout)
+            } else {
+                ("read", retry.read_timeout)
+            };
+            ProgramError::Timeout {
+                operation: format!("part {} {}", part.idx, operation),
+                duration,
+            }
+        }
+        e => e,
+    }
+}
+
+/// Sends the Range request for bytes `start..=part.end_inclusive` of a part, retrying
+/// only failures to connect.
 ///
-/// Updates the shared atomic counter as bytes are received.
-/// Verifies the final file size against the expected size.
-#[instrument(skip(client, counters), fields(part = part.idx))]
-async fn download_one_part(
-    client: &Client,
+/// Connection errors are retried up to `max_attempts` times (at least once) with a
+/// fixed [`CONNECT_RETRY_DELAY`]; any other error, or the last connection error, is
+/// returned to the part-level retry in [`download_one_part_with_retry`].
+async fn get_part_with_connect_retry<C: HttpClient>(
+    client: &C,
     url: &str,
     part: &Part,
-    counters: &[AtomicU64],
-) -> Result<(), ProgramError> {
-    let expected = part.end_inclusive - part.start + 1;
+    start: u64,
+    max_attempts: u32,
+) -> Result<Response, ProgramError> {
+    let max_attempts = max_attempts.max(1);
+    let mut attempt = 1;
 
-    // Resume check
-    if let Ok(meta) = fs::metadata(&part.path).await
-        && meta.len() == expected
-    {
-        debug!(part = part.idx, "Part already complete, skipping");
-        counters[part.idx].store(expected, Ordering::Relaxed);
-        return Ok(());
+    loop {
+        debug!(part = part.idx, attempt = attempt, "Connection attempt");
+        match client.get(url, Some((start, part.end_inclusive))).await {
+            Err(ProgramError::Http(e)) if e.is_connect() && attempt < max_attempts => {
+                debug!(
+                    part = part.idx,
+                    attempt = attempt,
+                    error = %e,
+                    "Connection failed, retrying in {}ms",
+                    CONNECT_RETRY_DELAY.as_millis()
+                );
+                sleep(CONNECT_RETRY_DELAY).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
     }
+}
 
-    let range = format!("bytes={}-{}", part.start, part.end_inclusive);
-    let resp = client.get(url).header(RANGE, range).send().await?;
+/// Executes the HTTP Range request to [`Part::url`] and streams data to a file for a single part.
+///
+/// A part file shorter than the part is resumed: only the missing bytes are requested
+/// and appended, and the counter starts at the size already on disk. A part file of
+/// the full size is kept as is; a longer one is downloaded again. Callers make sure the
+/// file belongs to this part and remote file first (see [`crate::part::prepare_resume`]).
+/// Updates the shared atomic counter as bytes are received.
+/// Verifies the final file size against the expected size and returns the part with
+/// [`Part::expected_hash`] set to the hex SHA-256 of its data (`None` if the part was
+/// already complete on disk) and [`Part::resumed`] set if a partial file was appended to.
+/// When a token bucket is given, waits after each chunk to honor the rate limit.
+/// Stops after the current chunk once `cancel` fires.
+#[instrument(skip(client, counter, retry, bucket, cancel), fields(part = part.idx))]
+async fn download_one_part<C: HttpClient>(
+    client: &C,
+    part: &Part,
+    counter: &AtomicU64,
+    retry: &RetryPolicy,
+    bucket: Option<&Mutex<TokenBucket>>,
+    cancel: &CancellationToken,
+) -> Result<Part, ProgramError> {
+    let expected = part.expected_size();
 
-    if resp.status().as_u16() != 206 {
-        return Err(ProgramError::Other(format!(
-            "Expected 206 Partial Content, got {}",
-            resp.status()
-        )));
-    }
+    // Resume check
+    let existing = match fs::metadata(&part.path).await {
+        Ok(meta) if meta.len() == expected => {
+            debug!(part = part.idx, "Part already complete, skipping");
+            counter.store(expected, Ordering::Relaxed);
+            return Ok(part.clone());
+        }
+        Ok(meta) if meta.len() < expected => meta.len(),
+        _ => 0,
+    };
 
-    let mut file = File::create(&part.path).await?;
-    let mut stream = resp.bytes_stream();
+    let resp = cancel
+        .run_until_cancelled(get_part_with_connect_retry(
+            client,
+            &part.url,
+            part,
+            part.start + existing,
+            retry.max_connection_attempts,
+        ))
+        .await
+        .ok_or_else(cancelled)?
+        .map_err(|e| part_timeout(e, part, retry))?;
 
-    let mut downloaded_so_far = 0;
+    check_status(&resp, &part.url, true)?;
 
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result?;
-        file.write_all(&chunk).await?;
+    let mut hasher = HashAccumulator::new(HashAlgorithm::Sha256);
+    let mut file = if existing > 0 {
+        debug!(
+            part = part.idx,
+            existing = existing,
+            remaining = expected - existing,
+            "Resuming partial part"
+        );
+        update_from_file(&part.path, std::slice::from_mut(&mut hasher)).await?;
+        OpenOptions::new().append(true).open(&part.path).await?
+    } else {
+        File::create(&part.path).await?
+    };
+    let mut downloaded_so_far = existing;
+    counter.store(existing, Ordering::Relaxed);
 
+    write_body(resp, &mut file, bucket, Some(cancel), |chunk| {
+        hasher.update(chunk);
         downloaded_so_far += chunk.len() as u64;
         // Update the atomic counter for this part
-        counters[part.idx].store(downloaded_so_far, Ordering::Relaxed);
-    }
-    file.flush().await?;
+        counter.store(downloaded_so_far, Ordering::Relaxed);
+    })
+    .await
+    .map_err(|e| part_timeout(e, part, retry))?;
 
     let got = fs::metadata(&part.path).await?.len();
     if got != expected {
@@ -281,5 +659,507 @@ async fn download_one_part(
         )));
     }
 
-    Ok(())
+    Ok(Part {
+        expected_hash: Some(hasher.finish_hex()),
+        resumed: existing > 0,
+        ..part.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_trait::{MockHttpClient, MockResponse};
+    use crate::part::split_into_parts;
+    use crate::test_server::{TestDir, TestServer, serve_file};
+    use crate::types::PartSizeStrategy;
+
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            retry_delay_ms: 10,
+            retry_delay_max_ms: 10,
+            max_connection_attempts: 1,
+            max_timeouts_per_part: 0,
+            connect_timeout: None,
+            read_timeout: None,
+            retry_on: Vec::new(),
+            fail_fast: true,
+        }
+    }
+
+    const HIDDEN: ProgressOptions = ProgressOptions {
+        show: false,
+        use_si: false,
+    };
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn part(url: &str, dir: &TestDir, start: u64, end_inclusive: u64) -> Part {
+        Part {
+            idx: 0,
+            start,
+            end_inclusive,
+            path: dir.join("out.bin.part0"),
+            url: url.to_string(),
+            expected_hash: None,
+            resumed: false,
+        }
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        let mut hasher = HashAccumulator::new(HashAlgorithm::Sha256);
+        hasher.update(data);
+        hasher.finish_hex()
+    }
+
+    #[tokio::test]
+    async fn partial_part_file_is_resumed() {
+        let content = data(1000);
+        let server = TestServer::start(serve_file(content.clone(), &[])).await;
+        let dir = TestDir::new("resume");
+        let part = part(&server.url, &dir, 200, 599);
+        std::fs::write(&part.path, &content[200..350]).unwrap();
+
+        let counter = AtomicU64::new(0);
+        let downloaded = download_one_part(
+            &client(),
+            &part,
+            &counter,
+            &retry_policy(),
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(server.requests()[0].range(), Some((350, 599)));
+        assert_eq!(std::fs::read(&part.path).unwrap(), &content[200..600]);
+        assert_eq!(downloaded.expected_hash, Some(sha256(&content[200..600])));
+        assert!(downloaded.resumed);
+        assert_eq!(counter.load(Ordering::Relaxed), 400);
+    }
+
+    #[tokio::test]
+    async fn complete_part_file_is_not_downloaded_again() {
+        let server = TestServer::start(serve_file(data(1000), &[])).await;
+        let dir = TestDir::new("complete");
+        let part = part(&server.url, &dir, 0, 99);
+        std::fs::write(&part.path, &data(1000)[..100]).unwrap();
+
+        let counter = AtomicU64::new(0);
+        let downloaded = download_one_part(
+            &client(),
+            &part,
+            &counter,
+            &retry_policy(),
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(downloaded.expected_hash, None);
+        assert!(!downloaded.resumed);
+        assert!(server.requests().is_empty());
+        assert_eq!(counter.load(Ordering::Relaxed), 100);
+    }
+
+    #[tokio::test]
+    async fn cancelled_parts_are_interrupted_not_failed() {
+        let server = TestServer::start(serve_file(data(300), &[])).await;
+        let dir = TestDir::new("cancel");
+        let parts = split_into_parts(
+            &server.url,
+            300,
+            3,
+            PartSizeStrategy::Equal,
+            &dir.join("out.bin"),
+            &dir.0,
+            None,
+            "",
+        )
+        .unwrap();
+        std::fs::write(&parts[0].path, &data(300)[..100]).unwrap();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let outcome = download_parts_parallel_with_cancel(
+            client(),
+            server.url.clone(),
+            parts,
+            300,
+            &retry_policy(),
+            HIDDEN,
+            None,
+            3,
+            &cancel,
+        )
+        .await
+        .unwrap();
+
+        let ParallelOutcome::Cancelled(partial) = outcome else {
+            panic!("download was not cancelled");
+        };
+        assert_eq!(partial.completed_parts, [0]);
+        assert_eq!(partial.interrupted_parts, [1, 2]);
+        assert!(partial.failed_parts.is_empty());
+        assert_eq!(partial.bytes_downloaded, 100);
+    }
+
+    #[tokio::test]
+    async fn fail_fast_keeps_completed_parts() {
+        let content = data(400);
+        let files = serve_file(content.clone(), &[]);
+        let server = TestServer::start(move |req| match req.range() {
+            Some((100, _)) => crate::test_server::response(404, &[], b""),
+            _ => files(req),
+        })
+        .await;
+        let dir = TestDir::new("fail-fast");
+        let parts = split_into_parts(
+            &server.url,
+            400,
+            4,
+            PartSizeStrategy::Equal,
+            &dir.join("out.bin"),
+            &dir.0,
+            None,
+            "",
+        )
+        .unwrap();
+
+        let result = download_parts_parallel_with_cancel(
+            client(),
+            server.url.clone(),
+            parts.clone(),
+            400,
+            &retry_policy(),
+            HIDDEN,
+            None,
+            1,
+            &CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(ProgramError::HttpStatus { code: 404, .. })
+        ));
+        assert_eq!(std::fs::read(&parts[0].path).unwrap(), &content[..100]);
+        let ranges: Vec<_> = server.requests().iter().map(|r| r.range()).collect();
+        assert_eq!(ranges, [Some((0, 99)), Some((100, 199))]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn one_concurrent_part_fetches_parts_in_turn() {
+        let content = data(800);
+        let files = serve_file(content.clone(), &[]);
+        // A slow first part lets later requests overtake it if they run at the same time
+        let server = TestServer::start(move |req| {
+            if req.range().is_some_and(|(start, _)| start == 0) {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            files(req)
+        })
+        .await;
+        let dir = TestDir::new("one-at-a-time");
+        let parts = split_into_parts(
+            &server.url,
+            800,
+            8,
+            PartSizeStrategy::Equal,
+            &dir.join("out.bin"),
+            &dir.0,
+            None,
+            "",
+        )
+        .unwrap();
+
+        let outcome = download_parts_parallel_with_cancel(
+            client(),
+            server.url.clone(),
+            parts.clone(),
+            800,
+            &retry_policy(),
+            HIDDEN,
+            None,
+            1,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let ParallelOutcome::Completed(done) = outcome else {
+            panic!("download was cancelled");
+        };
+        let order: Vec<_> = done.iter().map(|p| p.idx).collect();
+        assert_eq!(order, (0..8).collect::<Vec<_>>());
+        let starts: Vec<_> = server
+            .requests()
+            .iter()
+            .filter_map(|r| r.range())
+            .map(|(start, _)| start)
+            .collect();
+        assert_eq!(starts, (0..8).map(|i| i * 100).collect::<Vec<_>>());
+        for part in &parts {
+            let (start, end) = (part.start as usize, part.end_inclusive as usize);
+            assert_eq!(std::fs::read(&part.path).unwrap(), &content[start..=end]);
+        }
+    }
+
+    async fn single(client: &MockHttpClient, output: &Path) -> Result<u64, ProgramError> {
+        single_download(
+            client,
+            "http://mock/file.bin",
+            output,
+            0,
+            HIDDEN,
+            None,
+            None,
+            OutputMode::Create,
+            false,
+            None,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn single_download_saves_200_body() {
+        let dir = TestDir::new("single-200");
+        let client = MockHttpClient::new([MockResponse::new(200, &data(500))]);
+        let written = single(&client, &dir.join("out.bin")).await.unwrap();
+
+        assert_eq!(written, 500);
+        assert_eq!(std::fs::read(dir.join("out.bin")).unwrap(), data(500));
+        assert_eq!(
+            client.requests(),
+            [("http://mock/file.bin".to_string(), None)]
+        );
+    }
+
+    #[tokio::test]
+    async fn single_download_saves_206_body() {
+        let dir = TestDir::new("single-206");
+        let client = MockHttpClient::new([
+            MockResponse::new(206, &data(100)).header("Content-Range", "bytes 0-99/100")
+        ]);
+        let written = single(&client, &dir.join("out.bin")).await.unwrap();
+
+        assert_eq!(written, 100);
+        assert_eq!(std::fs::read(dir.join("out.bin")).unwrap(), data(100));
+    }
+
+    #[tokio::test]
+    async fn single_download_rejects_error_status() {
+        let dir = TestDir::new("single-404");
+        let client = MockHttpClient::new([MockResponse::new(404, b"not found")]);
+        let err = single(&client, &dir.join("out.bin")).await.unwrap_err();
+
+        assert!(
+            matches!(err, ProgramError::HttpStatus { code: 404, .. }),
+            "{err}"
+        );
+        assert!(!dir.join("out.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn failed_part_is_retried() {
+        let dir = TestDir::new("retry");
+        let part = part("http://mock/file.bin", &dir, 0, 99);
+        let client = MockHttpClient::new([
+            MockResponse::new(503, b"").delay(Duration::from_millis(20)),
+            MockResponse::new(206, &data(100)),
+        ]);
+        let retry = RetryPolicy {
+            retry_on: vec![503],
+            ..retry_policy()
+        };
+
+        let counter = AtomicU64::new(0);
+        let downloaded = download_one_part_with_retry(
+            &client,
+            &part,
+            &counter,
+            &retry,
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(downloaded.expected_hash, Some(sha256(&data(100))));
+        let ranges: Vec<_> = client.requests().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(ranges, [Some((0, 99)), Some((0, 99))]);
+    }
+
+    #[tokio::test]
+    async fn short_part_body_is_a_size_mismatch() {
+        let dir = TestDir::new("short");
+        let part = part("http://mock/file.bin", &dir, 0, 99);
+        let client = MockHttpClient::new([MockResponse::new(206, &data(60))]);
+
+        let err = download_one_part(
+            &client,
+            &part,
+            &AtomicU64::new(0),
+            &retry_policy(),
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("expected 100 got 60"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn download_to_writer_streams_the_body() {
+        let client = MockHttpClient::new([MockResponse::new(200, &data(1000))]);
+        let mut out = Vec::new();
+        let pb = ProgressBar::hidden();
+
+        let written = download_to_writer(
+            &client,
+            "http://mock/file.bin",
+            None,
+            &mut out,
+            Some(&pb),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 1000);
+        assert_eq!(out, data(1000));
+        assert_eq!(pb.position(), 1000);
+    }
+
+    #[tokio::test]
+    async fn download_to_writer_needs_206_for_a_range() {
+        let client = MockHttpClient::new([
+            MockResponse::new(206, &data(10)),
+            MockResponse::new(200, &data(100)),
+        ]);
+        let mut out = Vec::new();
+        let range = Some((0, 9));
+
+        download_to_writer(&client, "http://mock/file.bin", range, &mut out, None, None)
+            .await
+            .unwrap();
+        assert_eq!(out, data(10));
+        let err = download_to_writer(&client, "http://mock/file.bin", range, &mut out, None, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ProgramError::HttpStatus { code: 200, .. }),
+            "{err}"
+        );
+        assert_eq!(client.requests()[1].1, range);
+    }
+
+    #[tokio::test]
+    async fn download_to_writer_into_a_pipe() {
+        let client = MockHttpClient::new([MockResponse::new(200, &data(100_000))]);
+        // A small pipe buffer, so the body is written as the reader drains it
+        let (mut writer, mut reader) = tokio::io::duplex(1024);
+        let read = tokio::spawn(async move {
+            let mut out = Vec::new();
+            tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut out)
+                .await
+                .unwrap();
+            out
+        });
+
+        let written = download_to_writer(
+            &client,
+            "http://mock/file.bin",
+            None,
+            &mut writer,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        drop(writer);
+        assert_eq!(written, 100_000);
+        assert_eq!(read.await.unwrap(), data(100_000));
+    }
+
+    #[tokio::test]
+    async fn single_download_of_unknown_length() {
+        // Chunked body without Content-Length, so the probe reports a total size of 0
+        let server = TestServer::start(|_| {
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+              5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n"
+                .to_vec()
+        })
+        .await;
+        let dir = TestDir::new("chunked");
+        let output = dir.join("out.bin");
+
+        let written = single_download(
+            &client(),
+            &server.url,
+            &output,
+            0,
+            HIDDEN,
+            None,
+            None,
+            OutputMode::Create,
+            false,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(std::fs::read(&output).unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let delays: Vec<u64> = (1..=20)
+            .map(|attempt| backoff_ms(attempt, 1000, 5000))
+            .collect();
+        assert_eq!(delays[..4], [1000, 2000, 4000, 5000]);
+        assert!(delays.iter().all(|&d| d <= 5000));
+        assert!(delays[3..].iter().all(|&d| d == 5000));
+
+        // No overflow for large attempts or delays
+        assert_eq!(backoff_ms(100, 1000, 30_000), 30_000);
+        assert_eq!(backoff_ms(2, u64::MAX, u64::MAX), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn single_download_prepends_headers() {
+        let dir = TestDir::new("append-headers");
+        let output = dir.join("out.bin");
+        let client = MockHttpClient::new([
+            MockResponse::new(200, b"data").header("Content-Type", "text/plain")
+        ]);
+
+        let written = single_download(
+            &client,
+            "http://mock/file.bin",
+            &output,
+            4,
+            HIDDEN,
+            None,
+            None,
+            OutputMode::Create,
+            false,
+            Some(HeadersFormat::Httpd),
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 4);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "# content-type: text/plain\n\ndata"
+        );
+    }
 }
@@ -1,5 +1,5 @@
 use reqwest::header::InvalidHeaderValue;
-use std::fmt;
+use std::{fmt, time::Duration};
 
 /// Unified error type for the download program.
 ///
@@ -11,23 +11,90 @@ pub enum ProgramError {
     ArgNotValid(String),
     /// Errors originating from the HTTP client (reqwest).
     Http(reqwest::Error),
+    /// Unexpected HTTP status code in a server response.
+    HttpStatus { code: u16, url: String },
     /// File system or network I/O errors.
     Io(std::io::Error),
+    /// Downloaded file does not match the expected checksum.
+    ChecksumMismatch { expected: String, actual: String },
+    /// An operation (probe, connect, read) exceeded its time limit.
+    ///
+    /// `duration` is `None` when the limit that fired is not known (e.g. a reqwest
+    /// timeout without a configured `--connect-timeout` or `--read-timeout`).
+    Timeout {
+        operation: String,
+        duration: Option<Duration>,
+    },
     /// Generic or miscellaneous errors.
     Other(String),
 }
 
+impl ProgramError {
+    /// Returns the process exit code associated with this error.
+    ///
+    /// | Code | Meaning            |
+    /// |------|--------------------|
+    /// | 1    | Other failure      |
+    /// | 2    | Invalid argument   |
+    /// | 3    | HTTP error         |
+    /// | 4    | I/O error          |
+    /// | 5    | Checksum mismatch  |
+    /// | 7    | Timeout            |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ProgramError::Other(_) => 1,
+            ProgramError::ArgNotValid(_) => 2,
+            ProgramError::Http(_) | ProgramError::HttpStatus { .. } => 3,
+            ProgramError::Io(_) => 4,
+            ProgramError::ChecksumMismatch { .. } => 5,
+            ProgramError::Timeout { .. } => 7,
+        }
+    }
+}
+
 impl fmt::Display for ProgramError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ProgramError::ArgNotValid(msg) => write!(f, "invalid argument: {}", msg),
             ProgramError::Http(e) => write!(f, "HTTP error: {}", e),
+            ProgramError::HttpStatus { code, url } => write!(f, "HTTP {} from {}", code, url),
             ProgramError::Io(e) => write!(f, "IO error: {}", e),
+            ProgramError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {}, got {}",
+                expected, actual
+            ),
+            ProgramError::Timeout {
+                operation,
+                duration: Some(duration),
+            } => write!(
+                f,
+                "operation '{}' timed out after {:?}",
+                operation, duration
+            ),
+            ProgramError::Timeout {
+                operation,
+                duration: None,
+            } => write!(f, "operation '{}' timed out", operation),
             ProgramError::Other(msg) => write!(f, "{}", msg),
         }
     }
 }
 
+impl std::error::Error for ProgramError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProgramError::Http(e) => Some(e),
+            ProgramError::Io(e) => Some(e),
+            ProgramError::ArgNotValid(_)
+            | ProgramError::HttpStatus { .. }
+            | ProgramError::ChecksumMismatch { .. }
+            | ProgramError::Timeout { .. }
+            | ProgramError::Other(_) => None,
+        }
+    }
+}
+
 impl From<InvalidHeaderValue> for ProgramError {
     fn from(msg: InvalidHeaderValue) -> Self {
         ProgramError::ArgNotValid(msg.to_string())
@@ -45,3 +112,63 @@ impl From<std::io::Error> for ProgramError {
         ProgramError::Io(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_match_the_documented_table() {
+        let http = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let cases = [
+            (ProgramError::Other("failed".to_string()), 1),
+            (ProgramError::ArgNotValid("--threads".to_string()), 2),
+            (ProgramError::Http(http), 3),
+            (
+                ProgramError::HttpStatus {
+                    code: 404,
+                    url: "http://example.com/".to_string(),
+                },
+                3,
+            ),
+            (ProgramError::Io(std::io::Error::other("disk full")), 4),
+            (
+                ProgramError::ChecksumMismatch {
+                    expected: "aa".to_string(),
+                    actual: "bb".to_string(),
+                },
+                5,
+            ),
+            (
+                ProgramError::Timeout {
+                    operation: "probe".to_string(),
+                    duration: Some(Duration::from_secs(30)),
+                },
+                7,
+            ),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.exit_code(), code, "{err}");
+        }
+    }
+
+    #[test]
+    fn timeouts_show_the_limit_that_fired() {
+        let timeout = |duration| ProgramError::Timeout {
+            operation: "part 2 read".to_string(),
+            duration,
+        };
+        assert_eq!(
+            timeout(Some(Duration::from_millis(500))).to_string(),
+            "operation 'part 2 read' timed out after 500ms"
+        );
+        assert_eq!(
+            timeout(Some(Duration::from_secs(30))).to_string(),
+            "operation 'part 2 read' timed out after 30s"
+        );
+        assert_eq!(
+            timeout(None).to_string(),
+            "operation 'part 2 read' timed out"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use indicatif::ProgressBar;
+use reqwest::Url;
+use std::path::Path;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tracing::{debug, instrument, trace};
+
+use crate::error::ProgramError;
+use crate::types::OutputMode;
+use crate::utils::open_output_file;
+
+/// Default FTP control port.
+const FTP_PORT: u16 = 21;
+
+/// Read buffer size for the data connection.
+const DATA_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Downloads a file over FTP (passive mode, binary transfer) into `output`.
+///
+/// Credentials are taken from the URL; anonymous login is used otherwise.
+/// FTP transfers are always single-part.
+///
+/// # Arguments
+///
+/// * `url` - An `ftp://` URL.
+/// * `output` - The path where the downloaded file should be saved.
+/// * `pb` - Progress bar; its length is set from the `SIZE` reply when the server supports it.
+/// * `mode` - How the output file is opened (`--output-mode`).
+///
+/// # Returns
+///
+/// * `Ok(())` if the transfer completes.
+/// * `Err(ProgramError::Other)` if the server rejects a command.
+#[instrument(skip(pb), fields(output = ?output))]
+pub async fn ftp_download(
+    url: &str,
+    output: &Path,
+    pb: &ProgressBar,
+    mode: OutputMode,
+) -> Result<(), ProgramError> {
+    let url = Url::parse(url)
+        .map_err(|e| ProgramError::ArgNotValid(format!("invalid FTP URL {}: {}", url, e)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| ProgramError::ArgNotValid(format!("FTP URL has no host: {}", url)))?;
+    let port = url.port().unwrap_or(FTP_PORT);
+    let user = match url.username() {
+        "" => "anonymous",
+        user => user,
+    };
+    let password = url.password().unwrap_or("anonymous@");
+
+    debug!(host = %host, port = port, user = %user, "Connecting to FTP server");
+    let mut control = FtpControl {
+        stream: BufReader::new(TcpStream::connect((host, port)).await?),
+    };
+    control.expect(&[220]).await?;
+
+    if control
+        .command(&format!("USER {}", user), &[230, 331])
+        .await?
+        == 331
+    {
+        control
+            .command(&format!("PASS {}", password), &[230, 202])
+            .await?;
+    }
+    control.command("TYPE I", &[200]).await?;
+
+    let path = url.path();
+    // SIZE is an extension (RFC 3659); without it the bar has no length
+    if let Ok((213, size)) = control.send(&format!("SIZE {}", path)).await
+        && let Ok(size) = size.trim().parse::<u64>()
+    {
+        debug!(size = size, "FTP file size");
+        pb.set_length(size);
+    }
+
+    let reply = control.command_reply("PASV", &[227]).await?;
+    let data_port = parse_pasv_port(&reply)
+        .ok_or_else(|| ProgramError::Other(format!("malformed FTP PASV reply: {}", reply)))?;
+    // Connect to the control host rather than the address in the reply, which is
+    // often a private address behind NAT
+    debug!(data_port = data_port, "Opening FTP data connection");
+    let mut data = TcpStream::connect((host, data_port)).await?;
+
+    control
+        .command(&format!("RETR {}", path), &[125, 150])
+        .await?;
+
+    let mut out = open_output_file(output, mode).await?;
+    let mut buf = vec![0u8; DATA_BUFFER_SIZE];
+    loop {
+        let n = data.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n]).await?;
+        pb.inc(n as u64);
+    }
+    out.flush().await?;
+    drop(data);
+
+    control.expect(&[226, 250]).await?;
+    let _ = control.send("QUIT").await;
+
+    Ok(())
+}
+
+/// FTP control connection.
+struct FtpControl {
+    stream: BufReader<TcpStream>,
+}
+
+impl FtpControl {
+    /// Sends a command and returns the reply code and text.
+    async fn send(&mut self, command: &str) -> Result<(u16, String), ProgramError> {
+        // Never log the password
+        if command.starts_with("PASS ") {
+            trace!("FTP > PASS [REDACTED]");
+        } else {
+            trace!("FTP > {}", command);
+        }
+        self.stream
+            .get_mut()
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .await?;
+        self.read_reply().await
+    }
+
+    /// Sends a command and fails unless the reply code is one of `expected`.
+    async fn command(&mut self, command: &str, expected: &[u16]) -> Result<u16, ProgramError> {
+        let (code, text) = self.send(command).await?;
+        check_reply(code, &text, expected)?;
+        Ok(code)
+    }
+
+    /// Like [`FtpControl::command`], but returns the reply text.
+    async fn command_reply(
+        &mut self,
+        command: &str,
+        expected: &[u16],
+    ) -> Result<String, ProgramError> {
+        let (code, text) = self.send(command).await?;
+        check_reply(code, &text, expected)?;
+        Ok(text)
+    }
+
+    /// Reads the next reply and fails unless its code is one of `expected`.
+    async fn expect(&mut self, expected: &[u16]) -> Result<(), ProgramError> {
+        let (code, text) = self.read_reply().await?;
+        check_reply(code, &text, expected)
+    }
+
+    /// Reads a reply, including multi-line replies (`123-...` up to `123 ...`).
+    async fn read_reply(&mut self) -> Result<(u16, String), ProgramError> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err(ProgramError::Other(
+                "FTP server closed the connection".to_string(),
+            ));
+        }
+        trace!("FTP < {}", line.trim_end());
+
+        let code: u16 = line
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| ProgramError::Other(format!("malformed FTP reply: {}", line.trim())))?;
+        let mut text = line.get(4..).unwrap_or("").trim_end().to_string();
+
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let end = format!("{} ", code);
+            loop {
+                line.clear();
+                if self.stream.read_line(&mut line).await? == 0 {
+                    break;
+                }
+                trace!("FTP < {}", line.trim_end());
+                if line.starts_with(&end) {
+                    text = line[4..].trim_end().to_string();
+                    break;
+                }
+            }
+        }
+
+        Ok((code, text))
+    }
+}
+
+/// Helper to turn an unexpected FTP reply into an error.
+fn check_reply(code: u16, text: &str, expected: &[u16]) -> Result<(), ProgramError> {
+    if expected.contains(&code) {
+        Ok(())
+    } else {
+        Err(ProgramError::Other(format!("FTP {}: {}", code, text)))
+    }
+}
+
+/// Helper to extract the data port from a PASV reply.
+///
+/// Example input: "Entering Passive Mode (192,168,1,2,195,80)" -> 50000
+fn parse_pasv_port(reply: &str) -> Option<u16> {
+    let start = reply.find('(')?;
+    let end = reply[start..].find(')')? + start;
+    let fields: Vec<u16> = reply[start + 1..end]
+        .split(',')
+        .map(|f| f.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match fields[..] {
+        [_, _, _, _, p1, p2] if p1 <= 255 && p2 <= 255 => Some(p1 * 256 + p2),
+        _ => None,
+    }
+}
@@ -1,13 +1,88 @@
 use reqwest::{
-    header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE},
+    StatusCode,
+    header::{
+        ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG,
+        HeaderMap, LAST_MODIFIED, LOCATION, RANGE,
+    },
     {Client, Response},
 };
-use tracing::{debug, instrument, trace};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, instrument, warn};
 
 use crate::error::ProgramError;
-use crate::types::ProbeResult;
+use crate::types::{HeadersFormat, PostBody, ProbeResult, ProbeResultBuilder};
+use crate::utils::{header_to_string_lossy, json_string, send_traced};
 
-/// Probes the server to determine file size and range request support.
+/// Delay between probe attempts.
+const PROBE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Probes the server to determine file size and range request support, retrying on failure.
+///
+/// Each attempt runs the full HEAD + GET fallback sequence (see [`probe_once`]).
+/// Attempts are separated by a fixed one second delay without backoff.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client.
+/// * `url` - The URL to probe.
+/// * `probe_retries` - Maximum number of probe attempts (at least one attempt is made).
+/// * `probe_timeout` - Timeout applied to each individual probe request.
+/// * `conditional` - Conditional request headers (`If-Modified-Since`, `If-None-Match`).
+/// * `post` - Body to POST instead of probing with HEAD and a range GET.
+/// * `expected_headers` - Headers the probe response must carry (`--expect-header`).
+///
+/// # Returns
+///
+/// * `Ok(ProbeResult)` from the first successful attempt.
+/// * `Err(ProgramError)` from the last attempt if all attempts fail.
+#[instrument(skip(client, conditional, post, expected_headers), fields(url = %url))]
+pub async fn probe(
+    client: &Client,
+    url: &str,
+    probe_retries: u32,
+    probe_timeout: Duration,
+    conditional: &HeaderMap,
+    post: Option<&PostBody>,
+    expected_headers: &[(String, String)],
+) -> Result<ProbeResult, ProgramError> {
+    let max_attempts = probe_retries.max(1);
+    let mut attempt = 1;
+
+    loop {
+        debug!(attempt = attempt, "Probe attempt");
+        let result = match post {
+            Some(body) => {
+                probe_post(
+                    client,
+                    url,
+                    probe_timeout,
+                    conditional,
+                    body,
+                    expected_headers,
+                )
+                .await
+            }
+            None => probe_once(client, url, probe_timeout, conditional, expected_headers).await,
+        };
+        match result {
+            Ok(result) => return Ok(result),
+            Err(e) if attempt < max_attempts => {
+                warn!(
+                    attempt = attempt,
+                    error = %e,
+                    "Probe failed, retrying in {}ms",
+                    PROBE_RETRY_DELAY.as_millis()
+                );
+                sleep(PROBE_RETRY_DELAY).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Performs a single probe attempt.
 ///
 /// It first attempts a HEAD request. If that fails or returns no Content-Length,
 /// it falls back to a GET request for the first byte (bytes=0-0) to inspect
@@ -17,101 +92,914 @@ use crate::types::ProbeResult;
 ///
 /// * `client` - The HTTP client.
 /// * `url` - The URL to probe.
+/// * `probe_timeout` - Timeout applied to both the HEAD and the GET fallback request.
+/// * `conditional` - Conditional request headers sent with both requests.
+/// * `expected_headers` - Headers every response used for the result must carry.
 ///
 /// # Returns
 ///
 /// * `Ok(ProbeResult)` containing content length and range support status.
-/// * `Err(ProgramError)` if network fails or file size cannot be determined.
-#[instrument(skip(client), fields(url = %url))]
-pub async fn probe(client: &Client, url: &str) -> Result<ProbeResult, ProgramError> {
+///   A `304 Not Modified` answer is returned with `status_code` 304 and zero length.
+/// * `Err(ProgramError::HttpStatus)` if the range GET is answered with an error status; a
+///   rejected HEAD is ignored, as some servers only refuse HEAD.
+/// * `Err(ProgramError)` if network fails, a request times out or file size cannot be determined.
+async fn probe_once(
+    client: &Client,
+    url: &str,
+    probe_timeout: Duration,
+    conditional: &HeaderMap,
+    expected_headers: &[(String, String)],
+) -> Result<ProbeResult, ProgramError> {
     // Prefer HEAD, but some servers misbehave; fallback to GET 0-0
     debug!("Sending HEAD request");
-    let head = client.head(url).send().await;
+    let head = timeout(
+        probe_timeout,
+        send_traced(client.head(url).headers(conditional.clone())),
+    )
+    .await
+    .map_err(|_| probe_timed_out(probe_timeout))?;
+
+    // Result of a HEAD response that lacked `Accept-Ranges`
+    let mut head_result: Option<ProbeResult> = None;
 
     if let Ok(resp) = head {
         debug!(status = %resp.status(), "HEAD response received");
-        trace!("HEAD response headers:");
-        for (name, value) in resp.headers().iter() {
-            trace!(header_name = %name, header_value = ?value);
+
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(not_modified(&resp));
+        }
+        if let Some(result) = ftp_redirect(&resp) {
+            return Ok(result);
         }
 
-        let len = parse_content_length(&resp)?;
-        let accept_ranges = resp
-            .headers()
-            .get(ACCEPT_RANGES)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_ascii_lowercase().contains("bytes"))
-            .unwrap_or(false);
-
-        if len > 0 {
-            debug!(
-                content_length = len,
-                accept_ranges = accept_ranges,
-                "HEAD successful"
-            );
-            return Ok(ProbeResult {
-                content_length: len,
-                accept_ranges,
-            });
+        // Some servers reject HEAD but serve GET; the range GET below checks its own status
+        match check_probe_status(&resp) {
+            Ok(()) => head_result = head_probe_result(&resp, expected_headers)?,
+            Err(e) => debug!(error = %e, "HEAD rejected, ignoring its headers"),
+        }
+        if let Some(result) = head_result.take_if(|r| r.accept_ranges) {
+            return Ok(result);
         }
     }
 
     // Fallback: GET Range: bytes=0-0, then read Content-Range?
-    debug!("HEAD failed or returned 0 length, trying GET with Range: bytes=0-0");
-    let resp = client.get(url).header(RANGE, "bytes=0-0").send().await?;
+    debug!("HEAD failed, returned 0 length or no Accept-Ranges, trying GET with Range: bytes=0-0");
+    let resp = timeout(
+        probe_timeout,
+        send_traced(
+            client
+                .get(url)
+                .headers(conditional.clone())
+                .header(RANGE, "bytes=0-0"),
+        ),
+    )
+    .await
+    .map_err(|_| probe_timed_out(probe_timeout))?;
+
+    let (resp, head_result) = match (resp, head_result) {
+        (Ok(resp), head_result) => (resp, head_result),
+        (Err(e), Some(result)) => {
+            debug!(error = %e, "Range GET failed, using HEAD result without range support");
+            return Ok(result);
+        }
+        (Err(e), None) => return Err(e.into()),
+    };
 
     debug!(status = %resp.status(), "Range GET response received");
-    trace!("Range GET response headers:");
-    for (name, value) in resp.headers().iter() {
-        trace!(header_name = %name, header_value = ?value);
+
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        return Ok(not_modified(&resp));
+    }
+    if let Some(result) = ftp_redirect(&resp) {
+        return Ok(result);
+    }
+    if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        check_expected_headers(&resp, expected_headers)?;
+        warn!("Server returned 416 Range Not Satisfiable; falling back to single download");
+        return probe_without_range(client, url, probe_timeout, conditional, &resp, head_result)
+            .await;
     }
+    check_probe_status(&resp)?;
+    check_expected_headers(&resp, expected_headers)?;
+    let head_len = head_result.map(|r| r.content_length);
 
     let accept_ranges = resp.status().as_u16() == 206; // Partial Content indicates range support
+    if accept_ranges && !resp.headers().contains_key(ACCEPT_RANGES) {
+        warn!("Server returned 206 without Accept-Ranges header; assuming range support");
+    }
 
     // Content-Length in this response is 1, so we need total length:
     // Many servers include Content-Range: bytes 0-0/12345
-    let total = resp
+    let content_range = resp
         .headers()
         .get("content-range")
-        .and_then(|v| v.to_str().ok())
-        .and_then(parse_total_from_content_range)
-        .ok_or_else(|| {
-            ProgramError::Other(
-                "cannot determine total length (no valid Content-Length/Content-Range)".to_string(),
-            )
-        })?;
+        .map(header_to_string_lossy);
+    let parsed_total = content_range
+        .as_deref()
+        .and_then(parse_total_from_content_range);
+    if let (Some(raw), None, Some(len)) = (content_range, parsed_total, head_len) {
+        warn!(
+            content_range = %raw,
+            "Malformed Content-Range header, using HEAD Content-Length {}", len
+        );
+    }
+    // A server that ignores the range sends the whole body, so its Content-Length is the total
+    let full_len = match resp.status() {
+        StatusCode::OK => Some(parse_content_length(&resp)?).filter(|&len| len > 0),
+        _ => None,
+    };
+    let Some(total) = parsed_total.or(head_len).or(full_len) else {
+        // e.g. chunked encoding: the body can still be streamed by a single download
+        debug!("Total length unknown, probe completed without range support");
+        return Ok(ProbeResultBuilder::from_response(&resp).build());
+    };
 
     debug!(
         total_size = total,
         accept_ranges = accept_ranges,
         "Probe completed via Range GET"
     );
-    Ok(ProbeResult {
-        content_length: total,
-        accept_ranges,
-    })
+    let result = ProbeResultBuilder::from_response(&resp)
+        .content_length(total)
+        .accept_ranges(accept_ranges)
+        .build();
+    if accept_ranges {
+        release_connection(resp, probe_timeout).await;
+    }
+    Ok(result)
+}
+
+/// Builds the probe result of a successful HEAD response.
+///
+/// # Returns
+///
+/// * `Ok(None)` - If the response has no usable `Content-Length`.
+fn head_probe_result(
+    resp: &Response,
+    expected_headers: &[(String, String)],
+) -> Result<Option<ProbeResult>, ProgramError> {
+    let len = parse_content_length(resp)?;
+    let accept_ranges = resp
+        .headers()
+        .get(ACCEPT_RANGES)
+        .map(|v| {
+            header_to_string_lossy(v)
+                .to_ascii_lowercase()
+                .contains("bytes")
+        })
+        .unwrap_or(false);
+    if len == 0 {
+        return Ok(None);
+    }
+
+    check_expected_headers(resp, expected_headers)?;
+    debug!(
+        content_length = len,
+        accept_ranges = accept_ranges,
+        "HEAD successful"
+    );
+    // Without Accept-Ranges, the caller still checks for range support with GET 0-0
+    Ok(Some(
+        ProbeResultBuilder::from_response(resp)
+            .content_length(len)
+            .accept_ranges(accept_ranges)
+            .build(),
+    ))
+}
+
+/// Determines the file size after the `bytes=0-0` probe was answered with 416.
+///
+/// This happens for empty files and on servers that mishandle ranges. The size is taken
+/// from the HEAD response or the 416's `Content-Range: bytes */N` if either has it;
+/// otherwise a plain GET is sent and only its `Content-Length` is read. The result never
+/// has range support, so the download goes through the single-connection path, which
+/// also handles 0-byte files.
+async fn probe_without_range(
+    client: &Client,
+    url: &str,
+    probe_timeout: Duration,
+    conditional: &HeaderMap,
+    resp: &Response,
+    head_result: Option<ProbeResult>,
+) -> Result<ProbeResult, ProgramError> {
+    if let Some(result) = head_result {
+        debug!(
+            total_size = result.content_length,
+            "Using HEAD size after 416"
+        );
+        return Ok(result);
+    }
+    let range_total = resp
+        .headers()
+        .get("content-range")
+        .map(header_to_string_lossy)
+        .as_deref()
+        .and_then(parse_total_from_content_range);
+    if let Some(total) = range_total {
+        debug!(
+            total_size = total,
+            "Using Content-Range size of 416 response"
+        );
+        return Ok(ProbeResultBuilder::from_response(resp)
+            .content_length(total)
+            .build());
+    }
+
+    debug!("Sending GET without Range to read Content-Length");
+    let resp = timeout(
+        probe_timeout,
+        send_traced(client.get(url).headers(conditional.clone())),
+    )
+    .await
+    .map_err(|_| probe_timed_out(probe_timeout))??;
+    check_probe_status(&resp)?;
+    // The body is not read: dropping the response closes the connection
+    let len = parse_content_length(&resp)?;
+    debug!(status = %resp.status(), content_length = len, "Full GET probe completed");
+    Ok(ProbeResultBuilder::from_response(&resp)
+        .content_length(len)
+        .build())
+}
+
+/// Reads the one-byte body of a `bytes=0-0` response so its connection goes back to the pool.
+///
+/// hyper only reuses a keep-alive connection once the body has been consumed; otherwise
+/// dropping the response closes it and the first part has to open (and for HTTPS,
+/// handshake) a new one. Must not be used on a full 200 body.
+async fn release_connection(resp: Response, probe_timeout: Duration) {
+    match timeout(probe_timeout, resp.bytes()).await {
+        Ok(Ok(body)) => debug!(
+            len = body.len(),
+            "Range probe body drained, connection pooled"
+        ),
+        Ok(Err(e)) => debug!(error = %e, "Failed to drain range probe body"),
+        Err(_) => debug!("Timed out draining range probe body"),
+    }
+}
+
+/// Performs a single probe attempt for a POST download.
+///
+/// Range requests are not used with POST, so only the response headers of the
+/// POST itself are inspected; the body is discarded.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client.
+/// * `url` - The URL to probe.
+/// * `probe_timeout` - Timeout applied to the POST request.
+/// * `conditional` - Conditional request headers.
+/// * `body` - The request body.
+/// * `expected_headers` - Headers the POST response must carry.
+///
+/// # Returns
+///
+/// * `Ok(ProbeResult)` with the Content-Length (0 if unknown) and no range support.
+/// * `Err(ProgramError)` if the request fails or times out.
+async fn probe_post(
+    client: &Client,
+    url: &str,
+    probe_timeout: Duration,
+    conditional: &HeaderMap,
+    body: &PostBody,
+    expected_headers: &[(String, String)],
+) -> Result<ProbeResult, ProgramError> {
+    debug!(content_type = body.content_type, "Sending POST request");
+    let resp = timeout(
+        probe_timeout,
+        send_traced(
+            client
+                .post(url)
+                .headers(conditional.clone())
+                .header(CONTENT_TYPE, body.content_type)
+                .body(body.data.clone()),
+        ),
+    )
+    .await
+    .map_err(|_| probe_timed_out(probe_timeout))??;
+
+    debug!(status = %resp.status(), "POST response received");
+
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        return Ok(not_modified(&resp));
+    }
+    check_probe_status(&resp)?;
+    check_expected_headers(&resp, expected_headers)?;
+
+    Ok(ProbeResultBuilder::from_response(&resp)
+        .content_length(parse_content_length(&resp)?)
+        .build())
+}
+
+/// Checks that a response carries every expected header (`--expect-header`).
+///
+/// Header names are compared case-insensitively and values exactly (after trimming);
+/// a header sent several times matches if any of its values does.
+///
+/// # Returns
+///
+/// * `Ok(())` if all headers match.
+/// * `Err(ProgramError::Other)` naming the first header that is missing or different.
+pub fn check_expected_headers(
+    resp: &Response,
+    expected: &[(String, String)],
+) -> Result<(), ProgramError> {
+    for (name, value) in expected {
+        let actual: Vec<_> = resp
+            .headers()
+            .get_all(name.as_str())
+            .iter()
+            .map(header_to_string_lossy)
+            .collect();
+        if !actual.iter().any(|v| v.trim() == value) {
+            let got = if actual.is_empty() {
+                "no such header".to_string()
+            } else {
+                actual.join(", ")
+            };
+            return Err(ProgramError::Other(format!(
+                "expected header {}: {} not found; got {}",
+                name, value, got
+            )));
+        }
+        debug!(header = %name, value = %value, "Expected header present");
+    }
+    Ok(())
+}
+
+impl ProbeResultBuilder {
+    /// Creates a builder with all fields at their defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a builder with the status code, final URL, `Content-Type`,
+    /// `Content-Disposition`, `Content-Encoding`, `Last-Modified` and `ETag` of `resp`.
+    pub fn from_response(resp: &Response) -> Self {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .map(|v| header_to_string_lossy(v).into_owned())
+        };
+        Self::new()
+            .status_code(resp.status().as_u16())
+            .final_url(resp.url().to_string())
+            .content_type(header(CONTENT_TYPE))
+            .content_disposition(header(CONTENT_DISPOSITION))
+            .content_encoding(header(CONTENT_ENCODING))
+            .last_modified(header(LAST_MODIFIED))
+            .etag(header(ETAG))
+    }
+
+    pub fn content_length(mut self, content_length: u64) -> Self {
+        self.result.content_length = content_length;
+        self
+    }
+
+    pub fn accept_ranges(mut self, accept_ranges: bool) -> Self {
+        self.result.accept_ranges = accept_ranges;
+        self
+    }
+
+    pub fn status_code(mut self, status_code: u16) -> Self {
+        self.result.status_code = status_code;
+        self
+    }
+
+    pub fn final_url(mut self, final_url: String) -> Self {
+        self.result.final_url = final_url;
+        self
+    }
+
+    pub fn content_type(mut self, content_type: Option<String>) -> Self {
+        self.result.content_type = content_type;
+        self
+    }
+
+    pub fn content_disposition(mut self, content_disposition: Option<String>) -> Self {
+        self.result.content_disposition = content_disposition;
+        self
+    }
+
+    pub fn content_encoding(mut self, content_encoding: Option<String>) -> Self {
+        self.result.content_encoding = content_encoding;
+        self
+    }
+
+    pub fn last_modified(mut self, last_modified: Option<String>) -> Self {
+        self.result.last_modified = last_modified;
+        self
+    }
+
+    pub fn etag(mut self, etag: Option<String>) -> Self {
+        self.result.etag = etag;
+        self
+    }
+
+    pub fn build(self) -> ProbeResult {
+        self.result
+    }
+}
+
+/// Serializes the headers of `resp` for `--append-headers`.
+///
+/// Header names are lowercase, as received from reqwest. In JSON, a header sent more
+/// than once (e.g. `set-cookie`) becomes an array of its values.
+///
+/// # Examples
+///
+/// * `Httpd` -> `# content-type: text/plain\n# set-cookie: a=1\n# set-cookie: b=2\n\n`
+/// * `Json` -> `{"content-type": "text/plain", "set-cookie": ["a=1", "b=2"]}\n`
+pub fn serialize_headers(resp: &Response, format: HeadersFormat) -> Vec<u8> {
+    let headers = resp.headers();
+    let mut out = String::new();
+    match format {
+        HeadersFormat::Httpd => {
+            for (name, value) in headers {
+                out.push_str(&format!("# {}: {}\n", name, header_to_string_lossy(value)));
+            }
+            out.push('\n');
+        }
+        HeadersFormat::Json => {
+            let fields: Vec<String> = headers
+                .keys()
+                .map(|name| {
+                    let values: Vec<String> = headers
+                        .get_all(name)
+                        .iter()
+                        .map(|v| json_string(&header_to_string_lossy(v)))
+                        .collect();
+                    let value = match values.as_slice() {
+                        [single] => single.clone(),
+                        _ => format!("[{}]", values.join(", ")),
+                    };
+                    format!("{}: {}", json_string(name.as_str()), value)
+                })
+                .collect();
+            out.push_str(&format!("{{{}}}\n", fields.join(", ")));
+        }
+    }
+    out.into_bytes()
+}
+
+/// Helper to build the probe result for a `304 Not Modified` response.
+fn not_modified(resp: &Response) -> ProbeResult {
+    debug!("Server reported resource not modified");
+    ProbeResultBuilder::from_response(resp).build()
+}
+
+/// Helper to detect a redirect to an `ftp://` URL (only returned with `--follow-ftp`).
+///
+/// The result has the FTP URL as `final_url` and no known length.
+fn ftp_redirect(resp: &Response) -> Option<ProbeResult> {
+    if !resp.status().is_redirection() {
+        return None;
+    }
+    let location = resp.headers().get(LOCATION)?;
+    let target = resp.url().join(&header_to_string_lossy(location)).ok()?;
+    if target.scheme() != "ftp" {
+        return None;
+    }
+    debug!(location = %target, "Server redirected to FTP");
+    Some(
+        ProbeResultBuilder::from_response(resp)
+            .final_url(target.to_string())
+            .build(),
+    )
+}
+
+/// Helper to reject probe responses that are not 2xx (including 206).
+///
+/// `304 Not Modified`, `416 Range Not Satisfiable` and redirects to FTP are handled
+/// before this check.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::HttpStatus)` - For any other status, as `download.rs` does.
+fn check_probe_status(resp: &Response) -> Result<(), ProgramError> {
+    if !resp.status().is_success() {
+        return Err(ProgramError::HttpStatus {
+            code: resp.status().as_u16(),
+            url: resp.url().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Helper to build the error returned when a probe request exceeds its timeout.
+fn probe_timed_out(probe_timeout: Duration) -> ProgramError {
+    ProgramError::Timeout {
+        operation: "probe".to_string(),
+        duration: Some(probe_timeout),
+    }
 }
 
 /// Helper to parse total size from Content-Range header.
 ///
-/// Example inputs: "bytes 0-0/12345", "bytes 0-0/*"
-/// Returns `None` if size is unknown ("*") or format is invalid.
-fn parse_total_from_content_range(s: &str) -> Option<u64> {
-    let slash = s.rfind('/')?;
-    let total_str = &s[slash + 1..];
-    if total_str == "*" {
+/// Example inputs: "bytes 0-0/12345", "bytes */12345", "bytes 0-0/*"
+/// Returns `None` if size is unknown ("*"), zero (sent by some buggy servers),
+/// or the header does not match `bytes <start>-<end>/<total>` / `bytes */<total>`.
+pub(crate) fn parse_total_from_content_range(s: &str) -> Option<u64> {
+    let rest = s.trim().strip_prefix("bytes ")?;
+    let (range, total_str) = rest.split_once('/')?;
+
+    let range = range.trim();
+    if range != "*" {
+        let (start, end) = range.split_once('-')?;
+        let start = parse_digits(start)?;
+        let end = parse_digits(end)?;
+        if start > end {
+            return None;
+        }
+    }
+
+    match parse_digits(total_str.trim())? {
+        0 => None,
+        total => Some(total),
+    }
+}
+
+/// Helper to parse a string made only of ASCII digits (no sign, no whitespace).
+fn parse_digits(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
         return None;
     }
-    total_str.parse().ok()
+    s.parse().ok()
 }
 
 /// Helper to parse Content-Length header from a response.
+///
+/// Parses the raw header bytes instead of going through `HeaderValue::to_str`.
 fn parse_content_length(resp: &Response) -> Result<u64, ProgramError> {
     let len = resp
         .headers()
         .get(CONTENT_LENGTH)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok())
+        .and_then(|v| std::str::from_utf8(v.as_bytes().trim_ascii()).ok())
+        .and_then(parse_digits)
         .unwrap_or(0);
     Ok(len)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestServer, response, serve_file};
+
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+
+    async fn probe_url(url: &str) -> Result<ProbeResult, ProgramError> {
+        probe_once(
+            &client(),
+            url,
+            Duration::from_secs(5),
+            &HeaderMap::new(),
+            &[],
+        )
+        .await
+    }
+
+    #[test]
+    fn content_range_total() {
+        assert_eq!(parse_total_from_content_range("bytes 0-0/1234"), Some(1234));
+        assert_eq!(
+            parse_total_from_content_range("bytes 0-499/1234"),
+            Some(1234)
+        );
+        assert_eq!(parse_total_from_content_range("bytes */1234"), Some(1234));
+        assert_eq!(
+            parse_total_from_content_range("  bytes 0-0/1234 "),
+            Some(1234)
+        );
+        assert_eq!(
+            parse_total_from_content_range("bytes 0-0/18446744073709551615"),
+            Some(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn content_range_unknown_or_zero_total() {
+        assert_eq!(parse_total_from_content_range("bytes 0-0/*"), None);
+        assert_eq!(parse_total_from_content_range("bytes */*"), None);
+        assert_eq!(parse_total_from_content_range("bytes 0-0/0"), None);
+    }
+
+    #[test]
+    fn content_range_malformed() {
+        for header in [
+            "",
+            "bytes",
+            "bytes 0-0",
+            "bytes0-0/1234",
+            "items 0-0/1234",
+            "bytes 0/1234",
+            "bytes a-0/1234",
+            "bytes 0-b/1234",
+            "bytes 5-1/1234",
+            "bytes -1-0/1234",
+            "bytes 0-0/+1234",
+            "bytes 0-0/12a4",
+            "bytes 0-0/-1",
+        ] {
+            assert_eq!(parse_total_from_content_range(header), None, "{header:?}");
+        }
+    }
+
+    #[test]
+    fn content_range_overflow() {
+        assert_eq!(
+            parse_total_from_content_range("bytes 0-0/18446744073709551616"),
+            None
+        );
+        assert_eq!(
+            parse_total_from_content_range("bytes 0-18446744073709551616/1234"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn probe_uses_head_of_range_capable_server() {
+        let server = TestServer::start(serve_file(vec![7; 1000], &[])).await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert_eq!(result.content_length, 1000);
+        assert!(result.accept_ranges);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn probe_rejects_not_found_even_with_content_length() {
+        let server = TestServer::start(|req| match req.method.as_str() {
+            "HEAD" => response(404, &[("Content-Length", "162")], b""),
+            _ => response(404, &[], b"not found"),
+        })
+        .await;
+
+        let err = probe_url(&format!("{}/missing", server.url))
+            .await
+            .unwrap_err();
+        assert_eq!(server.requests()[0].path, "/missing");
+        assert!(
+            matches!(err, ProgramError::HttpStatus { code: 404, .. }),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn probe_rejects_server_error_on_range_get() {
+        let server = TestServer::start(|_| response(500, &[], b"oops")).await;
+        let err = probe_url(&server.url).await.unwrap_err();
+        assert!(
+            matches!(err, ProgramError::HttpStatus { code: 500, .. }),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn probe_ignores_rejected_head_when_get_works() {
+        let server = TestServer::start(|req| match req.method.as_str() {
+            "HEAD" => response(405, &[], b""),
+            _ => response(206, &[("Content-Range", "bytes 0-0/1234")], b"x"),
+        })
+        .await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert_eq!(result.content_length, 1234);
+        assert!(result.accept_ranges);
+        let requests = server.requests();
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[1].header("range"), Some("bytes=0-0"));
+    }
+
+    #[tokio::test]
+    async fn probe_takes_length_of_range_ignoring_server() {
+        // No HEAD and no range support: the range GET is answered with the whole body
+        let server = TestServer::start(|req| match req.method.as_str() {
+            "HEAD" => response(405, &[], b""),
+            _ => response(200, &[], &[7; 1000]),
+        })
+        .await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert_eq!(server.requests()[1].range(), Some((0, 0)));
+        assert_eq!(result.content_length, 1000);
+        assert!(!result.accept_ranges);
+    }
+
+    #[tokio::test]
+    async fn probe_keeps_not_modified() {
+        let server = TestServer::start(|_| response(304, &[], b"")).await;
+        let result = probe_url(&server.url).await.unwrap();
+        assert_eq!(result.status_code, 304);
+    }
+
+    #[tokio::test]
+    async fn probe_assumes_ranges_on_206_without_accept_ranges() {
+        // HEAD has no Accept-Ranges, but the range GET is answered with 206
+        let server = TestServer::start(|req| match (req.method.as_str(), req.range()) {
+            ("HEAD", _) => response(200, &[("Content-Length", "5000")], b""),
+            (_, Some((0, 0))) => response(206, &[("Content-Range", "bytes 0-0/5000")], b"x"),
+            _ => response(200, &[], &[0; 5000]),
+        })
+        .await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert!(result.accept_ranges);
+        assert_eq!(result.content_length, 5000);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn probe_without_206_has_no_range_support() {
+        // The range header is ignored and the whole body is sent
+        let server = TestServer::start(|_| response(200, &[], &[0; 5000])).await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert!(!result.accept_ranges);
+        assert_eq!(result.content_length, 5000);
+    }
+
+    fn response_with(headers: &[(&str, &[u8])]) -> Response {
+        let mut builder = http::Response::builder().status(200);
+        for (name, value) in headers {
+            builder = builder.header(
+                *name,
+                reqwest::header::HeaderValue::from_bytes(value).unwrap(),
+            );
+        }
+        Response::from(builder.body(Vec::new()).unwrap())
+    }
+
+    #[test]
+    fn non_utf8_headers_are_kept() {
+        let resp = response_with(&[
+            (
+                "Content-Disposition",
+                b"attachment; filename=\"caf\xe9.txt\"",
+            ),
+            ("Content-Length", b" 1234 "),
+            ("X-Mirror", b"m\xfcnchen"),
+        ]);
+        let result = ProbeResultBuilder::from_response(&resp).build();
+        assert_eq!(
+            result.content_disposition.as_deref(),
+            Some("attachment; filename=\"caf\u{FFFD}.txt\"")
+        );
+        assert_eq!(parse_content_length(&resp).unwrap(), 1234);
+
+        let expected = [("X-Mirror".to_string(), "m\u{FFFD}nchen".to_string())];
+        check_expected_headers(&resp, &expected).unwrap();
+    }
+
+    #[test]
+    fn invalid_content_length_is_unknown() {
+        for value in [&b"12\xe934"[..], b"-1", b"1 2", b""] {
+            let resp = response_with(&[("Content-Length", value)]);
+            assert_eq!(parse_content_length(&resp).unwrap(), 0, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn probe_result_builder_sets_every_field() {
+        let result = ProbeResultBuilder::new()
+            .content_length(1234)
+            .accept_ranges(true)
+            .status_code(206)
+            .final_url("https://example.com/file.bin".to_string())
+            .content_type(Some("application/octet-stream".to_string()))
+            .content_disposition(Some("attachment; filename=file.bin".to_string()))
+            .content_encoding(Some("gzip".to_string()))
+            .last_modified(Some("Tue, 14 Oct 2026 14:42:36 GMT".to_string()))
+            .etag(Some("\"5f3a\"".to_string()))
+            .build();
+
+        assert_eq!(result.content_length, 1234);
+        assert!(result.accept_ranges);
+        assert_eq!(result.status_code, 206);
+        assert_eq!(result.final_url, "https://example.com/file.bin");
+        assert_eq!(
+            result.content_type.as_deref(),
+            Some("application/octet-stream")
+        );
+        assert_eq!(
+            result.content_disposition.as_deref(),
+            Some("attachment; filename=file.bin")
+        );
+        assert_eq!(result.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(
+            result.last_modified.as_deref(),
+            Some("Tue, 14 Oct 2026 14:42:36 GMT")
+        );
+        assert_eq!(result.etag.as_deref(), Some("\"5f3a\""));
+        assert_eq!(result.redirect_count, 0);
+    }
+
+    #[test]
+    fn probe_result_builder_defaults() {
+        let result = ProbeResultBuilder::new().build();
+        assert_eq!(result.content_length, 0);
+        assert!(!result.accept_ranges);
+        assert!(result.final_url.is_empty());
+        assert_eq!(result.content_type, None);
+        assert_eq!(result.etag, None);
+    }
+
+    #[test]
+    fn probe_result_builder_from_response() {
+        let resp = response_with(&[
+            ("Content-Type", b"text/plain"),
+            ("Content-Encoding", b"gzip"),
+            ("Last-Modified", b"Tue, 14 Oct 2026 14:42:36 GMT"),
+            ("ETag", b"W/\"1\""),
+        ]);
+        let result = ProbeResultBuilder::from_response(&resp)
+            .content_length(10)
+            .build();
+        assert_eq!(result.status_code, 200);
+        assert_eq!(result.content_length, 10);
+        assert_eq!(result.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(result.content_disposition, None);
+        assert_eq!(result.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(
+            result.last_modified.as_deref(),
+            Some("Tue, 14 Oct 2026 14:42:36 GMT")
+        );
+        assert_eq!(result.etag.as_deref(), Some("W/\"1\""));
+    }
+
+    #[tokio::test]
+    async fn probe_416_falls_back_to_a_full_get() {
+        // Empty file: no HEAD, and 416 without a size for the range GET
+        let server = TestServer::start(|req| match (req.method.as_str(), req.range()) {
+            ("HEAD", _) => response(405, &[], b""),
+            (_, Some(_)) => response(416, &[], b""),
+            _ => response(200, &[], b""),
+        })
+        .await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert!(!result.accept_ranges);
+        assert_eq!(result.content_length, 0);
+        assert_eq!(result.status_code, 200);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].header("range"), None);
+    }
+
+    #[tokio::test]
+    async fn probe_416_uses_content_range_size() {
+        let server = TestServer::start(|req| match req.method.as_str() {
+            "HEAD" => response(405, &[], b""),
+            _ => response(416, &[("Content-Range", "bytes */5000")], b""),
+        })
+        .await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert!(!result.accept_ranges);
+        assert_eq!(result.content_length, 5000);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn probe_416_uses_head_size() {
+        let server = TestServer::start(|req| match req.method.as_str() {
+            "HEAD" => response(200, &[("Content-Length", "5000")], b""),
+            _ => response(416, &[], b""),
+        })
+        .await;
+
+        let result = probe_url(&server.url).await.unwrap();
+        assert!(!result.accept_ranges);
+        assert_eq!(result.content_length, 5000);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    fn cookie_response() -> Response {
+        response_with(&[
+            ("Content-Type", b"text/plain"),
+            ("Set-Cookie", b"a=1"),
+            ("Set-Cookie", b"b=2"),
+        ])
+    }
+
+    #[test]
+    fn headers_as_httpd_comments() {
+        let out = serialize_headers(&cookie_response(), HeadersFormat::Httpd);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# content-type: text/plain\n# set-cookie: a=1\n# set-cookie: b=2\n\n"
+        );
+    }
+
+    #[test]
+    fn headers_as_json() {
+        let out = serialize_headers(&cookie_response(), HeadersFormat::Json);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"content-type\": \"text/plain\", \"set-cookie\": [\"a=1\", \"b=2\"]}\n"
+        );
+
+        let out = serialize_headers(&response_with(&[]), HeadersFormat::Json);
+        assert_eq!(out, b"{}\n");
+    }
+}
@@ -1,114 +1,1378 @@
+mod auth;
+#[cfg(feature = "cipher-list")]
+mod cipher;
+mod client_trait;
+mod date;
+mod decompress;
+#[cfg(target_os = "linux")]
+mod direct_io;
+mod dns;
 mod download;
 mod error;
+mod ftp;
 mod http;
 mod part;
+mod plan;
 mod progress;
+mod signal;
+mod spider;
+mod template;
+#[cfg(test)]
+mod test_server;
+mod throttle;
 mod types;
 mod utils;
+mod verify;
+mod version;
 
 use clap::Parser;
-use std::path::{Path, PathBuf};
-use tokio::fs;
-use tracing::{info, warn};
+use indicatif::ProgressBar;
+use reqwest::{StatusCode, header::HeaderMap};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::fs::{self, File};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
-use download::{download_parts_parallel, single_download};
+use date::parse_date;
+use decompress::is_identity;
+use download::{
+    download_parts_parallel, download_parts_parallel_with_cancel, download_to_writer,
+    single_download,
+};
 use error::ProgramError;
+use ftp::ftp_download;
 use http::probe;
-use part::{merge_parts, split_into_parts};
-use progress::{format_bytes, style_spinner};
-use types::{Args, ProxyMode};
-use utils::{build_client, get_filename_from_url, init_tracing};
+use part::{
+    cleanup_parts, describe_parts, merge_parts, prepare_resume, reassemble_from_disk,
+    split_into_parts, write_resume_state,
+};
+use plan::{load_plan, save_plan};
+use progress::{
+    WarningCollector, format_bytes, format_bytes_exact, new_download_progress, new_progress_bar,
+    style_download_bar, style_merge_bar,
+};
+use signal::cancel_on_sigint;
+use spider::{spider, spider_recursive};
+use template::expand_write_out;
+use throttle::TokenBucket;
+use types::{
+    Args, AuthScheme, Cli, ClientOptions, Command, DownloadPlan, DownloadSummary, FilenameDeriver,
+    HashAlgorithm, HttpAuth, LogLevel, MergeArgs, OutputMode, ParallelOutcome, Part,
+    PartSizeStrategy, PartStrategy, PartialResult, PostBody, ProbeResult, ProgressOptions,
+    ProxyMode, ResumePart, ResumeState, RetryPolicy, VerifyArgs,
+};
+use utils::{
+    build_client, check_output_mode, conditional_headers, expand_user_agent_template, init_tracing,
+    is_device, open_output_file, set_file_mtime,
+};
+use verify::{
+    checksum_line, fetch_checksum_sidecar, hash_file, parse_expected, verify_download,
+    write_checksum_file,
+};
+use version::check_latest_version;
+
+/// Redirect chains longer than this are reported as a warning.
+const REDIRECT_WARN_THRESHOLD: u32 = 3;
 
 #[tokio::main]
-async fn main() -> Result<(), ProgramError> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        None => download_command(cli.args, false).await,
+        Some(Command::Download(args)) => download_command(*args, false).await,
+        Some(Command::Probe(args)) => download_command(*args, true).await,
+        Some(Command::Merge(merge)) => {
+            init_tracing(LogLevel::Warn, None, false, &Arc::default());
+            exit_code(merge_command(merge).await, false)
+        }
+        Some(Command::Verify(verify)) => {
+            init_tracing(LogLevel::Warn, None, false, &Arc::default());
+            exit_code(verify_command(verify).await, false)
+        }
+    }
+}
 
+/// Runs a download (or only its probe, for `oxidown probe`) with logging, the version
+/// check and `--write-out` around it.
+async fn download_command(args: Args, probe_only: bool) -> ExitCode {
     // Initialize tracing with log level control
-    init_tracing(args.log_level, args.debug);
+    let warnings = Arc::new(WarningCollector::default());
+    let very_verbose = args.very_verbose || args.debug >= 2;
+    let log_level = if args.silent {
+        LogLevel::Off
+    } else if args.quiet {
+        LogLevel::Error
+    } else if args.trace_requests || very_verbose {
+        LogLevel::Trace
+    } else {
+        args.log_level
+    };
+    // --silent and --quiet still win over an explicit filter
+    let log_filter = args
+        .log_filter
+        .clone()
+        .filter(|_| !args.silent && !args.quiet);
+    let debug_mode = (args.debug > 0 || very_verbose) && !args.silent;
+    init_tracing(log_level, log_filter, debug_mode, &warnings);
+
+    let silent = args.silent;
+    let write_out = args.write_out.clone();
+    let started = Instant::now();
+    let mut summary = DownloadSummary {
+        url_effective: args.url.clone(),
+        ..Default::default()
+    };
+
+    // Runs alongside the download so it never delays it
+    let user_agent = resolve_user_agent(&args);
+    let version_check = (args.version_check && !silent)
+        .then(|| build_client(&client_options(&args, &user_agent)).ok())
+        .flatten()
+        .map(|client| tokio::spawn(check_latest_version(client)));
+
+    let result = run(args, &user_agent, &mut summary, probe_only).await;
+    if let Some(handle) = version_check {
+        let _ = handle.await;
+    }
+
+    summary.time_total = started.elapsed().as_secs_f64();
+    if let Some(template) = write_out {
+        print!("{}", expand_write_out(&template, &summary));
+    }
+
+    // Repeat warnings that were logged while a progress bar was drawing over them
+    let collected = warnings.take();
+    if !collected.is_empty() && !silent {
+        eprintln!("Warnings during download:");
+        for warning in &collected {
+            eprintln!("  - {}", warning);
+        }
+    }
+
+    exit_code(result, silent)
+}
+
+/// Prints the error of a failed command (unless `silent`) and maps it to the exit code.
+fn exit_code(result: Result<(), ProgramError>, silent: bool) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if !silent {
+                eprintln!("Error: {}", e);
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// `oxidown merge`: merges the part files left in `--parts-dir` into `--output`.
+async fn merge_command(merge: MergeArgs) -> Result<(), ProgramError> {
+    let parts_dir = merge.parts_dir.clone().unwrap_or_else(|| {
+        merge
+            .output
+            .parent()
+            .unwrap_or(Path::new("."))
+            .to_path_buf()
+    });
+    let progress = ProgressOptions {
+        show: !merge.no_progress,
+        use_si: false,
+    };
+    merge_from_disk(
+        &merge.output,
+        &parts_dir,
+        merge.temp_file_prefix.as_deref(),
+        &merge.temp_file_suffix,
+        merge.merge_buffer_size,
+        progress,
+        merge.direct_io,
+        merge.output_mode,
+    )
+    .await?;
+    Ok(())
+}
+
+/// `oxidown verify`: checks `--output` and prints `<file>: OK` like `sha256sum -c`.
+async fn verify_command(verify: VerifyArgs) -> Result<(), ProgramError> {
+    verify_download(
+        &verify.output,
+        verify.checksum.as_deref(),
+        verify.checksum_file.as_deref(),
+        &[],
+    )
+    .await?;
+    println!("{}: OK", verify.output.display());
+    Ok(())
+}
+
+/// Merges the `<output>.part<N>` files found in `temp_dir` (`--merge-only`, `oxidown merge`).
+///
+/// # Returns
+///
+/// * `Ok(u64)` - Size of the merged file.
+#[allow(clippy::too_many_arguments)]
+async fn merge_from_disk(
+    output: &Path,
+    temp_dir: &Path,
+    prefix: Option<&str>,
+    suffix: &str,
+    buffer_size: usize,
+    progress: ProgressOptions,
+    direct_io: bool,
+    mode: OutputMode,
+) -> Result<u64, ProgramError> {
+    let parts = reassemble_from_disk(output, temp_dir, prefix, suffix).await?;
+    let total_len = parts.last().map_or(0, |p| p.end_inclusive + 1);
+    info!(
+        "Merging {} parts ({}) found in {:?}",
+        parts.len(),
+        format_bytes(total_len, progress.use_si),
+        temp_dir
+    );
+
+    let pb_merge = new_merge_progress(total_len, progress);
+    merge_parts(
+        output,
+        &parts,
+        buffer_size,
+        Some(&pb_merge),
+        progress.use_si,
+        direct_io,
+        mode,
+    )
+    .await?;
+    pb_merge.finish_with_message("Merge completed");
+    Ok(total_len)
+}
+
+/// Runs the download described by the parsed command-line arguments.
+///
+/// With `probe_only`, stops after the probe and prints what the server reported.
+/// Statistics are recorded into `summary` as they become known, so they are
+/// available for `--write-out` even if the download fails.
+async fn run(
+    args: Args,
+    user_agent: &str,
+    summary: &mut DownloadSummary,
+    probe_only: bool,
+) -> Result<(), ProgramError> {
+    let progress = ProgressOptions {
+        show: !(args.no_progress || args.quiet || args.silent),
+        use_si: args.si,
+    };
 
     if args.threads == 0 {
         return Err(ProgramError::ArgNotValid(
             "threads must be >= 1".to_string(),
         ));
     }
+    if args.max_parts == Some(0) {
+        return Err(ProgramError::ArgNotValid(
+            "max-parts must be >= 1".to_string(),
+        ));
+    }
+    if args.max_concurrent_parts == Some(0) {
+        return Err(ProgramError::ArgNotValid(
+            "max-concurrent-parts must be >= 1".to_string(),
+        ));
+    }
+    if args.part_strategy != PartStrategy::Equal
+        && !(args.part_geo_ratio.is_finite() && args.part_geo_ratio >= 1.0)
+    {
+        return Err(ProgramError::ArgNotValid(
+            "part-geo-ratio must be a number >= 1".to_string(),
+        ));
+    }
+    let part_strategy = match args.part_strategy {
+        PartStrategy::Equal => PartSizeStrategy::Equal,
+        PartStrategy::GeoInc => PartSizeStrategy::GeometricIncrease {
+            ratio: args.part_geo_ratio,
+        },
+        PartStrategy::GeoDec => PartSizeStrategy::GeometricDecrease {
+            ratio: args.part_geo_ratio,
+        },
+    };
+    if args.socket_timeout == Some(0) {
+        return Err(ProgramError::ArgNotValid(
+            "socket-timeout must be >= 1".to_string(),
+        ));
+    }
+    if args.merge_buffer_size == 0 {
+        return Err(ProgramError::ArgNotValid(
+            "merge-buffer-size must be >= 1".to_string(),
+        ));
+    }
+    if args.rate_limit == Some(0) || args.rate_limit_burst == Some(0) {
+        return Err(ProgramError::ArgNotValid(
+            "rate-limit and rate-limit-burst must be >= 1".to_string(),
+        ));
+    }
 
-    // Derive output path
-    let output_path = match args.output {
-        Some(p) => p,
-        None => PathBuf::from(get_filename_from_url(&args.url)),
+    // A saved plan replaces the URL, output and split of the command line
+    let plan = match &args.execute_plan {
+        Some(path) => Some(load_plan(path).await?),
+        None => None,
     };
+    let url = plan
+        .as_ref()
+        .map_or(args.url.as_str(), |plan| plan.url.as_str());
+
+    if args.no_verify_ssl {
+        warn!("--no-verify-ssl is deprecated; use --insecure");
+    }
+    // Printed directly so that no log level can hide it
+    if (args.insecure || args.no_verify_ssl) && !args.silent {
+        eprintln!("WARNING: TLS certificate verification is DISABLED. This is insecure.");
+    }
+
+    if let Some(user) = &args.auth_user {
+        let scheme = args.http_auth_scheme.unwrap_or(AuthScheme::Basic);
+        if let AuthScheme::Ntlm | AuthScheme::Negotiate = scheme {
+            return Err(ProgramError::ArgNotValid(format!(
+                "--http-auth-scheme {} is not supported: it needs the platform's SSPI or GSSAPI",
+                format!("{:?}", scheme).to_ascii_lowercase()
+            )));
+        }
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .ok_or_else(|| {
+                ProgramError::ArgNotValid("--auth-user needs a URL with a host".to_string())
+            })?;
+        auth::init(HttpAuth {
+            scheme,
+            user: user.clone(),
+            password: args.auth_password.clone().unwrap_or_default(),
+            host,
+            digest: Mutex::new(None),
+        });
+    }
+
+    // One bucket shared by all connections, so the limit applies to the total rate
+    let bucket = args.rate_limit.map(|rate| {
+        let burst = args.rate_limit_burst.unwrap_or(rate);
+        debug!(rate_limit = rate, burst = burst, "Rate limiting enabled");
+        Arc::new(Mutex::new(TokenBucket::new(burst, rate)))
+    });
+
+    if let Some(plan) = plan {
+        return execute_plan(&args, user_agent, plan, summary, progress, bucket).await;
+    }
+
+    // Recovery: merge the part files of a crashed download, no network involved
+    if let Some(output_path) = args.output.clone().filter(|_| args.merge_only) {
+        let temp_dir = temp_dir_for(&args, &output_path);
+        summary.size_download = merge_from_disk(
+            &output_path,
+            &temp_dir,
+            args.temp_file_prefix.as_deref(),
+            &args.temp_file_suffix,
+            args.merge_buffer_size,
+            progress,
+            args.direct_io,
+            args.output_mode,
+        )
+        .await?;
+
+        let digests = verify_download(
+            &output_path,
+            args.checksum.as_deref(),
+            args.checksum_file.as_deref(),
+            &output_hash_algorithms(&args),
+        )
+        .await?;
+        report_output_hashes(&args, &digests, &output_path).await?;
+        apply_mtime(&args, &output_path, None)?;
+        info!("File saved to {:?}", output_path);
+        return Ok(());
+    }
 
     info!("Starting download: {}", args.url);
-    info!("Output: {:?}", output_path);
 
-    let proxy_mode = if args.proxy.is_some() {
-        ProxyMode::Custom
-    } else {
-        args.proxy_mode
+    let redirects = Arc::new(AtomicU32::new(0));
+    let client_options = ClientOptions {
+        redirect_count: Some(&redirects),
+        ..client_options(&args, user_agent)
+    };
+    let client = build_client(&client_options)?;
+
+    if args.spider_recursive {
+        return spider_recursive(&client, &args.url, args.spider_depth).await;
+    }
+    if args.spider {
+        return spider(&client, &args.url, progress.use_si).await;
+    }
+
+    // Probe (with its own connect timeout if requested)
+    let probe_client = match args.probe_connect_timeout {
+        Some(secs) => build_client(&ClientOptions {
+            connect_timeout: Some(Duration::from_secs(secs)),
+            ..client_options
+        })?,
+        None => client.clone(),
+    };
+
+    let conditional = conditional_headers(
+        args.if_modified_since.as_deref(),
+        args.if_none_match.as_deref(),
+    )?;
+    let post_body = match (&args.post_data, &args.post_json) {
+        (Some(data), _) => Some(PostBody {
+            content_type: "application/x-www-form-urlencoded",
+            data: data.clone(),
+        }),
+        (None, Some(json)) => Some(PostBody {
+            content_type: "application/json",
+            data: json.clone(),
+        }),
+        (None, None) => None,
     };
-    let client = build_client(&args.user_agent, proxy_mode, args.proxy.as_deref())?;
+    let probe_timeout = Duration::from_secs(args.probe_timeout);
+    redirects.store(0, Ordering::Relaxed);
+    let mut probe_result = probe(
+        &probe_client,
+        &args.url,
+        args.probe_retries,
+        probe_timeout,
+        &conditional,
+        post_body.as_ref(),
+        &args.expect_header,
+    )
+    .await?;
+    record_redirects(&mut probe_result, &redirects);
+    summary.http_code = probe_result.status_code;
+    summary.url_effective = probe_result.final_url.clone();
+    if probe_only {
+        print_probe_result(&args, &probe_result, progress.use_si);
+        return Ok(());
+    }
+    if probe_result.status_code == StatusCode::NOT_MODIFIED.as_u16() {
+        if !args.silent {
+            eprintln!("File not modified, skipping");
+        }
+        return Ok(());
+    }
+
+    // Derive output path, possibly from the server's Content-Disposition
+    let output_given = args.output.is_some();
+    let output_path = FilenameDeriver.derive_filename(
+        &args.url,
+        probe_result.content_disposition.as_deref(),
+        args.output.clone(),
+    );
+    summary.filename_effective = output_path.display().to_string();
+    info!("Output: {:?}", output_path);
+
+    // --output-fd never opens the output path, so the path is not checked
+    #[cfg(unix)]
+    let output_to_fd = args.output_fd.is_some();
+    #[cfg(not(unix))]
+    let output_to_fd = false;
+
+    // Devices have no meaningful file size and cannot be renamed or backed up
+    let output_is_device = !output_to_fd && is_device(&output_path);
+    if output_is_device {
+        warn!(output = ?output_path, "Writing to a block or character device");
+    }
+    if !output_to_fd {
+        check_output_mode(&output_path, args.output_mode)?;
+    }
+    let temp_dir = temp_dir_for(&args, &output_path);
+
+    if args.follow_ftp && probe_result.final_url.starts_with("ftp://") {
+        warn!("Following redirect to FTP, which is unencrypted and less secure than HTTPS");
+        let pb = new_progress_bar(0, progress.show);
+        pb.set_style(style_download_bar(progress.use_si));
+        pb.set_message("Downloading");
+
+        ftp_download(&probe_result.final_url, &output_path, &pb, args.output_mode).await?;
+        pb.finish_with_message("Download completed");
+        summary.size_download = fs::metadata(&output_path).await?.len();
+
+        let digests = verify_download(
+            &output_path,
+            args.checksum.as_deref(),
+            args.checksum_file.as_deref(),
+            &output_hash_algorithms(&args),
+        )
+        .await?;
+        report_output_hashes(&args, &digests, &output_path).await?;
+        apply_mtime(&args, &output_path, probe_result.last_modified.as_deref())?;
+        info!("Download completed successfully");
+        return Ok(());
+    }
 
-    // Probe
-    let probe_result = probe(&client, &args.url).await?;
     info!(
         "File size: {} (Accept Ranges: {})",
-        format_bytes(probe_result.content_length),
+        format_bytes(probe_result.content_length, progress.use_si),
         probe_result.accept_ranges
     );
+    debug!(content_type = ?probe_result.content_type, "Probe completed");
+
+    // An unknown length (0) cannot be checked up front
+    let len = probe_result.content_length;
+    if len > 0 {
+        if let Some(min) = args.min_filesize
+            && len < min
+        {
+            return Err(ProgramError::Other(format!(
+                "file too small: {} < min {}",
+                format_bytes_exact(len),
+                format_bytes_exact(min)
+            )));
+        }
+        if let Some(max) = args.max_filesize
+            && len > max
+        {
+            return Err(ProgramError::Other(format!(
+                "file too large: {} > max {}",
+                format_bytes_exact(len),
+                format_bytes_exact(max)
+            )));
+        }
+    }
+
+    let mut expected_checksum = args.checksum.clone();
+    if expected_checksum.is_none() && args.auto_checksum {
+        expected_checksum = fetch_checksum_sidecar(&client, &args.url).await?;
+    }
+
+    let retry = retry_policy(&args);
+
+    #[cfg(unix)]
+    if let Some(fd) = args.output_fd {
+        if post_body.is_some() {
+            return Err(ProgramError::ArgNotValid(
+                "--output-fd cannot be used with POST".to_string(),
+            ));
+        }
+        summary.filename_effective = format!("fd {}", fd);
+        summary.size_download = download_to_fd(
+            &client,
+            &probe_result.final_url,
+            fd,
+            probe_result.content_length,
+            progress,
+            bucket.as_deref(),
+        )
+        .await?;
+        info!(
+            "Saved to file descriptor {} ({} bytes)",
+            fd, summary.size_download
+        );
+        return Ok(());
+    }
+
+    // Arbitrary byte range: a single request, no split and no size check
+    if let Some((start, end)) = args.range {
+        if post_body.is_some() {
+            return Err(ProgramError::ArgNotValid(
+                "--range cannot be used with POST".to_string(),
+            ));
+        }
+        let pb = new_progress_bar(end - start + 1, progress.show);
+        pb.set_style(style_download_bar(progress.use_si));
+        pb.set_message("Downloading range");
+
+        let mut out = open_output_file(&output_path, args.output_mode).await?;
+        summary.size_download = download_to_writer(
+            &client,
+            &probe_result.final_url,
+            Some((start, end)),
+            &mut out,
+            Some(&pb),
+            bucket.as_deref(),
+        )
+        .await?;
+
+        pb.finish_with_message("Download completed");
+        apply_mtime(&args, &output_path, probe_result.last_modified.as_deref())?;
+        info!(
+            "Range {}-{} saved to {:?} ({} bytes)",
+            start, end, output_path, summary.size_download
+        );
+        return Ok(());
+    }
+
+    // Debugging aid: show which part files of this download exist, then stop
+    if args.parts_dir_info {
+        let parts = split_into_parts(
+            &probe_result.final_url,
+            probe_result.content_length,
+            part_count(args.threads, args.max_parts, probe_result.content_length),
+            part_strategy,
+            &output_path,
+            &temp_dir,
+            args.temp_file_prefix.as_deref(),
+            &args.temp_file_suffix,
+        )?;
+        for line in describe_parts(&parts).await {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    // Debugging aid: fetch a single part of the normal split, then stop
+    if let Some(idx) = args.output_part {
+        if !(probe_result.accept_ranges || args.force_multipart) || post_body.is_some() {
+            return Err(ProgramError::ArgNotValid(
+                "--output-part requires range support and cannot be used with POST".to_string(),
+            ));
+        }
+        fs::create_dir_all(&temp_dir).await?;
+
+        let parts = split_into_parts(
+            &probe_result.final_url,
+            probe_result.content_length,
+            part_count(args.threads, args.max_parts, probe_result.content_length),
+            part_strategy,
+            &output_path,
+            &temp_dir,
+            args.temp_file_prefix.as_deref(),
+            &args.temp_file_suffix,
+        )?;
+        let mut part = parts.get(idx).cloned().ok_or_else(|| {
+            ProgramError::ArgNotValid(format!(
+                "--output-part {} out of range (file is split into {} parts)",
+                idx,
+                parts.len()
+            ))
+        })?;
+        if output_given {
+            part.path = output_path.clone();
+        }
+
+        let size = part.expected_size();
+        let downloaded = download_parts_parallel(
+            client.clone(),
+            probe_result.final_url.clone(),
+            vec![part],
+            size,
+            &retry,
+            progress,
+            bucket.clone(),
+        )
+        .await?;
+        let part = &downloaded[0];
+        let hash = match &part.expected_hash {
+            Some(hash) => hash.clone(),
+            None => hash_file(&part.path, &[HashAlgorithm::Sha256])
+                .await?
+                .remove(0),
+        };
+        let actual_size = fs::metadata(&part.path).await?.len();
+        summary.size_download = actual_size;
+
+        println!(
+            "Part {}: bytes {}-{}, {} bytes, sha256 {}",
+            part.idx, part.start, part.end_inclusive, actual_size, hash
+        );
+        info!("Part saved to {:?}", part.path);
+        return Ok(());
+    }
 
     // Fallback
-    if !probe_result.accept_ranges || args.threads == 1 || probe_result.content_length == 0 {
+    // Servers rarely honor ranges on repeated POSTs, so POST downloads are always single-part.
+    // Ranges of a compressed body cannot be decompressed independently either; a coding
+    // --decompress cannot undo is rejected there before anything is written.
+    let accept_ranges = probe_result.accept_ranges || args.force_multipart;
+    let compressed = args.decompress
+        && probe_result
+            .content_encoding
+            .as_deref()
+            .is_some_and(|value| !is_identity(value));
+    let single = !accept_ranges
+        || post_body.is_some()
+        || compressed
+        || args.append_headers
+        || probe_result.content_length == 0
+        || output_is_device;
+
+    if let Some(plan_path) = &args.save_plan {
+        if single {
+            return Err(ProgramError::Other(
+                "--save-plan needs a download in parts: range support, a known size, \
+                 and no POST, --decompress or --append-headers"
+                    .to_string(),
+            ));
+        }
+        let plan = DownloadPlan {
+            url: probe_result.final_url.clone(),
+            output: output_path.clone(),
+            threads: args.max_concurrent_parts.unwrap_or(args.threads),
+            total_len: probe_result.content_length,
+            etag: probe_result.etag.clone(),
+            last_modified: probe_result.last_modified.clone(),
+            proxy_mode: client_options.proxy_mode,
+            proxy: args.proxy.clone(),
+            checksum: expected_checksum
+                .as_deref()
+                .map(parse_expected)
+                .transpose()?,
+            parts: split_into_parts(
+                &probe_result.final_url,
+                probe_result.content_length,
+                part_count(args.threads, args.max_parts, probe_result.content_length),
+                part_strategy,
+                &output_path,
+                &temp_dir,
+                args.temp_file_prefix.as_deref(),
+                &args.temp_file_suffix,
+            )?,
+        };
+        save_plan(plan_path, &plan).await?;
+        if !args.silent {
+            eprintln!(
+                "Plan for {} parts saved to {}",
+                plan.parts.len(),
+                plan_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if single || args.threads == 1 {
         warn!("Falling back to single download");
-        single_download(
+        // A redirected POST may have turned into a GET, so it is sent to the original URL
+        let url = match post_body {
+            Some(_) => &args.url,
+            None => &probe_result.final_url,
+        };
+        summary.size_download = single_download(
             &client,
-            &args.url,
+            url,
             &output_path,
             probe_result.content_length,
+            progress,
+            post_body.as_ref(),
+            bucket.as_deref(),
+            args.output_mode,
+            args.decompress,
+            args.append_headers.then_some(args.headers_format),
         )
         .await?;
+        // Hashing the device would also read whatever follows the written image
+        let digests = if output_is_device {
+            if expected_checksum.is_some()
+                || args.checksum_file.is_some()
+                || !output_hash_algorithms(&args).is_empty()
+            {
+                warn!("Checksum verification is skipped when writing to a device");
+            }
+            Vec::new()
+        } else {
+            verify_download(
+                &output_path,
+                expected_checksum.as_deref(),
+                args.checksum_file.as_deref(),
+                &output_hash_algorithms(&args),
+            )
+            .await?
+        };
+        report_output_hashes(&args, &digests, &output_path).await?;
+        if !output_is_device {
+            apply_mtime(&args, &output_path, probe_result.last_modified.as_deref())?;
+        }
         info!("Download completed successfully");
         return Ok(());
     }
 
     // Multi-part
-    let threads = args
-        .threads
-        .min(probe_result.content_length as usize)
-        .max(1);
+    fs::create_dir_all(&temp_dir).await?;
 
-    let temp_dir = args
-        .temp_dir
-        .unwrap_or_else(|| output_path.parent().unwrap_or(Path::new(".")).to_path_buf());
+    let mut retries_remaining = args.retry_all;
 
-    fs::create_dir_all(&temp_dir).await?;
+    // Retry the whole download from scratch if parts fail permanently (--retry-all)
+    let parts = loop {
+        let parts = split_into_parts(
+            &probe_result.final_url,
+            probe_result.content_length,
+            part_count(args.threads, args.max_parts, probe_result.content_length),
+            part_strategy,
+            &output_path,
+            &temp_dir,
+            args.temp_file_prefix.as_deref(),
+            &args.temp_file_suffix,
+        )?;
 
-    let parts = split_into_parts(
-        probe_result.content_length,
-        threads,
-        &output_path,
-        &temp_dir,
-    )?;
+        // Only part files left for this exact file and split are resumed
+        let mut resume = resume_state(&probe_result, &parts);
+        prepare_resume(&parts, &resume).await?;
+
+        // Ctrl+C stops the parts cleanly and leaves them for the next run
+        let cancel = CancellationToken::new();
+        let sigint = cancel_on_sigint(cancel.clone());
+        let result = download_parts_parallel_with_cancel(
+            client.clone(),
+            probe_result.final_url.clone(),
+            parts.clone(),
+            probe_result.content_length,
+            &retry,
+            progress,
+            bucket.clone(),
+            args.max_concurrent_parts.unwrap_or(args.threads),
+            &cancel,
+        )
+        .await;
+        drop(sigint);
 
-    download_parts_parallel(
-        client,
-        args.url.clone(),
-        parts.clone(),
+        match result {
+            Ok(ParallelOutcome::Completed(downloaded)) => break downloaded,
+            Ok(ParallelOutcome::Cancelled(partial)) => {
+                resume.progress = partial;
+                return save_interrupted(&args, &parts, &resume, progress).await;
+            }
+            Err(e) if retries_remaining > 0 => {
+                retries_remaining -= 1;
+                warn!(
+                    attempt = args.retry_all - retries_remaining,
+                    error = %e,
+                    "Download failed, restarting entire download"
+                );
+                cleanup_parts(&parts).await?;
+                // The first probe already passed the conditional check
+                redirects.store(0, Ordering::Relaxed);
+                probe_result = probe(
+                    &probe_client,
+                    &args.url,
+                    args.probe_retries,
+                    probe_timeout,
+                    &HeaderMap::new(),
+                    None,
+                    &args.expect_header,
+                )
+                .await?;
+                record_redirects(&mut probe_result, &redirects);
+            }
+            Err(e) => {
+                report_kept_parts(&args, &parts).await;
+                return Err(e);
+            }
+        }
+    };
+
+    summary.size_download = probe_result.content_length;
+    merge_and_verify(
+        &args,
+        &output_path,
+        &parts,
         probe_result.content_length,
-        args.retries,
-        args.retry_delay,
+        expected_checksum.as_deref(),
+        probe_result.last_modified.as_deref(),
+        progress,
     )
-    .await?;
+    .await
+}
+
+/// Runs a download saved with `--save-plan`: the server is not probed, and the saved
+/// split is downloaded, merged and verified as in a normal multipart download.
+async fn execute_plan(
+    args: &Args,
+    user_agent: &str,
+    plan: DownloadPlan,
+    summary: &mut DownloadSummary,
+    progress: ProgressOptions,
+    bucket: Option<Arc<Mutex<TokenBucket>>>,
+) -> Result<(), ProgramError> {
+    info!(
+        "Executing plan: {} -> {:?} ({} parts)",
+        plan.url,
+        plan.output,
+        plan.parts.len()
+    );
+    summary.url_effective = plan.url.clone();
+    summary.filename_effective = plan.output.display().to_string();
+
+    let client = build_client(&ClientOptions {
+        proxy_mode: plan.proxy_mode,
+        proxy: plan.proxy.as_deref(),
+        ..client_options(args, user_agent)
+    })?;
+    check_output_mode(&plan.output, args.output_mode)?;
+    for dir in plan.parts.iter().filter_map(|p| p.path.parent()) {
+        fs::create_dir_all(dir).await?;
+    }
+
+    let retry = retry_policy(args);
+    let mut retries_remaining = args.retry_all;
+    let parts = loop {
+        let mut resume = ResumeState {
+            total_len: plan.total_len,
+            etag: plan.etag.clone(),
+            last_modified: plan.last_modified.clone(),
+            parts: plan.parts.iter().map(ResumePart::from).collect(),
+            progress: PartialResult::default(),
+        };
+        prepare_resume(&plan.parts, &resume).await?;
+
+        let cancel = CancellationToken::new();
+        let sigint = cancel_on_sigint(cancel.clone());
+        let result = download_parts_parallel_with_cancel(
+            client.clone(),
+            plan.url.clone(),
+            plan.parts.clone(),
+            plan.total_len,
+            &retry,
+            progress,
+            bucket.clone(),
+            plan.threads,
+            &cancel,
+        )
+        .await;
+        drop(sigint);
+
+        match result {
+            Ok(ParallelOutcome::Completed(downloaded)) => break downloaded,
+            Ok(ParallelOutcome::Cancelled(partial)) => {
+                resume.progress = partial;
+                return save_interrupted(args, &plan.parts, &resume, progress).await;
+            }
+            Err(e) if retries_remaining > 0 => {
+                retries_remaining -= 1;
+                warn!(
+                    attempt = args.retry_all - retries_remaining,
+                    error = %e,
+                    "Download failed, restarting entire download"
+                );
+                cleanup_parts(&plan.parts).await?;
+            }
+            Err(e) => {
+                report_kept_parts(args, &plan.parts).await;
+                return Err(e);
+            }
+        }
+    };
+
+    summary.size_download = plan.total_len;
+    let checksum = plan.checksum.as_ref().map(|(_, hex)| hex.as_str());
+    merge_and_verify(
+        args,
+        &plan.output,
+        &parts,
+        plan.total_len,
+        checksum,
+        plan.last_modified.as_deref(),
+        progress,
+    )
+    .await
+}
 
-    // Merge with a spinner
-    let pb_merge = indicatif::ProgressBar::new_spinner();
-    pb_merge.set_style(style_spinner());
-    pb_merge.set_message("Merging parts...");
-    pb_merge.enable_steady_tick(std::time::Duration::from_millis(100));
+/// Merges the downloaded `parts` into `output_path`, then verifies and reports the
+/// checksums and sets the modification time.
+async fn merge_and_verify(
+    args: &Args,
+    output_path: &Path,
+    parts: &[Part],
+    total_len: u64,
+    expected_checksum: Option<&str>,
+    last_modified: Option<&str>,
+    progress: ProgressOptions,
+) -> Result<(), ProgramError> {
+    let pb_merge = new_merge_progress(total_len, progress);
 
-    merge_parts(&output_path, &parts).await?;
+    merge_parts(
+        output_path,
+        parts,
+        args.merge_buffer_size,
+        Some(&pb_merge),
+        progress.use_si,
+        args.direct_io,
+        args.output_mode,
+    )
+    .await?;
 
     pb_merge.finish_with_message("Merge completed");
 
+    let digests = verify_download(
+        output_path,
+        expected_checksum,
+        args.checksum_file.as_deref(),
+        &output_hash_algorithms(args),
+    )
+    .await?;
+    report_output_hashes(args, &digests, output_path).await?;
+    apply_mtime(args, output_path, last_modified)?;
+
     info!("File saved to {:?}", output_path);
     Ok(())
 }
+
+/// Returns `--user-agent` if given, otherwise the expanded `--user-agent-template`.
+fn resolve_user_agent(args: &Args) -> String {
+    match &args.user_agent {
+        Some(ua) => ua.clone(),
+        None => expand_user_agent_template(&args.user_agent_template),
+    }
+}
+
+/// Saves where a cancelled multipart download stopped (`resume.progress`) to the resume
+/// state file and tells the user how to continue.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::Other)` - The download was cancelled.
+async fn save_interrupted(
+    args: &Args,
+    parts: &[Part],
+    resume: &ResumeState,
+    progress: ProgressOptions,
+) -> Result<(), ProgramError> {
+    let state = write_resume_state(parts, resume).await?;
+    let partial = &resume.progress;
+    if !args.silent {
+        eprintln!(
+            "Download interrupted: {} of {} parts complete, {} received. \
+             Run the same command again to resume (state saved to {}).",
+            partial.completed_parts.len(),
+            parts.len(),
+            format_bytes(partial.bytes_downloaded, progress.use_si),
+            state.display()
+        );
+    }
+    Err(ProgramError::Other("download cancelled".to_string()))
+}
+
+/// Lists the part files left on disk after a failed download (`--keep-parts-on-error`).
+async fn report_kept_parts(args: &Args, parts: &[Part]) {
+    if args.keep_parts_on_error {
+        eprintln!("Part files kept for inspection:");
+        for line in describe_parts(parts).await {
+            eprintln!("  {}", line);
+        }
+    }
+}
+
+/// Retry and timeout settings of the part downloads.
+fn retry_policy(args: &Args) -> RetryPolicy {
+    RetryPolicy {
+        max_retries: args.retries,
+        retry_delay_ms: args.retry_delay,
+        retry_delay_max_ms: args.retry_delay_max,
+        max_connection_attempts: args.max_connection_attempts,
+        max_timeouts_per_part: args.max_timeouts_per_part,
+        connect_timeout: (args.connect_timeout > 0)
+            .then(|| Duration::from_secs(args.connect_timeout)),
+        read_timeout: read_timeout(args),
+        retry_on: args.retry_on.clone(),
+        fail_fast: !args.no_fail_fast,
+    }
+}
+
+/// Sets the modification time of the finished output file for `--set-mtime` or `--timestamp`
+/// (from `last_modified`, the server's `Last-Modified` header); otherwise leaves it alone.
+fn apply_mtime(
+    args: &Args,
+    output: &Path,
+    last_modified: Option<&str>,
+) -> Result<(), ProgramError> {
+    let mtime = match (args.set_mtime, args.timestamp) {
+        (Some(secs), _) => secs,
+        (None, true) => match last_modified.and_then(parse_date) {
+            Some(secs) => secs,
+            None => {
+                warn!("Server sent no usable Last-Modified date, modification time not set");
+                return Ok(());
+            }
+        },
+        (None, false) => return Ok(()),
+    };
+    set_file_mtime(output, mtime)
+}
+
+/// Describes the remote file and the split of `parts` for the resume state file.
+fn resume_state(probe_result: &ProbeResult, parts: &[Part]) -> ResumeState {
+    ResumeState {
+        total_len: probe_result.content_length,
+        etag: probe_result.etag.clone(),
+        last_modified: probe_result.last_modified.clone(),
+        parts: parts.iter().map(ResumePart::from).collect(),
+        progress: PartialResult::default(),
+    }
+}
+
+/// Stores the redirects followed by the last probe in `result`, warning about long chains.
+fn record_redirects(result: &mut ProbeResult, redirects: &AtomicU32) {
+    result.redirect_count = redirects.swap(0, Ordering::Relaxed);
+    if result.redirect_count > REDIRECT_WARN_THRESHOLD {
+        warn!(
+            redirects = result.redirect_count,
+            final_url = %result.final_url,
+            "Long redirect chain, the link may be outdated"
+        );
+    } else if result.redirect_count > 0 {
+        debug!(redirects = result.redirect_count, final_url = %result.final_url, "Redirects followed");
+    }
+}
+
+/// Prints the probe result for `oxidown probe`.
+fn print_probe_result(args: &Args, result: &ProbeResult, use_si: bool) {
+    println!("Status: {}", result.status_code);
+    if result.final_url != args.url {
+        println!(
+            "Redirected to: {} ({} redirects)",
+            result.final_url, result.redirect_count
+        );
+    }
+    println!(
+        "Content-Type: {}",
+        result.content_type.as_deref().unwrap_or("unknown")
+    );
+    match result.content_length {
+        0 => println!("Size: unknown"),
+        len => println!("Size: {} ({} bytes)", format_bytes(len, use_si), len),
+    }
+    println!(
+        "Accept-Ranges: {}",
+        if result.accept_ranges { "yes" } else { "no" }
+    );
+    let filename = FilenameDeriver.derive_filename(
+        &args.url,
+        result.content_disposition.as_deref(),
+        args.output.clone(),
+    );
+    println!("Filename: {}", filename.display());
+}
+
+/// Directory for part files: `--temp-dir`, or the directory of the output file.
+fn temp_dir_for(args: &Args, output_path: &Path) -> PathBuf {
+    args.temp_dir
+        .clone()
+        .unwrap_or_else(|| output_path.parent().unwrap_or(Path::new(".")).to_path_buf())
+}
+
+/// Per-read timeout: the shorter of `--read-timeout` and `--socket-timeout`.
+///
+/// tokio sockets are non-blocking, so `SO_RCVTIMEO` would never fire; reqwest's read
+/// timeout bounds each read the same way.
+fn read_timeout(args: &Args) -> Option<Duration> {
+    let read = (args.read_timeout > 0).then(|| Duration::from_secs(args.read_timeout));
+    let socket = args.socket_timeout.map(Duration::from_millis);
+    match (read, socket) {
+        (Some(read), Some(socket)) => Some(read.min(socket)),
+        (read, socket) => read.or(socket),
+    }
+}
+
+/// Collects the HTTP client settings from the command line.
+fn client_options<'a>(args: &'a Args, user_agent: &'a str) -> ClientOptions<'a> {
+    let proxy_mode = if args.proxy.is_some() {
+        ProxyMode::Custom
+    } else {
+        args.proxy_mode
+    };
+    ClientOptions {
+        user_agent,
+        proxy_mode,
+        proxy: args.proxy.as_deref(),
+        no_http_cache: args.no_http_cache,
+        cache_control: args.cache_control.as_deref(),
+        accept: args.accept.as_deref(),
+        accept_encoding: args
+            .accept_encoding
+            .as_deref()
+            .or(args.decompress.then_some("gzip, deflate")),
+        cookies: args.cookies_string.as_deref(),
+        http_version: args.http_version,
+        interface: args.interface.as_deref(),
+        connect_timeout: (args.connect_timeout > 0)
+            .then(|| Duration::from_secs(args.connect_timeout)),
+        read_timeout: read_timeout(args),
+        socket_timeout: args.socket_timeout.map(Duration::from_millis),
+        follow_ftp: args.follow_ftp,
+        keepalive_idle: args.keepalive_idle.map(Duration::from_secs),
+        keepalive_count: args.keepalive_count,
+        cacert: args.cacert.as_deref(),
+        insecure: args.insecure || args.no_verify_ssl,
+        tls_min_version: args.tls_min_version,
+        tls_max_version: args.tls_max_version,
+        cipher_list: args.cipher_list.as_deref(),
+        tls_hostname: args.tls_hostname.as_deref(),
+        pool_idle_timeout: args.pool_idle_timeout.map(Duration::from_secs),
+        dns_cache_ttl: (args.dns_cache_ttl > 0).then(|| Duration::from_secs(args.dns_cache_ttl)),
+        redirect_count: None,
+    }
+}
+
+/// Streams the whole download into the open file descriptor `fd` (`--output-fd`).
+///
+/// Always a single request: part files and merging need a seekable path.
+///
+/// # Safety requirements
+///
+/// `fd` must be an open descriptor, writable, and not owned by anything else in this
+/// process. It is wrapped with `File::from_raw_fd`, which takes ownership and closes it
+/// when the download ends. Passing a descriptor that is closed or reused elsewhere
+/// would write to an unrelated file.
+#[cfg(unix)]
+async fn download_to_fd(
+    client: &reqwest::Client,
+    url: &str,
+    fd: i32,
+    total_size: u64,
+    progress: ProgressOptions,
+    bucket: Option<&Mutex<TokenBucket>>,
+) -> Result<u64, ProgramError> {
+    use std::os::unix::io::FromRawFd;
+
+    // Wrapping a closed descriptor would abort on drop, so check it is open first
+    // SAFETY: F_GETFD only reads the descriptor flags
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(ProgramError::ArgNotValid(format!(
+            "output-fd {} is not an open file descriptor",
+            fd
+        )));
+    }
+    // SAFETY: the caller hands the descriptor to us via --output-fd (see above)
+    let mut out = File::from_std(unsafe { std::fs::File::from_raw_fd(fd) });
+
+    let pb = new_download_progress(total_size, progress);
+    let written = download_to_writer(client, url, None, &mut out, Some(&pb), bucket).await?;
+    pb.finish_with_message("Download completed");
+    Ok(written)
+}
+
+/// Number of parts to split a file of `total_len` bytes into.
+///
+/// `--max-parts` caps the split count; `--threads` still sets the requested concurrency.
+fn part_count(threads: usize, max_parts: Option<usize>, total_len: u64) -> usize {
+    threads
+        .min(max_parts.unwrap_or(usize::MAX))
+        .min(total_len as usize)
+        .max(1)
+}
+
+/// Creates the merge bar, showing the merged byte count out of `total_len`.
+fn new_merge_progress(total_len: u64, progress: ProgressOptions) -> ProgressBar {
+    let pb = new_progress_bar(total_len, progress.show);
+    pb.set_style(style_merge_bar());
+    pb.set_message(format!(
+        "Merging: 0 B of {}",
+        format_bytes(total_len, progress.use_si)
+    ));
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+/// Hash algorithms to compute after the download: `--output-hash`, then the
+/// `--output-checksum-algo` if `--output-checksum-file` is set.
+fn output_hash_algorithms(args: &Args) -> Vec<HashAlgorithm> {
+    args.output_hash
+        .into_iter()
+        .chain(
+            args.output_checksum_file
+                .is_some()
+                .then_some(args.output_checksum_algo),
+        )
+        .collect()
+}
+
+/// Prints the `--output-hash` digest in GNU coreutils format (`<hash>  <filename>`)
+/// and appends the `--output-checksum-file` entry.
+///
+/// `digests` are in the order of [`output_hash_algorithms`], or empty if hashing was skipped.
+async fn report_output_hashes(
+    args: &Args,
+    digests: &[String],
+    output_path: &Path,
+) -> Result<(), ProgramError> {
+    let mut digests = digests.iter();
+    if args.output_hash.is_some()
+        && let Some(hash) = digests.next()
+    {
+        println!(
+            "{}",
+            checksum_line(hash, &output_path.display().to_string())
+        );
+    }
+    if let Some(path) = &args.output_checksum_file
+        && let Some(hash) = digests.next()
+    {
+        write_checksum_file(path, hash, &output_path.display().to_string()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestServer, serve_file};
+
+    const HIDDEN: ProgressOptions = ProgressOptions {
+        show: false,
+        use_si: false,
+    };
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn download_to_fd_writes_into_a_pipe() {
+        use std::os::unix::io::FromRawFd;
+
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let server = TestServer::start(serve_file(data.clone(), &[])).await;
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        // SAFETY: the read end was just created by pipe() and is owned here only
+        let mut reader = File::from_std(unsafe { std::fs::File::from_raw_fd(fds[0]) });
+        let read = tokio::spawn(async move {
+            let mut out = Vec::new();
+            tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut out)
+                .await
+                .unwrap();
+            out
+        });
+
+        // Takes ownership of the write end and closes it when done, ending the read
+        let written = download_to_fd(
+            &reqwest::Client::new(),
+            &format!("{}/file.bin", server.url),
+            fds[1],
+            data.len() as u64,
+            HIDDEN,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(read.await.unwrap(), data);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn download_to_fd_rejects_a_closed_descriptor() {
+        let server = TestServer::start(serve_file(b"data".to_vec(), &[])).await;
+
+        // Descriptors are allocated lowest first, so a closed high one is not reused by
+        // the other tests
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let fd = unsafe { libc::fcntl(fds[1], libc::F_DUPFD, 900) };
+        assert!(fd >= 900);
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+            libc::close(fd);
+        }
+
+        let err = download_to_fd(
+            &reqwest::Client::new(),
+            &format!("{}/file.bin", server.url),
+            fd,
+            4,
+            HIDDEN,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+        assert!(server.requests().is_empty());
+    }
+}
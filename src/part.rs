@@ -1,80 +1,165 @@
-use std::path::Path;
+use indicatif::ProgressBar;
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 use tokio::{
-    fs::{self, File, OpenOptions},
-    io::{AsyncWriteExt, copy},
+    fs::{self, File},
+    io::{AsyncWriteExt, BufReader, copy_buf},
 };
-use tracing::{debug, info, instrument};
+use tracing::{Instrument, debug, debug_span, field, info, instrument, warn};
 
 use crate::error::ProgramError;
 use crate::progress::format_bytes;
-use crate::types::Part;
+use crate::types::{
+    HashAlgorithm, OutputMode, Part, PartSizeStrategy, PartStatus, ResumePart, ResumeState,
+};
+use crate::utils::{check_output_mode, open_output_file, output_mode_error};
+use crate::verify::hash_file;
 
 impl Part {
-    /// Returns the expected size of this part in bytes
-    #[allow(dead_code)]
+    /// Returns the expected size of this part in bytes.
+    ///
+    /// Saturates at `u64::MAX` for the (unrepresentable) full `0..=u64::MAX` range, and
+    /// is 0 for an empty range (`end_inclusive < start`).
     pub fn expected_size(&self) -> u64 {
-        self.end_inclusive - self.start + 1
+        self.end_inclusive
+            .checked_sub(self.start)
+            .map_or(0, |len| len.saturating_add(1))
+    }
+
+    /// Returns true if the byte ranges of `self` and `other` share at least one byte.
+    pub fn overlaps(&self, other: &Part) -> bool {
+        self.start <= other.end_inclusive && other.start <= self.end_inclusive
+    }
+
+    /// Inspects this part's temporary file and compares its size with [`Part::expected_size`].
+    pub async fn status(&self) -> PartStatus {
+        match fs::metadata(&self.path).await {
+            Ok(meta) if meta.len() == self.expected_size() => PartStatus::Complete,
+            Ok(meta) => PartStatus::Partial(meta.len()),
+            Err(_) => PartStatus::Missing,
+        }
+    }
+
+    /// Checks that `parts` cover `0..total_len` exactly once, in order and without gaps.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(ProgramError::Other)` - Describing the first invalid part.
+    pub fn validate_all(parts: &[Part], total_len: u64) -> Result<(), ProgramError> {
+        let mut next_start = 0;
+        for (i, p) in parts.iter().enumerate() {
+            if i > 0 && p.overlaps(&parts[i - 1]) {
+                return Err(ProgramError::Other(format!(
+                    "invalid part {}: range {}-{} overlaps part {}",
+                    p.idx,
+                    p.start,
+                    p.end_inclusive,
+                    parts[i - 1].idx
+                )));
+            }
+            if p.start != next_start || p.end_inclusive < p.start {
+                return Err(ProgramError::Other(format!(
+                    "invalid part {}: range {}-{} (expected start {})",
+                    p.idx, p.start, p.end_inclusive, next_start
+                )));
+            }
+            next_start += p.expected_size();
+        }
+        if next_start != total_len {
+            return Err(ProgramError::Other(format!(
+                "parts cover {} bytes, expected {}",
+                next_start, total_len
+            )));
+        }
+        Ok(())
     }
 }
 
-/// Calculates part boundaries and splits the total file size into equal chunks.
+/// Calculates part boundaries and splits the total file size into chunks.
 ///
 /// # Arguments
 ///
+/// * `url` - URL every part is downloaded from, stored in [`Part::url`].
 /// * `total_len` - Total size of the file in bytes.
 /// * `threads` - Number of parts/threads to split into.
+/// * `strategy` - How the part sizes are chosen.
 /// * `output` - The final output path (used to name temporary part files).
 /// * `temp_dir` - Directory where temporary part files will be stored.
+/// * `prefix` / `suffix` - Part files are named `<prefix><idx><suffix>`; the prefix
+///   defaults to `<output file name>.part` (`--temp-file-prefix`, `--temp-file-suffix`).
 ///
 /// # Returns
 ///
 /// * `Ok(Vec<Part>)` - A vector of `Part` structs describing each chunk.
+/// * `Err(ProgramError::ArgNotValid)` - If a part file or the resume state file would
+///   be the output file itself.
+#[allow(clippy::too_many_arguments)]
 pub fn split_into_parts(
+    url: &str,
     total_len: u64,
     threads: usize,
+    strategy: PartSizeStrategy,
     output: &Path,
     temp_dir: &Path,
+    prefix: Option<&str>,
+    suffix: &str,
 ) -> Result<Vec<Part>, ProgramError> {
-    let mut parts = Vec::with_capacity(threads);
-
-    let base_name = output
-        .file_name()
-        .ok_or_else(|| ProgramError::ArgNotValid("output has no file name".to_string()))?
-        .to_string_lossy()
-        .to_string();
-
-    let chunk = total_len / threads as u64;
-    let mut start = 0u64;
+    if total_len == 0 {
+        return Err(ProgramError::ArgNotValid("total length is 0".to_string()));
+    }
 
-    for idx in 0..threads {
-        let mut end = if idx == threads - 1 {
-            total_len - 1
-        } else {
-            start + chunk - 1
-        };
+    let prefix = part_name_prefix(output, prefix, suffix)?;
 
-        // Handle tiny files / rounding
-        if end >= total_len {
-            end = total_len - 1;
+    // Every part needs at least one byte
+    let threads = threads.clamp(1, usize::try_from(total_len).unwrap_or(usize::MAX));
+    let sizes = match strategy {
+        PartSizeStrategy::Equal => equal_sizes(total_len, threads),
+        PartSizeStrategy::GeometricIncrease { ratio } => {
+            geometric_sizes(total_len, threads, ratio, false)
         }
+        PartSizeStrategy::GeometricDecrease { ratio } => {
+            geometric_sizes(total_len, threads, ratio, true)
+        }
+    };
 
-        let part_path = temp_dir.join(format!("{}.part{}", base_name, idx));
+    let mut parts = Vec::with_capacity(sizes.len());
+    let mut start = 0u64;
+    for (idx, size) in sizes.into_iter().enumerate() {
+        let part_path = temp_dir.join(format!("{}{}{}", prefix, idx, suffix));
+        if same_path(&part_path, output) {
+            return Err(ProgramError::ArgNotValid(format!(
+                "temporary part file {:?} would overwrite the output file",
+                part_path
+            )));
+        }
         parts.push(Part {
             idx,
             start,
-            end_inclusive: end,
+            end_inclusive: start + size - 1,
             path: part_path,
+            url: url.to_string(),
+            expected_hash: None,
+            resumed: false,
         });
+        start += size;
+    }
 
-        start = end + 1;
-        if start >= total_len {
-            break;
-        }
+    Part::validate_all(&parts, total_len)?;
+    if let Some(state) = resume_state_path(&parts)
+        && same_path(&state, output)
+    {
+        return Err(ProgramError::ArgNotValid(format!(
+            "resume state file {:?} would overwrite the output file",
+            state
+        )));
     }
 
     debug!(
         total_len = total_len,
         threads = threads,
+        strategy = ?strategy,
         actual_parts = parts.len(),
         "File split into parts"
     );
@@ -82,18 +167,197 @@ pub fn split_into_parts(
     Ok(parts)
 }
 
+/// Returns the file name prefix of the part files: `prefix` if given, else `<output file name>.part`.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` - If `output` has no file name, or `prefix` or
+///   `suffix` contains a path separator (part files always live in the temp directory).
+fn part_name_prefix(
+    output: &Path,
+    prefix: Option<&str>,
+    suffix: &str,
+) -> Result<String, ProgramError> {
+    let prefix = match prefix {
+        Some(prefix) => prefix.to_string(),
+        None => {
+            let base_name = output
+                .file_name()
+                .ok_or_else(|| ProgramError::ArgNotValid("output has no file name".to_string()))?
+                .to_string_lossy();
+            format!("{}.part", base_name)
+        }
+    };
+    if prefix
+        .chars()
+        .chain(suffix.chars())
+        .any(std::path::is_separator)
+    {
+        return Err(ProgramError::ArgNotValid(
+            "temp-file-prefix and temp-file-suffix cannot contain path separators".to_string(),
+        ));
+    }
+    Ok(prefix)
+}
+
+/// Returns true if `a` and `b` name the same file, comparing absolute paths
+/// (`./out.bin` and `out.bin` are the same); symbolic links are not resolved.
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (std::path::absolute(a), std::path::absolute(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Splits `total_len` into `n` equal sizes; the last part takes the remainder.
+fn equal_sizes(total_len: u64, n: usize) -> Vec<u64> {
+    let chunk = total_len / n as u64;
+    let mut sizes = vec![chunk; n];
+    sizes[n - 1] += total_len % n as u64;
+    sizes
+}
+
+/// Splits `total_len` into `n` sizes following a geometric series with the given ratio.
+///
+/// Boundaries are rounded from the cumulative series, so the sizes always sum to
+/// `total_len` and each part keeps at least one byte.
+fn geometric_sizes(total_len: u64, n: usize, ratio: f64, decreasing: bool) -> Vec<u64> {
+    let weights: Vec<f64> = (0..n)
+        .map(|i| {
+            let exp = if decreasing { n - 1 - i } else { i };
+            ratio.powi(exp as i32)
+        })
+        .collect();
+    let total_weight: f64 = weights.iter().sum();
+
+    let mut sizes = Vec::with_capacity(n);
+    let mut cumulative = 0.0;
+    let mut prev_end = 0u64;
+    for (i, weight) in weights.iter().enumerate() {
+        cumulative += weight;
+        let remaining_parts = (n - 1 - i) as u64;
+        let end = if remaining_parts == 0 {
+            total_len
+        } else {
+            ((total_len as f64 * cumulative / total_weight).round() as u64)
+                .clamp(prev_end + 1, total_len - remaining_parts)
+        };
+        sizes.push(end - prev_end);
+        prev_end = end;
+    }
+    sizes
+}
+
+/// Rebuilds the parts of an interrupted download from the files left in `temp_dir`.
+///
+/// Files named `<prefix><N><suffix>` are collected (see [`split_into_parts`] for the
+/// defaults); `N` becomes the part index
+/// and the byte ranges follow from the file sizes, in index order. This recovers
+/// downloads that crashed before writing any state (`--merge-only`), so every part
+/// must be complete: the sizes are not checked against the server.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Part>)` - The parts sorted by index.
+/// * `Err(ProgramError::Other)` - If no parts are found, an index is missing or a part is empty.
+pub async fn reassemble_from_disk(
+    output: &Path,
+    temp_dir: &Path,
+    prefix: Option<&str>,
+    suffix: &str,
+) -> Result<Vec<Part>, ProgramError> {
+    let prefix = part_name_prefix(output, prefix, suffix)?;
+
+    let mut found = Vec::new();
+    let mut entries = fs::read_dir(temp_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let Some(idx) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix(&prefix))
+            .and_then(|n| n.strip_suffix(suffix))
+            .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| n.parse::<usize>().ok())
+        else {
+            continue;
+        };
+        if entry.file_type().await?.is_file() {
+            found.push((idx, entry.path(), entry.metadata().await?.len()));
+        }
+    }
+    if found.is_empty() {
+        return Err(ProgramError::Other(format!(
+            "no {}<N>{} files found in {:?}",
+            prefix, suffix, temp_dir
+        )));
+    }
+    found.sort_unstable_by_key(|(idx, _, _)| *idx);
+
+    let mut parts = Vec::with_capacity(found.len());
+    let mut start = 0u64;
+    for (expected_idx, (idx, path, len)) in found.into_iter().enumerate() {
+        if idx != expected_idx {
+            return Err(ProgramError::Other(format!(
+                "part {} is missing in {:?}",
+                expected_idx, temp_dir
+            )));
+        }
+        if len == 0 {
+            return Err(ProgramError::Other(format!(
+                "part file {:?} is empty",
+                path
+            )));
+        }
+        parts.push(Part {
+            idx,
+            start,
+            end_inclusive: start + len - 1,
+            path,
+            url: String::new(),
+            expected_hash: None,
+            resumed: false,
+        });
+        start += len;
+    }
+
+    debug!(
+        num_parts = parts.len(),
+        total_len = start,
+        "Parts found on disk"
+    );
+    Ok(parts)
+}
+
 /// Merges all downloaded parts into the final output file.
 ///
 /// This function reads each temporary part file in order and appends it to the output file.
-/// It uses efficient `io::copy` (which may use zero-copy syscalls like `sendfile` on Linux).
+/// Each part is read through a buffer of `buffer_size` bytes; larger buffers reduce
+/// syscall overhead on slow disks. Resumed parts with an `expected_hash` are re-hashed
+/// first, as their data was partly read back from a file left by an earlier run; the
+/// others were hashed as they were streamed to disk and are not read twice.
+/// An existing output file is kept as a backup until the merge succeeds (see [`MergeGuard`]);
+/// in `append` mode it is truncated back to its old length if the merge fails.
 /// After successful merging, temporary files are deleted.
 ///
 /// # Arguments
 ///
 /// * `output` - Path to the final output file.
 /// * `parts` - Vector of parts (used to locate temp files).
-#[instrument(skip(parts), fields(output = ?output, num_parts = parts.len()))]
-pub async fn merge_parts(output: &Path, parts: &[Part]) -> Result<(), ProgramError> {
+/// * `buffer_size` - Read buffer size in bytes.
+/// * `progress` - Bar advanced by the size of each merged part.
+/// * `use_si` - Format byte counts with SI instead of IEC units.
+/// * `direct_io` - Bypass the page cache with `O_DIRECT` on Linux (`--direct-io`).
+/// * `mode` - How the output file is opened (`--output-mode`).
+#[instrument(skip(parts, progress, use_si), fields(output = ?output, num_parts = parts.len()))]
+pub async fn merge_parts(
+    output: &Path,
+    parts: &[Part],
+    buffer_size: usize,
+    progress: Option<&ProgressBar>,
+    use_si: bool,
+    direct_io: bool,
+    mode: OutputMode,
+) -> Result<(), ProgramError> {
     info!("Merging parts into final file");
     debug!("Merging {} parts into {:?}...", parts.len(), output);
 
@@ -101,35 +365,960 @@ pub async fn merge_parts(output: &Path, parts: &[Part]) -> Result<(), ProgramErr
     let mut parts_sorted = parts.to_vec();
     parts_sorted.sort_by_key(|p| p.idx);
 
-    let mut out = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(output)
-        .await?;
-
-    let mut total_merged: u64 = 0;
     for p in &parts_sorted {
-        debug!(part = p.idx, path = ?p.path, "Merging part");
-        let mut f = File::open(&p.path).await?;
-        let copied = copy(&mut f, &mut out).await?;
-        total_merged += copied;
-        debug!(part = p.idx, bytes = copied, "Part merged");
+        if let Some(expected) = p.expected_hash.as_ref().filter(|_| p.resumed) {
+            let actual = hash_file(&p.path, &[HashAlgorithm::Sha256]).await?;
+            if actual[0] != *expected {
+                return Err(ProgramError::Other(format!(
+                    "Part {} checksum mismatch before merge",
+                    p.idx
+                )));
+            }
+            debug!(part = p.idx, "Part checksum verified");
+        }
     }
-    out.flush().await?;
+
+    check_output_mode(output, mode)?;
+    let guard = MergeGuard::new(output, mode)?;
+    // The checked file has been moved to the backup, so it is created again
+    let open_mode = if guard.backup.is_some() {
+        OutputMode::Create
+    } else {
+        mode
+    };
+    let total_merged = if direct_io && mode == OutputMode::Append {
+        warn!("--direct-io cannot append to an existing file, merging with buffered I/O");
+        merge_buffered(
+            output,
+            &parts_sorted,
+            open_mode,
+            buffer_size,
+            progress,
+            use_si,
+        )
+        .await?
+    } else if direct_io {
+        merge_direct(
+            output,
+            &parts_sorted,
+            open_mode,
+            buffer_size,
+            progress,
+            use_si,
+        )
+        .await?
+    } else {
+        merge_buffered(
+            output,
+            &parts_sorted,
+            open_mode,
+            buffer_size,
+            progress,
+            use_si,
+        )
+        .await?
+    };
+    guard.commit();
 
     info!(
         total_merged = total_merged,
-        total_human = %format_bytes(total_merged),
+        total_human = %format_bytes(total_merged, use_si),
         "Merge completed"
     );
 
-    // Cleanup
-    debug!("Cleaning up temporary part files");
-    for p in &parts_sorted {
-        debug!(part = p.idx, path = ?p.path, "Removing temp file");
-        fs::remove_file(&p.path).await?;
+    cleanup_parts(&parts_sorted).await
+}
+
+/// Appends the sorted parts to `output` through the page cache (the default merge).
+async fn merge_buffered(
+    output: &Path,
+    parts_sorted: &[Part],
+    mode: OutputMode,
+    buffer_size: usize,
+    progress: Option<&ProgressBar>,
+    use_si: bool,
+) -> Result<u64, ProgramError> {
+    let mut out = open_output_file(output, mode).await?;
+
+    let mut total_merged: u64 = 0;
+    for p in parts_sorted {
+        // One span per part, so trace consumers see the time each copy took
+        let span = debug_span!(
+            "merge_part",
+            idx = p.idx,
+            bytes = field::Empty,
+            duration_ms = field::Empty
+        );
+        let started = Instant::now();
+        let copied = async {
+            debug!(path = ?p.path, "Merging part");
+            let f = File::open(&p.path).await?;
+            let mut reader = BufReader::with_capacity(buffer_size, f);
+            copy_buf(&mut reader, &mut out).await
+        }
+        .instrument(span.clone())
+        .await?;
+        span.record("bytes", copied)
+            .record("duration_ms", started.elapsed().as_millis() as u64);
+        span.in_scope(|| debug!("Part merged"));
+        total_merged += copied;
+        if let Some(pb) = progress {
+            advance_merge_bar(pb, copied, use_si);
+        }
+    }
+    out.flush().await?;
+    Ok(total_merged)
+}
+
+/// Appends the sorted parts to `output` with `O_DIRECT` (`--direct-io`).
+///
+/// The copy runs on a blocking thread (see [`crate::direct_io::merge_files`]). If the
+/// file system rejects `O_DIRECT`, the merge is redone with buffered I/O.
+#[cfg(target_os = "linux")]
+async fn merge_direct(
+    output: &Path,
+    parts_sorted: &[Part],
+    mode: OutputMode,
+    buffer_size: usize,
+    progress: Option<&ProgressBar>,
+    use_si: bool,
+) -> Result<u64, ProgramError> {
+    debug!("Merging with direct I/O");
+    let out = output.to_path_buf();
+    let inputs: Vec<PathBuf> = parts_sorted.iter().map(|p| p.path.clone()).collect();
+    let pb = progress.cloned();
+    let result = tokio::task::spawn_blocking(move || {
+        crate::direct_io::merge_files(&out, &inputs, mode, buffer_size, |copied| {
+            if let Some(pb) = &pb {
+                advance_merge_bar(pb, copied, use_si);
+            }
+        })
+    })
+    .await
+    .map_err(|e| ProgramError::Other(format!("direct I/O merge task failed: {}", e)))?;
+
+    match result {
+        Ok(total) => Ok(total),
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {
+            warn!(error = %e, "Direct I/O not supported here, merging with buffered I/O");
+            if let Some(pb) = progress {
+                pb.set_position(0);
+            }
+            // The file may have been created by the direct attempt
+            merge_buffered(
+                output,
+                parts_sorted,
+                OutputMode::Create,
+                buffer_size,
+                progress,
+                use_si,
+            )
+            .await
+        }
+        Err(e) => Err(output_mode_error(output, mode, e)),
+    }
+}
+
+/// `O_DIRECT` is Linux-specific; elsewhere `--direct-io` falls back to buffered I/O.
+#[cfg(not(target_os = "linux"))]
+async fn merge_direct(
+    output: &Path,
+    parts_sorted: &[Part],
+    mode: OutputMode,
+    buffer_size: usize,
+    progress: Option<&ProgressBar>,
+    use_si: bool,
+) -> Result<u64, ProgramError> {
+    warn!("--direct-io is only supported on Linux, merging with buffered I/O");
+    merge_buffered(output, parts_sorted, mode, buffer_size, progress, use_si).await
+}
+
+/// Advances the merge bar by `copied` bytes and updates its byte count message.
+fn advance_merge_bar(pb: &ProgressBar, copied: u64, use_si: bool) {
+    pb.inc(copied);
+    pb.set_message(format!(
+        "Merging: {} of {}",
+        format_bytes(pb.position(), use_si),
+        format_bytes(pb.length().unwrap_or(0), use_si)
+    ));
+}
+
+/// Keeps a pre-existing output file safe while it is overwritten by a merge.
+///
+/// On creation, an existing output file is renamed to `<output>.oxidown_backup`.
+/// [`MergeGuard::commit`] deletes the backup; dropping the guard without committing
+/// (merge failed or was cancelled) moves the backup back to `output`.
+///
+/// In `append` mode the file stays in place and its length is recorded instead;
+/// dropping the guard truncates it back to that length.
+struct MergeGuard {
+    output: PathBuf,
+    backup: Option<PathBuf>,
+    append_from: Option<u64>,
+    committed: bool,
+}
+
+impl MergeGuard {
+    /// Moves an existing output file out of the way, or records its length for `append`.
+    fn new(output: &Path, mode: OutputMode) -> Result<Self, ProgramError> {
+        let mut backup = None;
+        let mut append_from = None;
+        if mode == OutputMode::Append {
+            append_from = match std::fs::metadata(output) {
+                Ok(meta) => Some(meta.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+        } else if output.exists() {
+            let mut name = output.as_os_str().to_owned();
+            name.push(".oxidown_backup");
+            let path = PathBuf::from(name);
+            debug!(backup = ?path, "Backing up existing output file");
+            std::fs::rename(output, &path)?;
+            backup = Some(path);
+        }
+
+        Ok(Self {
+            output: output.to_path_buf(),
+            backup,
+            append_from,
+            committed: false,
+        })
+    }
+
+    /// Marks the merge as successful and removes the backup.
+    fn commit(mut self) {
+        self.committed = true;
+        if let Some(backup) = &self.backup
+            && let Err(e) = std::fs::remove_file(backup)
+        {
+            warn!(backup = ?backup, error = %e, "Failed to remove backup of previous output file");
+        }
+    }
+}
+
+impl Drop for MergeGuard {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        if let Some(backup) = &self.backup {
+            debug!(backup = ?backup, "Merge failed, restoring previous output file");
+            if let Err(e) = std::fs::rename(backup, &self.output) {
+                warn!(backup = ?backup, error = %e, "Failed to restore previous output file");
+            }
+        }
+        if let Some(len) = self.append_from {
+            debug!(length = len, "Merge failed, removing appended data");
+            if let Err(e) = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&self.output)
+                .and_then(|f| f.set_len(len))
+            {
+                warn!(output = ?self.output, error = %e, "Failed to remove appended data");
+            }
+        }
+    }
+}
+
+/// Describes the temporary file of each part, one line per part.
+///
+/// # Examples
+///
+/// * `Part 0: "out.bin.part0" 524288/524288 bytes, complete`
+/// * `Part 1: "out.bin.part1" 1024/524288 bytes, partial`
+/// * `Part 2: "out.bin.part2" missing`
+pub async fn describe_parts(parts: &[Part]) -> Vec<String> {
+    let mut lines = Vec::with_capacity(parts.len());
+    for p in parts {
+        let expected = p.expected_size();
+        lines.push(match p.status().await {
+            PartStatus::Complete => format!(
+                "Part {}: {:?} {}/{} bytes, complete",
+                p.idx, p.path, expected, expected
+            ),
+            PartStatus::Partial(len) => format!(
+                "Part {}: {:?} {}/{} bytes, partial",
+                p.idx, p.path, len, expected
+            ),
+            PartStatus::Missing => format!("Part {}: {:?} missing", p.idx, p.path),
+        });
+    }
+    lines
+}
+
+/// Returns the path of the resume state file next to the part files
+/// (`out.bin.part0` -> `out.bin.resume`), or `None` without parts.
+pub fn resume_state_path(parts: &[Part]) -> Option<PathBuf> {
+    parts.first().map(|p| p.path.with_extension("resume"))
+}
+
+/// Version of the resume state format written by [`write_resume_state`].
+const RESUME_STATE_VERSION: u32 = 1;
+
+impl From<&Part> for ResumePart {
+    fn from(part: &Part) -> Self {
+        Self {
+            start: part.start,
+            end_inclusive: part.end_inclusive,
+            file_name: part
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+}
+
+/// Writes the resume state file of `parts`.
+///
+/// It is written when a multipart download starts ([`prepare_resume`]) and again with
+/// the progress when it is cancelled. The file describes the remote file and the split
+/// (one `part=<start>-<end> <file name>` line per part), then where the download
+/// stopped; `failed` only holds parts that stopped with an error before the cancellation:
+///
+/// ```text
+/// version=1
+/// total_len=1000000
+/// etag="5f3a-f4240"
+/// last_modified=Tue, 14 Oct 2026 14:42:36 GMT
+/// part=0-499999 out.bin.part0
+/// part=500000-999999 out.bin.part1
+/// completed=0
+/// interrupted=1
+/// failed=
+/// bytes_downloaded=786432
+/// ```
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` - The path of the written state file.
+pub async fn write_resume_state(
+    parts: &[Part],
+    state: &ResumeState,
+) -> Result<PathBuf, ProgramError> {
+    let path = resume_state_path(parts)
+        .ok_or_else(|| ProgramError::Other("no parts to record".to_string()))?;
+    fs::write(&path, format_resume_state(state)).await?;
+    debug!(path = ?path, "Resume state written");
+    Ok(path)
+}
+
+/// Formats `state` as the `key=value` lines described at [`write_resume_state`].
+fn format_resume_state(state: &ResumeState) -> String {
+    let join = |idx: &[usize]| {
+        idx.iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    let mut out = format!(
+        "version={}\ntotal_len={}\n",
+        RESUME_STATE_VERSION, state.total_len
+    );
+    if let Some(etag) = &state.etag {
+        out.push_str(&format!("etag={}\n", etag));
+    }
+    if let Some(last_modified) = &state.last_modified {
+        out.push_str(&format!("last_modified={}\n", last_modified));
+    }
+    for part in &state.parts {
+        out.push_str(&format!(
+            "part={}-{} {}\n",
+            part.start, part.end_inclusive, part.file_name
+        ));
+    }
+    out.push_str(&format!(
+        "completed={}\ninterrupted={}\nfailed={}\nbytes_downloaded={}\n",
+        join(&state.progress.completed_parts),
+        join(&state.progress.interrupted_parts),
+        join(&state.progress.failed_parts),
+        state.progress.bytes_downloaded
+    ));
+    out
+}
+
+/// Parses a resume state file written by [`write_resume_state`].
+///
+/// Returns `None` for another version, a missing `total_len`, a part file name with a
+/// path separator, or any malformed value; unknown keys are ignored.
+fn parse_resume_state(contents: &str) -> Option<ResumeState> {
+    let list = |value: &str| -> Option<Vec<usize>> {
+        value
+            .split(',')
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse().ok())
+            .collect()
+    };
+    let part = |value: &str| -> Option<ResumePart> {
+        let (range, file_name) = value.split_once(' ')?;
+        let (start, end) = range.split_once('-')?;
+        if file_name.is_empty() || file_name.contains(std::path::is_separator) {
+            return None;
+        }
+        Some(ResumePart {
+            start: start.parse().ok()?,
+            end_inclusive: end.parse().ok()?,
+            file_name: file_name.to_string(),
+        })
+    };
+
+    let mut state = ResumeState::default();
+    let (mut version, mut total_len) = (None, None);
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key {
+            "version" => version = Some(value.parse::<u32>().ok()?),
+            "total_len" => total_len = Some(value.parse().ok()?),
+            "etag" => state.etag = Some(value.to_string()),
+            "last_modified" => state.last_modified = Some(value.to_string()),
+            "part" => state.parts.push(part(value)?),
+            "completed" => state.progress.completed_parts = list(value)?,
+            "interrupted" => state.progress.interrupted_parts = list(value)?,
+            "failed" => state.progress.failed_parts = list(value)?,
+            "bytes_downloaded" => state.progress.bytes_downloaded = value.parse().ok()?,
+            _ => {}
+        }
     }
 
+    if version != Some(RESUME_STATE_VERSION) {
+        return None;
+    }
+    state.total_len = total_len?;
+    Some(state)
+}
+
+/// Checks the part files left by an earlier run before `parts` are downloaded.
+///
+/// Part files are only resumed if the resume state file shows they belong to the same
+/// remote file (size, `ETag` and `Last-Modified`) and were written for the same byte range.
+/// Otherwise appending to them would corrupt the output, so they are removed:
+///
+/// * all of them if there is no readable state file, or the remote file changed;
+/// * those whose range differs, if only the split changed (`--threads`, `--max-parts`,
+///   `--part-strategy`). Files of earlier parts not in the new split are removed too.
+///
+/// Then `state` (the current remote file and split, without progress) is written.
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` - The path of the written state file.
+pub async fn prepare_resume(parts: &[Part], state: &ResumeState) -> Result<PathBuf, ProgramError> {
+    let path = resume_state_path(parts)
+        .ok_or_else(|| ProgramError::Other("no parts to record".to_string()))?;
+    let previous = match fs::read_to_string(&path).await {
+        Ok(contents) => parse_resume_state(&contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let same_file = previous.as_ref().is_some_and(|p| {
+        p.total_len == state.total_len
+            && p.etag == state.etag
+            && p.last_modified == state.last_modified
+    });
+    let mut stale: Vec<PathBuf> = parts
+        .iter()
+        .filter(|part| {
+            !same_file
+                || previous.as_ref().and_then(|p| p.parts.get(part.idx))
+                    != Some(&ResumePart::from(*part))
+        })
+        .map(|part| part.path.clone())
+        .collect();
+    if let (Some(previous), Some(dir)) = (&previous, path.parent()) {
+        stale.extend(
+            previous
+                .parts
+                .iter()
+                .map(|p| dir.join(&p.file_name))
+                .filter(|file| !parts.iter().any(|part| part.path == *file)),
+        );
+    }
+
+    let mut discarded = 0;
+    for file in stale {
+        match fs::remove_file(&file).await {
+            Ok(()) => discarded += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if discarded > 0 {
+        let reason = match previous {
+            None => "no usable resume state",
+            Some(_) if !same_file => "the remote file changed",
+            Some(_) => "the parts were split differently",
+        };
+        warn!(
+            discarded = discarded,
+            "Discarding part files of an earlier download: {}", reason
+        );
+    }
+
+    write_resume_state(parts, state).await
+}
+
+/// Removes the temporary files of the given parts and their resume state file.
+///
+/// Parts whose file does not exist (e.g. not started yet) are skipped.
+pub async fn cleanup_parts(parts: &[Part]) -> Result<(), ProgramError> {
+    debug!("Cleaning up temporary part files");
+    let paths = parts
+        .iter()
+        .map(|p| p.path.clone())
+        .chain(resume_state_path(parts));
+    for path in paths {
+        debug!(path = ?path, "Removing temp file");
+        match fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestDir;
+    use crate::types::{OutputMode, PartialResult};
+
+    fn split(dir: &TestDir, total_len: u64, threads: usize) -> Vec<Part> {
+        split_into_parts(
+            "http://example.com/out.bin",
+            total_len,
+            threads,
+            PartSizeStrategy::Equal,
+            &dir.join("out.bin"),
+            &dir.0,
+            None,
+            "",
+        )
+        .unwrap()
+    }
+
+    fn state_for(parts: &[Part], total_len: u64, etag: Option<&str>) -> ResumeState {
+        ResumeState {
+            total_len,
+            etag: etag.map(str::to_string),
+            last_modified: Some("Tue, 14 Oct 2026 14:42:36 GMT".to_string()),
+            parts: parts.iter().map(ResumePart::from).collect(),
+            progress: PartialResult::default(),
+        }
+    }
+
+    #[test]
+    fn resume_state_round_trip() {
+        let state = ResumeState {
+            total_len: 3000,
+            etag: Some("\"5f3a-2fe\"".to_string()),
+            last_modified: None,
+            parts: (0..3)
+                .map(|i| ResumePart {
+                    start: i * 1000,
+                    end_inclusive: i * 1000 + 999,
+                    file_name: format!("out.bin.part{}", i),
+                })
+                .collect(),
+            progress: PartialResult {
+                completed_parts: vec![0],
+                interrupted_parts: vec![1, 2],
+                failed_parts: Vec::new(),
+                bytes_downloaded: 1500,
+            },
+        };
+        let text = format_resume_state(&state);
+        assert!(text.contains("interrupted=1,2\nfailed=\n"));
+        assert_eq!(parse_resume_state(&text), Some(state));
+    }
+
+    #[test]
+    fn resume_state_rejects_other_versions_and_garbage() {
+        assert_eq!(parse_resume_state("completed=0\nfailed=1\n"), None);
+        assert_eq!(parse_resume_state("version=2\ntotal_len=1\n"), None);
+        assert_eq!(parse_resume_state("version=1\ntotal_len=x\n"), None);
+        assert_eq!(parse_resume_state("version=1\npart=0-0 a.part0\n"), None);
+        for part in ["0+0 a.part0", "0-0", "0-x a.part0", "0-0 ../a.part0"] {
+            let text = format!("version=1\ntotal_len=1\npart={}\n", part);
+            assert_eq!(parse_resume_state(&text), None, "{part}");
+        }
+    }
+
+    #[tokio::test]
+    async fn matching_parts_are_kept() {
+        let dir = TestDir::new("resume-keep");
+        let parts = split(&dir, 300, 3);
+        let state = state_for(&parts, 300, Some("\"a\""));
+        write_resume_state(&parts, &state).await.unwrap();
+        std::fs::write(&parts[0].path, [1u8; 100]).unwrap();
+        std::fs::write(&parts[1].path, [2u8; 40]).unwrap();
+
+        prepare_resume(&parts, &state).await.unwrap();
+        assert!(parts[0].path.exists());
+        assert!(parts[1].path.exists());
+    }
+
+    #[tokio::test]
+    async fn parts_of_a_changed_file_are_discarded() {
+        let dir = TestDir::new("resume-etag");
+        let parts = split(&dir, 300, 3);
+        write_resume_state(&parts, &state_for(&parts, 300, Some("\"a\"")))
+            .await
+            .unwrap();
+        std::fs::write(&parts[0].path, [1u8; 100]).unwrap();
+
+        let changed = state_for(&parts, 300, Some("\"b\""));
+        prepare_resume(&parts, &changed).await.unwrap();
+        assert!(!parts[0].path.exists());
+        let written = std::fs::read_to_string(resume_state_path(&parts).unwrap()).unwrap();
+        assert_eq!(parse_resume_state(&written), Some(changed));
+    }
+
+    #[tokio::test]
+    async fn parts_of_another_split_are_discarded() {
+        let dir = TestDir::new("resume-split");
+        let old = split(&dir, 400, 2);
+        write_resume_state(&old, &state_for(&old, 400, None))
+            .await
+            .unwrap();
+        std::fs::write(&old[0].path, [1u8; 150]).unwrap();
+        std::fs::write(&old[1].path, [2u8; 150]).unwrap();
+
+        // Part 0 becomes 0-99 instead of 0-199, so its data cannot be appended to
+        let parts = split(&dir, 400, 4);
+        prepare_resume(&parts, &state_for(&parts, 400, None))
+            .await
+            .unwrap();
+        assert!(!parts[0].path.exists());
+        assert!(!parts[1].path.exists());
+    }
+
+    #[tokio::test]
+    async fn parts_beyond_a_smaller_split_are_removed() {
+        let dir = TestDir::new("resume-fewer");
+        let old = split(&dir, 400, 4);
+        write_resume_state(&old, &state_for(&old, 400, None))
+            .await
+            .unwrap();
+        for part in &old {
+            std::fs::write(&part.path, [1u8; 50]).unwrap();
+        }
+
+        let parts = split(&dir, 400, 2);
+        prepare_resume(&parts, &state_for(&parts, 400, None))
+            .await
+            .unwrap();
+        assert!(old.iter().all(|p| !p.path.exists()));
+    }
+
+    #[tokio::test]
+    async fn parts_without_state_are_discarded() {
+        let dir = TestDir::new("resume-none");
+        let parts = split(&dir, 300, 3);
+        std::fs::write(&parts[2].path, [3u8; 10]).unwrap();
+
+        prepare_resume(&parts, &state_for(&parts, 300, None))
+            .await
+            .unwrap();
+        assert!(!parts[2].path.exists());
+    }
+
+    async fn merge(dir: &TestDir, parts: &[Part]) -> Result<(), ProgramError> {
+        merge_parts(
+            &dir.join("out.bin"),
+            parts,
+            4096,
+            None,
+            false,
+            false,
+            OutputMode::Create,
+        )
+        .await
+    }
+
+    /// Fills the part files and records a hash that does not match it.
+    fn write_with_wrong_hash(parts: &mut [Part], resumed: bool) {
+        for part in parts {
+            std::fs::write(&part.path, vec![7u8; part.expected_size() as usize]).unwrap();
+            part.expected_hash = Some("0".repeat(64));
+            part.resumed = resumed;
+        }
+    }
+
+    #[tokio::test]
+    async fn resumed_parts_are_rehashed_before_merging() {
+        let dir = TestDir::new("merge-resumed");
+        let mut parts = split(&dir, 200, 2);
+        write_with_wrong_hash(&mut parts, true);
+
+        let err = merge(&dir, &parts).await.unwrap_err();
+        assert!(
+            matches!(&err, ProgramError::Other(msg) if msg == "Part 0 checksum mismatch before merge"),
+            "{err}"
+        );
+        assert!(!dir.join("out.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn streamed_parts_are_not_rehashed() {
+        let dir = TestDir::new("merge-streamed");
+        let mut parts = split(&dir, 200, 2);
+        write_with_wrong_hash(&mut parts, false);
+
+        merge(&dir, &parts).await.unwrap();
+        assert_eq!(std::fs::read(dir.join("out.bin")).unwrap(), [7u8; 200]);
+    }
+
+    /// Name and value of each field recorded on one span.
+    type SpanFields = Vec<(String, String)>;
+
+    /// Collects the fields of every `merge_part` span, in the order the spans are opened.
+    #[derive(Clone, Default)]
+    struct MergeSpans(std::sync::Arc<std::sync::Mutex<Vec<SpanFields>>>);
+
+    struct Fields<'a>(&'a mut SpanFields);
+
+    impl field::Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for MergeSpans
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if attrs.metadata().name() != "merge_part" {
+                return;
+            }
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = vec![("id".to_string(), id.into_u64().to_string())];
+            attrs.record(&mut Fields(&mut fields));
+            spans.push(fields);
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let id = id.into_u64().to_string();
+            let mut spans = self.0.lock().unwrap();
+            if let Some(fields) = spans.iter_mut().find(|f| f[0].1 == id) {
+                values.record(&mut Fields(fields));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn each_merged_part_gets_a_span_with_its_size() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let dir = TestDir::new("merge-spans");
+        let parts = split(&dir, 300, 3);
+        for part in &parts {
+            std::fs::write(&part.path, vec![1u8; part.expected_size() as usize]).unwrap();
+        }
+
+        let spans = MergeSpans::default();
+        let subscriber = tracing_subscriber::registry().with(spans.clone());
+        let _default = tracing::subscriber::set_default(subscriber);
+        merge(&dir, &parts).await.unwrap();
+
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 3);
+        for (idx, fields) in spans.iter().enumerate() {
+            let value = |name: &str| {
+                fields
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.as_str())
+            };
+            assert_eq!(value("idx"), Some(idx.to_string().as_str()));
+            assert_eq!(value("bytes"), Some("100"));
+            assert!(value("duration_ms").is_some(), "{fields:?}");
+        }
+    }
+
+    fn range(idx: usize, start: u64, end_inclusive: u64) -> Part {
+        Part {
+            idx,
+            start,
+            end_inclusive,
+            path: PathBuf::from(format!("out.bin.part{}", idx)),
+            url: String::new(),
+            expected_hash: None,
+            resumed: false,
+        }
+    }
+
+    #[test]
+    fn expected_size_counts_the_last_byte() {
+        assert_eq!(range(0, 0, 0).expected_size(), 1);
+        assert_eq!(range(0, 7, 7).expected_size(), 1);
+        assert_eq!(range(0, 0, 99).expected_size(), 100);
+        assert_eq!(range(0, 100, 199).expected_size(), 100);
+        assert_eq!(range(0, u64::MAX, u64::MAX).expected_size(), 1);
+        assert_eq!(range(0, 1, u64::MAX).expected_size(), u64::MAX);
+        assert_eq!(range(0, 0, u64::MAX).expected_size(), u64::MAX);
+    }
+
+    #[test]
+    fn expected_size_of_empty_range_is_zero() {
+        assert_eq!(range(0, 5, 4).expected_size(), 0);
+        assert_eq!(range(0, u64::MAX, 0).expected_size(), 0);
+    }
+
+    #[test]
+    fn overlaps() {
+        assert!(range(0, 0, 99).overlaps(&range(1, 99, 199)));
+        assert!(range(0, 0, 99).overlaps(&range(1, 50, 60)));
+        assert!(range(1, 50, 60).overlaps(&range(0, 0, 99)));
+        assert!(!range(0, 0, 99).overlaps(&range(1, 100, 199)));
+        assert!(!range(1, 100, 199).overlaps(&range(0, 0, 99)));
+    }
+
+    #[test]
+    fn validate_all_accepts_exact_cover() {
+        let parts = [range(0, 0, 99), range(1, 100, 100), range(2, 101, 999)];
+        Part::validate_all(&parts, 1000).unwrap();
+    }
+
+    #[test]
+    fn validate_all_rejects_bad_layouts() {
+        let cases: [(&[Part], &str); 6] = [
+            (&[range(0, 0, 99), range(1, 99, 199)], "overlaps part 0"),
+            (&[range(0, 0, 99), range(1, 101, 199)], "expected start 100"),
+            (&[range(0, 1, 199)], "expected start 0"),
+            (&[range(0, 0, 99), range(1, 100, 99)], "range 100-99"),
+            (&[range(0, 0, 99), range(1, 100, 198)], "cover 199 bytes"),
+            (&[], "cover 0 bytes"),
+        ];
+        for (parts, message) in cases {
+            let err = Part::validate_all(parts, 200).unwrap_err();
+            assert!(err.to_string().contains(message), "{err}");
+        }
+    }
+
+    #[test]
+    fn more_threads_than_bytes() {
+        let dir = TestDir::new("split-threads");
+        for strategy in [
+            PartSizeStrategy::Equal,
+            PartSizeStrategy::GeometricIncrease { ratio: 2.0 },
+            PartSizeStrategy::GeometricDecrease { ratio: 2.0 },
+        ] {
+            let parts = split_into_parts(
+                "http://example.com/out.bin",
+                5,
+                16,
+                strategy,
+                &dir.join("out.bin"),
+                &dir.0,
+                None,
+                "",
+            )
+            .unwrap();
+            assert_eq!(parts.len(), 5, "{strategy:?}");
+            assert!(parts.iter().all(|p| p.expected_size() == 1));
+            Part::validate_all(&parts, 5).unwrap();
+        }
+    }
+
+    #[test]
+    fn parts_cover_the_file() {
+        let dir = TestDir::new("split-cover");
+        for (total_len, threads) in [(1000, 3), (1001, 4), (7, 7), (u64::MAX, 8)] {
+            let parts = split(&dir, total_len, threads);
+            assert_eq!(parts.len(), threads);
+            assert_eq!(parts.last().unwrap().end_inclusive, total_len - 1);
+            Part::validate_all(&parts, total_len).unwrap();
+        }
+    }
+
+    #[test]
+    fn one_byte_file_has_one_part() {
+        let dir = TestDir::new("split-one");
+        let parts = split(&dir, 1, 8);
+        assert_eq!(parts.len(), 1);
+        assert_eq!((parts[0].start, parts[0].end_inclusive), (0, 0));
+        assert_eq!(parts[0].expected_size(), 1);
+    }
+
+    #[test]
+    fn empty_file_cannot_be_split() {
+        let dir = TestDir::new("split-empty");
+        for threads in [1, 8] {
+            let err = split_into_parts(
+                "http://example.com/out.bin",
+                0,
+                threads,
+                PartSizeStrategy::Equal,
+                &dir.join("out.bin"),
+                &dir.0,
+                None,
+                "",
+            )
+            .unwrap_err();
+            assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+        }
+    }
+
+    fn sizes(strategy: PartSizeStrategy, total_len: u64, threads: usize) -> Vec<u64> {
+        let dir = TestDir::new("split-strategy");
+        let parts = split_into_parts(
+            "http://example.com/out.bin",
+            total_len,
+            threads,
+            strategy,
+            &dir.join("out.bin"),
+            &dir.0,
+            None,
+            "",
+        )
+        .unwrap();
+        Part::validate_all(&parts, total_len).unwrap();
+        parts.iter().map(Part::expected_size).collect()
+    }
+
+    #[test]
+    fn strategies_split_100_mib_into_4_parts() {
+        const TOTAL: u64 = 100 * 1024 * 1024;
+        assert_eq!(sizes(PartSizeStrategy::Equal, TOTAL, 4), [TOTAL / 4; 4]);
+        // Weights 1, 2, 4, 8 out of 15
+        assert_eq!(
+            sizes(PartSizeStrategy::GeometricIncrease { ratio: 2.0 }, TOTAL, 4),
+            [6_990_507, 13_981_013, 27_962_027, 55_924_053]
+        );
+        assert_eq!(
+            sizes(PartSizeStrategy::GeometricDecrease { ratio: 2.0 }, TOTAL, 4),
+            [55_924_053, 27_962_027, 13_981_013, 6_990_507]
+        );
+        assert_eq!(
+            sizes(PartSizeStrategy::GeometricIncrease { ratio: 1.0 }, TOTAL, 4),
+            [TOTAL / 4; 4]
+        );
+    }
+
+    #[test]
+    fn steep_ratio_keeps_a_byte_per_part() {
+        let inc = sizes(PartSizeStrategy::GeometricIncrease { ratio: 1000.0 }, 10, 4);
+        assert_eq!(inc, [1, 1, 1, 7]);
+        let dec = sizes(PartSizeStrategy::GeometricDecrease { ratio: 1000.0 }, 10, 4);
+        assert_eq!(dec, [7, 1, 1, 1]);
+    }
+}
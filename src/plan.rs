@@ -0,0 +1,704 @@
+//! Download plans for `--save-plan` and `--execute-plan`.
+//!
+//! A plan is a small JSON document, written and parsed by hand:
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "url": "https://example.com/file.iso",
+//!   "output": "file.iso",
+//!   "threads": 8,
+//!   "total_len": 1000000,
+//!   "etag": "\"5f3a-f4240\"",
+//!   "last_modified": null,
+//!   "proxy_mode": "custom",
+//!   "proxy": "http://proxy:3128",
+//!   "checksum": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+//!   "checksum_algorithm": "sha256",
+//!   "parts": [
+//!     {"start": 0, "end_inclusive": 499999, "path": "file.iso.part0"},
+//!     {"start": 500000, "end_inclusive": 999999, "path": "file.iso.part1"}
+//!   ]
+//! }
+//! ```
+
+use clap::ValueEnum;
+use std::{iter::Peekable, path::Path, path::PathBuf, str::Chars};
+use tokio::fs;
+use tracing::debug;
+
+use crate::error::ProgramError;
+use crate::types::{DownloadPlan, HashAlgorithm, Part, ProxyMode};
+use crate::utils::json_string;
+use crate::verify::parse_expected;
+
+/// Version of the plan format written by [`save_plan`].
+const PLAN_VERSION: u64 = 1;
+
+/// Writes `plan` to `path` as JSON.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` - If the output or a part path is not valid UTF-8.
+pub async fn save_plan(path: &Path, plan: &DownloadPlan) -> Result<(), ProgramError> {
+    fs::write(path, format_plan(plan)?).await?;
+    debug!(path = ?path, parts = plan.parts.len(), "Plan written");
+    Ok(())
+}
+
+/// Reads a plan written by [`save_plan`].
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` - If the file is not a valid plan of this version.
+pub async fn load_plan(path: &Path) -> Result<DownloadPlan, ProgramError> {
+    let contents = fs::read_to_string(path).await?;
+    parse_plan(&contents)
+        .map_err(|e| ProgramError::ArgNotValid(format!("invalid plan {:?}: {}", path, e)))
+}
+
+/// Formats `plan` as the JSON document described in the module docs.
+fn format_plan(plan: &DownloadPlan) -> Result<String, ProgramError> {
+    let path_string = |path: &Path| {
+        path.to_str().map(json_string).ok_or_else(|| {
+            ProgramError::ArgNotValid(format!("plan paths must be valid UTF-8: {:?}", path))
+        })
+    };
+    let optional = |value: Option<&str>| value.map_or("null".to_string(), json_string);
+
+    let mut out = String::from("{\n");
+    out.push_str(&format!("  \"version\": {},\n", PLAN_VERSION));
+    out.push_str(&format!("  \"url\": {},\n", json_string(&plan.url)));
+    out.push_str(&format!("  \"output\": {},\n", path_string(&plan.output)?));
+    out.push_str(&format!("  \"threads\": {},\n", plan.threads));
+    out.push_str(&format!("  \"total_len\": {},\n", plan.total_len));
+    out.push_str(&format!(
+        "  \"etag\": {},\n",
+        optional(plan.etag.as_deref())
+    ));
+    out.push_str(&format!(
+        "  \"last_modified\": {},\n",
+        optional(plan.last_modified.as_deref())
+    ));
+    out.push_str(&format!(
+        "  \"proxy_mode\": {},\n",
+        json_string(proxy_mode_name(plan.proxy_mode))
+    ));
+    out.push_str(&format!(
+        "  \"proxy\": {},\n",
+        optional(plan.proxy.as_deref())
+    ));
+    let (algorithm, checksum) = match &plan.checksum {
+        Some((algorithm, hex)) => (algorithm.to_possible_value(), Some(hex.as_str())),
+        None => (None, None),
+    };
+    out.push_str(&format!("  \"checksum\": {},\n", optional(checksum)));
+    out.push_str(&format!(
+        "  \"checksum_algorithm\": {},\n",
+        optional(algorithm.as_ref().map(|v| v.get_name()))
+    ));
+    let parts = plan
+        .parts
+        .iter()
+        .map(|p| {
+            Ok(format!(
+                "    {{\"start\": {}, \"end_inclusive\": {}, \"path\": {}}}",
+                p.start,
+                p.end_inclusive,
+                path_string(&p.path)?
+            ))
+        })
+        .collect::<Result<Vec<_>, ProgramError>>()?;
+    out.push_str(&format!("  \"parts\": [\n{}\n  ]\n}}\n", parts.join(",\n")));
+    Ok(out)
+}
+
+/// Name of `mode` in a plan; `--proxy-mode` values, plus `custom` for `--proxy`.
+fn proxy_mode_name(mode: ProxyMode) -> &'static str {
+    match mode {
+        ProxyMode::Auto => "auto",
+        ProxyMode::Off => "off",
+        ProxyMode::Custom => "custom",
+    }
+}
+
+/// Parses and checks a plan: the version, the required fields, and that the parts
+/// cover the file exactly once. Unknown fields are ignored.
+fn parse_plan(contents: &str) -> Result<DownloadPlan, String> {
+    let mut parser = JsonParser {
+        chars: contents.chars().peekable(),
+    };
+    let document = parser.value()?;
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err("trailing characters after the plan".to_string());
+    }
+    let fields = document.object("plan")?;
+
+    let version = field(fields, "version")?.number("version")?;
+    if version != PLAN_VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+
+    let url = field(fields, "url")?.string("url")?.to_string();
+    let output = PathBuf::from(field(fields, "output")?.string("output")?);
+    let threads = usize::try_from(field(fields, "threads")?.number("threads")?)
+        .ok()
+        .filter(|&t| t > 0)
+        .ok_or("threads must be >= 1")?;
+    let total_len = field(fields, "total_len")?.number("total_len")?;
+    let etag = optional_string(fields, "etag")?;
+    let last_modified = optional_string(fields, "last_modified")?;
+
+    let proxy = optional_string(fields, "proxy")?;
+    let proxy_mode = match field(fields, "proxy_mode")?.string("proxy_mode")? {
+        "auto" => ProxyMode::Auto,
+        "off" => ProxyMode::Off,
+        "custom" if proxy.is_some() => ProxyMode::Custom,
+        "custom" => return Err("proxy_mode custom needs a proxy".to_string()),
+        other => return Err(format!("unknown proxy_mode {:?}", other)),
+    };
+
+    let checksum = match (
+        optional_string(fields, "checksum")?,
+        optional_string(fields, "checksum_algorithm")?,
+    ) {
+        (None, None) => None,
+        (Some(checksum), Some(name)) => {
+            let algorithm = HashAlgorithm::from_str(&name, true)
+                .map_err(|_| format!("unknown checksum_algorithm {:?}", name))?;
+            match parse_expected(&checksum) {
+                Ok((detected, hex)) if detected == algorithm => Some((algorithm, hex)),
+                _ => return Err(format!("checksum is not a valid {} digest", name)),
+            }
+        }
+        _ => return Err("checksum and checksum_algorithm must be given together".to_string()),
+    };
+
+    let parts = field(fields, "parts")?
+        .array("parts")?
+        .iter()
+        .enumerate()
+        .map(|(idx, part)| {
+            let part = part.object("part")?;
+            Ok(Part {
+                idx,
+                start: field(part, "start")?.number("start")?,
+                end_inclusive: field(part, "end_inclusive")?.number("end_inclusive")?,
+                path: PathBuf::from(field(part, "path")?.string("path")?),
+                url: url.clone(),
+                expected_hash: None,
+                resumed: false,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if parts.is_empty() {
+        return Err("no parts".to_string());
+    }
+    Part::validate_all(&parts, total_len).map_err(|e| e.to_string())?;
+    if let Some(p) = parts.iter().find(|p| p.path == output) {
+        return Err(format!("part {} would be the output file", p.idx));
+    }
+
+    Ok(DownloadPlan {
+        url,
+        output,
+        threads,
+        total_len,
+        etag,
+        last_modified,
+        proxy_mode,
+        proxy,
+        checksum,
+        parts,
+    })
+}
+
+/// Returns the value of the required field `name`.
+fn field<'a>(fields: &'a [(String, Json)], name: &str) -> Result<&'a Json, String> {
+    fields
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| format!("missing field {:?}", name))
+}
+
+/// Returns the string field `name`, or `None` if it is missing or `null`.
+fn optional_string(fields: &[(String, Json)], name: &str) -> Result<Option<String>, String> {
+    match field(fields, name) {
+        Err(_) | Ok(Json::Null) => Ok(None),
+        Ok(value) => value.string(name).map(|s| Some(s.to_string())),
+    }
+}
+
+/// A parsed JSON value; numbers are limited to unsigned integers, which is all a plan holds.
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    /// Fields in document order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn number(&self, name: &str) -> Result<u64, String> {
+        match self {
+            Json::Number(n) => Ok(*n),
+            _ => Err(format!("{} must be an unsigned integer", name)),
+        }
+    }
+
+    fn string(&self, name: &str) -> Result<&str, String> {
+        match self {
+            Json::String(s) => Ok(s),
+            _ => Err(format!("{} must be a string", name)),
+        }
+    }
+
+    fn array(&self, name: &str) -> Result<&[Json], String> {
+        match self {
+            Json::Array(values) => Ok(values),
+            _ => Err(format!("{} must be an array", name)),
+        }
+    }
+
+    fn object(&self, name: &str) -> Result<&[(String, Json)], String> {
+        match self {
+            Json::Object(fields) => Ok(fields),
+            _ => Err(format!("{} must be an object", name)),
+        }
+    }
+}
+
+/// Recursive-descent parser for the subset of JSON described at [`Json`].
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected {:?}, found {:?}", expected, c)),
+            None => Err(format!("expected {:?}, found the end", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('0'..='9') => self.number(),
+            Some('t' | 'f' | 'n') => self.literal(),
+            Some(c) => Err(format!("unexpected {:?}", c)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+
+    /// Parses a comma-separated list up to `close`, calling `item` for each element.
+    fn list(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&close).is_some() {
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(c) if c == close => return Ok(()),
+                _ => return Err(format!("expected ',' or {:?}", close)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.list('}', |p| {
+            p.skip_whitespace();
+            let key = p.string()?;
+            p.expect(':')?;
+            fields.push((key, p.value()?));
+            Ok(())
+        })?;
+        Ok(Json::Object(fields))
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.list(']', |p| {
+            values.push(p.value()?);
+            Ok(())
+        })?;
+        Ok(Json::Array(values))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next().ok_or("unterminated string")? {
+                '"' => return Ok(out),
+                '\\' => match self.chars.next().ok_or("unterminated string")? {
+                    c @ ('"' | '\\' | '/') => out.push(c),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'u' => out.push(self.unicode_escape()?),
+                    c => return Err(format!("invalid escape \\{}", c)),
+                },
+                c if c.is_control() => return Err("unescaped control character".to_string()),
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Parses the hex digits after `\u`, combining a UTF-16 surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                return Err("unpaired surrogate".to_string());
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err("unpaired surrogate".to_string());
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| "unpaired surrogate".to_string())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        (0..4).try_fold(0, |acc, _| {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or("invalid \\u escape")?;
+            Ok(acc * 16 + digit)
+        })
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut digits = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+        if matches!(self.chars.peek(), Some('.' | 'e' | 'E')) {
+            return Err("only unsigned integers are supported".to_string());
+        }
+        digits
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| format!("number out of range: {}", digits))
+    }
+
+    fn literal(&mut self) -> Result<Json, String> {
+        let mut word = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphabetic()) {
+            word.push(c);
+        }
+        match word.as_str() {
+            "null" => Ok(Json::Null),
+            "true" => Ok(Json::Bool(true)),
+            "false" => Ok(Json::Bool(false)),
+            _ => Err(format!("unexpected {:?}", word)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn part(idx: usize, start: u64, end_inclusive: u64) -> Part {
+        Part {
+            idx,
+            start,
+            end_inclusive,
+            path: PathBuf::from(format!("dir/out.bin.part{}", idx)),
+            url: "https://example.com/out.bin".to_string(),
+            expected_hash: None,
+            resumed: false,
+        }
+    }
+
+    fn plan() -> DownloadPlan {
+        DownloadPlan {
+            url: "https://example.com/out.bin".to_string(),
+            output: PathBuf::from("dir/out.bin"),
+            threads: 4,
+            total_len: 1000,
+            etag: Some("\"5f3a\"".to_string()),
+            last_modified: None,
+            proxy_mode: ProxyMode::Custom,
+            proxy: Some("http://proxy:3128".to_string()),
+            checksum: Some((HashAlgorithm::Sha256, ABC_SHA256.to_string())),
+            parts: vec![part(0, 0, 499), part(1, 500, 999)],
+        }
+    }
+
+    /// `plan()` as written by `format_plan`, with `edit` applied.
+    fn plan_json(edit: impl FnOnce(String) -> String) -> String {
+        edit(format_plan(&plan()).unwrap())
+    }
+
+    fn parse_error(contents: &str) -> String {
+        parse_plan(contents).unwrap_err()
+    }
+
+    #[test]
+    fn plan_format() {
+        assert_eq!(
+            format_plan(&plan()).unwrap(),
+            format!(
+                r#"{{
+  "version": 1,
+  "url": "https://example.com/out.bin",
+  "output": "dir/out.bin",
+  "threads": 4,
+  "total_len": 1000,
+  "etag": "\"5f3a\"",
+  "last_modified": null,
+  "proxy_mode": "custom",
+  "proxy": "http://proxy:3128",
+  "checksum": "{}",
+  "checksum_algorithm": "sha256",
+  "parts": [
+    {{"start": 0, "end_inclusive": 499, "path": "dir/out.bin.part0"}},
+    {{"start": 500, "end_inclusive": 999, "path": "dir/out.bin.part1"}}
+  ]
+}}
+"#,
+                ABC_SHA256
+            )
+        );
+    }
+
+    #[test]
+    fn plan_round_trip() {
+        let parsed = parse_plan(&format_plan(&plan()).unwrap()).unwrap();
+        assert_eq!(parsed.url, "https://example.com/out.bin");
+        assert_eq!(parsed.output, PathBuf::from("dir/out.bin"));
+        assert_eq!(parsed.threads, 4);
+        assert_eq!(parsed.total_len, 1000);
+        assert_eq!(parsed.etag.as_deref(), Some("\"5f3a\""));
+        assert_eq!(parsed.last_modified, None);
+        assert_eq!(parsed.proxy_mode, ProxyMode::Custom);
+        assert_eq!(parsed.proxy.as_deref(), Some("http://proxy:3128"));
+        assert_eq!(
+            parsed.checksum,
+            Some((HashAlgorithm::Sha256, ABC_SHA256.to_string()))
+        );
+
+        let ranges: Vec<_> = parsed
+            .parts
+            .iter()
+            .map(|p| {
+                (
+                    p.idx,
+                    p.start,
+                    p.end_inclusive,
+                    p.path.clone(),
+                    p.url.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                (
+                    0,
+                    0,
+                    499,
+                    PathBuf::from("dir/out.bin.part0"),
+                    "https://example.com/out.bin"
+                ),
+                (
+                    1,
+                    500,
+                    999,
+                    PathBuf::from("dir/out.bin.part1"),
+                    "https://example.com/out.bin"
+                ),
+            ]
+        );
+        assert_eq!(format_plan(&parsed).unwrap(), format_plan(&plan()).unwrap());
+    }
+
+    #[test]
+    fn plan_without_proxy_or_checksum() {
+        let plan = DownloadPlan {
+            proxy_mode: ProxyMode::Off,
+            proxy: None,
+            checksum: None,
+            ..plan()
+        };
+        let json = format_plan(&plan).unwrap();
+        assert!(json.contains("\"proxy_mode\": \"off\",\n  \"proxy\": null,"));
+        assert!(json.contains("\"checksum\": null,\n  \"checksum_algorithm\": null,"));
+
+        let parsed = parse_plan(&json).unwrap();
+        assert_eq!(parsed.proxy_mode, ProxyMode::Off);
+        assert_eq!(parsed.proxy, None);
+        assert_eq!(parsed.checksum, None);
+    }
+
+    #[test]
+    fn compact_plan_with_escapes_and_unknown_fields() {
+        let json = r#"{"version":1,"url":"https://example.com/a\u0020b\/c","output":"caf\u00e9 \ud83d\ude00.bin","threads":2,"total_len":10,"proxy_mode":"auto","future":[true,false,{"x":null}],"parts":[{"start":0,"end_inclusive":9,"path":"p0"}]}"#;
+        let parsed = parse_plan(json).unwrap();
+        assert_eq!(parsed.url, "https://example.com/a b/c");
+        assert_eq!(parsed.output, PathBuf::from("café 😀.bin"));
+        assert_eq!(parsed.proxy_mode, ProxyMode::Auto);
+        assert_eq!(parsed.etag, None);
+        assert_eq!(parsed.parts.len(), 1);
+        assert_eq!(parsed.parts[0].expected_size(), 10);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let json = plan_json(|j| j.replace("\"version\": 1", "\"version\": 2"));
+        assert_eq!(parse_error(&json), "unsupported version 2");
+        let json = plan_json(|j| j.replace("\"version\": 1,\n", ""));
+        assert_eq!(parse_error(&json), "missing field \"version\"");
+    }
+
+    #[test]
+    fn invalid_fields_are_rejected() {
+        let cases = [
+            ("\"threads\": 4", "\"threads\": 0", "threads must be >= 1"),
+            (
+                "\"threads\": 4",
+                "\"threads\": \"4\"",
+                "threads must be an unsigned integer",
+            ),
+            (
+                "\"total_len\": 1000",
+                "\"total_len\": 1.5",
+                "only unsigned integers are supported",
+            ),
+            ("\"url\": ", "\"url_\": ", "missing field \"url\""),
+            (
+                "\"proxy_mode\": \"custom\"",
+                "\"proxy_mode\": \"socks\"",
+                "unknown proxy_mode \"socks\"",
+            ),
+            (
+                "\"proxy\": \"http://proxy:3128\"",
+                "\"proxy\": null",
+                "proxy_mode custom needs a proxy",
+            ),
+            (
+                "\"sha256\"",
+                "\"sha512\"",
+                "checksum is not a valid sha512 digest",
+            ),
+            (
+                "\"sha256\"",
+                "\"crc32\"",
+                "unknown checksum_algorithm \"crc32\"",
+            ),
+            (
+                "\"sha256\"",
+                "null",
+                "checksum and checksum_algorithm must be given together",
+            ),
+        ];
+        for (from, to, error) in cases {
+            let json = plan_json(|j| j.replace(from, to));
+            assert_eq!(parse_error(&json), error, "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn parts_must_cover_the_file() {
+        let json = plan_json(|j| j.replace("\"start\": 500", "\"start\": 499"));
+        assert!(parse_error(&json).contains("overlaps part 0"));
+        let json = plan_json(|j| j.replace("\"end_inclusive\": 999", "\"end_inclusive\": 998"));
+        assert!(parse_error(&json).contains("parts cover 999 bytes, expected 1000"));
+        let json = plan_json(|j| {
+            j.replace(
+                "\"path\": \"dir/out.bin.part1\"",
+                "\"path\": \"dir/out.bin\"",
+            )
+        });
+        assert_eq!(parse_error(&json), "part 1 would be the output file");
+
+        let start = plan_json(|j| j[..j.find("\"parts\"").unwrap()].to_string());
+        assert_eq!(
+            parse_error(&format!("{}\"parts\": []}}", start)),
+            "no parts"
+        );
+    }
+
+    #[test]
+    fn malformed_json_is_rejected() {
+        let json = format_plan(&plan()).unwrap();
+        assert_eq!(parse_error(&json[..json.len() - 3]), "expected ',' or '}'");
+        assert_eq!(
+            parse_error(&format!("{} x", json)),
+            "trailing characters after the plan"
+        );
+        assert_eq!(parse_error("[1, 2]"), "plan must be an object");
+        assert_eq!(parse_error(""), "unexpected end");
+        assert_eq!(parse_error("{\"a\": nil}"), "unexpected \"nil\"");
+        assert_eq!(parse_error("{\"a\": \"\\x\"}"), "invalid escape \\x");
+        assert_eq!(parse_error("{\"a\": \"\\ud83d\"}"), "unpaired surrogate");
+        assert_eq!(
+            parse_error("{\"a\": 18446744073709551616}"),
+            "number out of range: 18446744073709551616"
+        );
+        assert_eq!(parse_error("{\"a\" 1}"), "expected ':', found '1'");
+    }
+
+    #[tokio::test]
+    async fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("oxidown-test-{}.plan", std::process::id()));
+        save_plan(&path, &plan()).await.unwrap();
+        let loaded = load_plan(&path).await.unwrap();
+        assert_eq!(format_plan(&loaded).unwrap(), format_plan(&plan()).unwrap());
+
+        std::fs::write(&path, "{}").unwrap();
+        let err = load_plan(&path).await.unwrap_err().to_string();
+        let _ = std::fs::remove_file(&path);
+        assert!(err.contains("missing field \"version\""), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_are_rejected() {
+        use std::os::unix::ffi::OsStrExt;
+        let plan = DownloadPlan {
+            output: PathBuf::from(std::ffi::OsStr::from_bytes(b"caf\xe9.bin")),
+            ..plan()
+        };
+        assert!(matches!(
+            format_plan(&plan),
+            Err(ProgramError::ArgNotValid(_))
+        ));
+    }
+}
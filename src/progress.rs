@@ -1,43 +1,332 @@
-use indicatif::ProgressStyle;
-use std::borrow::Cow;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle, WeakProgressBar};
+use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::types::ProgressOptions;
+
+/// Visible bars and spinners; a warning logged while one is drawing may be overdrawn.
+static VISIBLE_BARS: Mutex<Vec<WeakProgressBar>> = Mutex::new(Vec::new());
+
+/// Remembers `pb` as drawn on the terminal until it is finished or dropped.
+fn register_visible(pb: &ProgressBar) {
+    let mut bars = VISIBLE_BARS.lock().unwrap_or_else(|e| e.into_inner());
+    bars.retain(|bar| bar.upgrade().is_some_and(|bar| !bar.is_finished()));
+    bars.push(pb.downgrade());
+}
+
+/// Returns true while a visible progress bar or spinner is not finished yet.
+fn progress_visible() -> bool {
+    VISIBLE_BARS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|bar| {
+            bar.upgrade()
+                .is_some_and(|bar| !bar.is_finished() && !bar.is_hidden())
+        })
+}
+
+/// Creates a progress bar of the given length, hidden when `show_progress` is false.
+///
+/// A hidden bar still tracks its position, so callers can use it unconditionally.
+pub fn new_progress_bar(total: u64, show_progress: bool) -> ProgressBar {
+    if show_progress {
+        let pb = ProgressBar::new(total);
+        register_visible(&pb);
+        pb
+    } else {
+        ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::hidden())
+    }
+}
+
+/// Creates the progress display for a single-request download of `total_size` bytes.
+///
+/// Without a known length (`0`, e.g. chunked encoding) only the received bytes can be
+/// shown, so a spinner is used instead of a bar. The message is set to "Downloading".
+pub fn new_download_progress(total_size: u64, progress: ProgressOptions) -> ProgressBar {
+    let pb = if total_size == 0 {
+        let pb = new_spinner(progress.show);
+        pb.set_style(style_download_spinner(progress.use_si));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        pb
+    } else {
+        let pb = new_progress_bar(total_size, progress.show);
+        pb.set_style(style_download_bar(progress.use_si));
+        pb
+    };
+    pb.set_message("Downloading");
+    pb
+}
+
+/// Creates a spinner for downloads of unknown length, hidden when `show_progress` is false.
+pub fn new_spinner(show_progress: bool) -> ProgressBar {
+    if show_progress {
+        let pb = ProgressBar::new_spinner();
+        register_visible(&pb);
+        pb
+    } else {
+        ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden())
+    }
+}
+
+/// Creates a spinner style for downloads without a known length.
+///
+/// Format: `Spinner [Elapsed] Message: Bytes received (Speed)`
+pub fn style_download_spinner(use_si: bool) -> ProgressStyle {
+    let template = if use_si {
+        "{spinner:.green} [{elapsed_precise}] {msg}: {decimal_bytes} received ({decimal_bytes_per_sec})"
+    } else {
+        "{spinner:.green} [{elapsed_precise}] {msg}: {binary_bytes} received ({binary_bytes_per_sec})"
+    };
+    ProgressStyle::default_spinner().template(template).unwrap()
+}
 
 /// Creates a configured progress bar style for downloads.
 ///
 /// Format: `Spinner [Elapsed] [Bar] Bytes/Total (Speed, ETA)`
 /// Uses cyan/blue colors for the bar and green for the spinner.
-pub fn style_download_bar() -> ProgressStyle {
+/// Byte counts use SI units (kB, MB) when `use_si` is true, IEC units (KiB, MiB) otherwise.
+pub fn style_download_bar(use_si: bool) -> ProgressStyle {
+    let template = if use_si {
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {decimal_bytes}/{decimal_total_bytes} ({decimal_bytes_per_sec}, {eta})"
+    } else {
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {binary_bytes}/{binary_total_bytes} ({binary_bytes_per_sec}, {eta})"
+    };
     ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+        .template(template)
         .unwrap()
         .progress_chars("#>-")
 }
 
-/// Creates a spinner style for indeterminate states (e.g., merging files).
+/// Creates a progress bar style for merging parts.
 ///
-/// Format: `Spinner Message`
-pub fn style_spinner() -> ProgressStyle {
-    ProgressStyle::default_spinner()
-        .template("{spinner:.blue} {msg}")
+/// Format: `Spinner [Bar] Message`, where the message shows the merged byte count.
+pub fn style_merge_bar() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template("{spinner:.blue} [{bar:40.cyan/blue}] {msg}")
         .unwrap()
+        .progress_chars("#>-")
+}
+
+/// Formats bytes with SI units when `use_si` is true, IEC units otherwise (`--si`).
+///
+/// The download bar formats bytes itself; this is used for logging and the merge bar message.
+pub fn format_bytes(bytes: u64, use_si: bool) -> Cow<'static, str> {
+    if use_si {
+        format_bytes_si(bytes)
+    } else {
+        format_bytes_iec(bytes)
+    }
+}
+
+/// 1024-based (IEC) byte units.
+pub(crate) const KIB: u64 = 1 << 10;
+pub(crate) const MIB: u64 = KIB * 1024;
+pub(crate) const GIB: u64 = MIB * 1024;
+pub(crate) const TIB: u64 = GIB * 1024;
+
+/// 1000-based (SI) byte units.
+pub(crate) const KB: u64 = 1000;
+pub(crate) const MB: u64 = KB * 1000;
+pub(crate) const GB: u64 = MB * 1000;
+pub(crate) const TB: u64 = GB * 1000;
+
+/// Formats bytes with 1024-based IEC units (KiB, MiB, GiB, TiB).
+///
+/// # Examples
+///
+/// * `1023` -> `"1023 B"`
+/// * `1024` -> `"1.00 KiB"`
+/// * `1_572_864` -> `"1.50 MiB"`
+/// * `10 * TIB` -> `"10.00 TiB"`
+pub fn format_bytes_iec(bytes: u64) -> Cow<'static, str> {
+    scale_bytes(
+        bytes,
+        &[(TIB, "TiB"), (GIB, "GiB"), (MIB, "MiB"), (KIB, "KiB")],
+    )
 }
 
-/// Helper to format bytes into human-readable strings (KB, MB, GB).
+/// Formats bytes with 1000-based SI units (KB, MB, GB, TB).
+///
+/// # Examples
 ///
-/// This function is currently not used by the progress bar (which handles formatting internally),
-/// but is available for logging purposes.
-#[allow(dead_code)]
-pub fn format_bytes(bytes: u64) -> Cow<'static, str> {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
+/// * `999` -> `"999 B"`
+/// * `1000` -> `"1.00 KB"`
+/// * `1_500_000` -> `"1.50 MB"`
+pub fn format_bytes_si(bytes: u64) -> Cow<'static, str> {
+    scale_bytes(bytes, &[(TB, "TB"), (GB, "GB"), (MB, "MB"), (KB, "KB")])
+}
 
-    if bytes >= GB {
-        format!("{:.2} GB", bytes as f64 / GB as f64).into()
-    } else if bytes >= MB {
-        format!("{:.2} MB", bytes as f64 / MB as f64).into()
-    } else if bytes >= KB {
-        format!("{:.2} KB", bytes as f64 / KB as f64).into()
+/// Formats bytes with IEC units followed by the raw byte count, for detailed reports.
+///
+/// # Examples
+///
+/// * `1_321_205_760` -> `"1.23 GiB (1321205760 B)"`
+/// * `512` -> `"512 B"`
+pub fn format_bytes_exact(bytes: u64) -> String {
+    if bytes < KIB {
+        format!("{} B", bytes)
     } else {
-        format!("{} B", bytes).into()
+        format!("{} ({} B)", format_bytes_iec(bytes), bytes)
+    }
+}
+
+/// Formats `bytes` with the first of `units` (largest first) that fits, or as plain bytes.
+fn scale_bytes(bytes: u64, units: &[(u64, &str)]) -> Cow<'static, str> {
+    for &(scale, unit) in units {
+        if bytes >= scale {
+            return format!("{:.2} {}", bytes as f64 / scale as f64, unit).into();
+        }
+    }
+    format!("{} B", bytes).into()
+}
+
+/// Collects WARN-level log messages so they can be repeated after the download.
+///
+/// Warnings logged while a progress bar is being redrawn are easy to miss; the collector
+/// keeps a copy of each of those so `main` can print them in a summary block at the end.
+/// Warnings logged with no bar on screen were plainly visible and are not repeated.
+#[derive(Default)]
+pub struct WarningCollector {
+    warnings: Mutex<Vec<String>>,
+}
+
+impl WarningCollector {
+    /// Returns a `tracing` layer that records WARN events logged during progress rendering.
+    pub fn layer(self: &Arc<Self>) -> WarningLayer {
+        WarningLayer(self.clone())
+    }
+
+    /// Takes all warnings collected so far, leaving the collector empty.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// `tracing_subscriber` layer feeding a shared [`WarningCollector`].
+pub struct WarningLayer(Arc<WarningCollector>);
+
+impl<S: Subscriber> Layer<S> for WarningLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::WARN || !progress_visible() {
+            return;
+        }
+
+        let mut visitor = WarningVisitor::default();
+        event.record(&mut visitor);
+        self.0
+            .warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(visitor.finish());
+    }
+}
+
+/// Formats an event as `message key=value ...`.
+#[derive(Default)]
+struct WarningVisitor {
+    message: String,
+    fields: String,
+}
+
+impl WarningVisitor {
+    fn finish(self) -> String {
+        if self.fields.is_empty() {
+            self.message
+        } else {
+            format!("{}{}", self.message, self.fields)
+        }
+    }
+}
+
+impl Visit for WarningVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIDDEN: ProgressOptions = ProgressOptions {
+        show: false,
+        use_si: false,
+    };
+
+    #[test]
+    fn unknown_size_uses_a_spinner() {
+        let pb = new_download_progress(0, HIDDEN);
+        assert_eq!(pb.length(), None);
+        pb.inc(12);
+        assert_eq!(pb.position(), 12);
+
+        assert_eq!(new_download_progress(100, HIDDEN).length(), Some(100));
+    }
+
+    #[test]
+    fn iec_thresholds() {
+        let cases = [
+            (0, "0 B"),
+            (KIB - 1, "1023 B"),
+            (KIB, "1.00 KiB"),
+            (MIB, "1.00 MiB"),
+            (MIB + MIB / 2, "1.50 MiB"),
+            (GIB, "1.00 GiB"),
+            (TIB, "1.00 TiB"),
+            (10 * TIB, "10.00 TiB"),
+            (u64::MAX, "16777216.00 TiB"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(format_bytes_iec(bytes), expected, "{bytes}");
+            assert_eq!(format_bytes(bytes, false), expected, "{bytes}");
+        }
+    }
+
+    #[test]
+    fn si_thresholds() {
+        let cases = [
+            (KB - 1, "999 B"),
+            (KB, "1.00 KB"),
+            (MB, "1.00 MB"),
+            (1_500_000, "1.50 MB"),
+            (GB, "1.00 GB"),
+            (TB, "1.00 TB"),
+            (10 * TB, "10.00 TB"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(format_bytes_si(bytes), expected, "{bytes}");
+            assert_eq!(format_bytes(bytes, true), expected, "{bytes}");
+        }
+    }
+
+    #[test]
+    fn exact_format_adds_the_byte_count() {
+        assert_eq!(format_bytes_exact(512), "512 B");
+        assert_eq!(format_bytes_exact(KIB - 1), "1023 B");
+        assert_eq!(format_bytes_exact(KIB), "1.00 KiB (1024 B)");
+        assert_eq!(format_bytes_exact(1_321_205_760), "1.23 GiB (1321205760 B)");
+        assert_eq!(format_bytes_exact(TIB), "1.00 TiB (1099511627776 B)");
     }
 }
@@ -0,0 +1,80 @@
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// Cancels a token on SIGINT (Ctrl+C) while alive; see [`cancel_on_sigint`].
+///
+/// Dropping the guard restores the default SIGINT behavior.
+pub struct SigintGuard {
+    poller: Option<JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Set by the signal handler, which must not do anything but async-signal-safe work.
+    pub static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_sigint(_signal: libc::c_int) {
+        SIGINT_RECEIVED.store(true, Ordering::Relaxed);
+    }
+
+    /// Installs the flag-setting handler; `SA_RESETHAND` makes a second Ctrl+C kill the process.
+    pub fn install() -> bool {
+        SIGINT_RECEIVED.store(false, Ordering::Relaxed);
+        // SAFETY: the handler only stores to an atomic, and the struct is fully initialized
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESETHAND;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut()) == 0
+        }
+    }
+
+    pub fn restore() {
+        // SAFETY: resetting a signal to its default disposition
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
+}
+
+/// Cancels `token` when the process receives SIGINT, until the returned guard is dropped.
+///
+/// The signal handler only sets a flag, which a background task polls every 100ms.
+/// Only the first Ctrl+C is caught: a second one terminates the process as usual.
+/// On platforms other than Unix, Ctrl+C keeps its default behavior.
+pub fn cancel_on_sigint(token: CancellationToken) -> SigintGuard {
+    #[cfg(unix)]
+    if unix::install() {
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
+
+        let poller = tokio::spawn(async move {
+            while !unix::SIGINT_RECEIVED.load(Ordering::Relaxed) {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            debug!("SIGINT received, cancelling download");
+            token.cancel();
+        });
+        return SigintGuard {
+            poller: Some(poller),
+        };
+    }
+
+    debug!("SIGINT handling not available");
+    let _ = token;
+    SigintGuard { poller: None }
+}
+
+impl Drop for SigintGuard {
+    fn drop(&mut self) {
+        if let Some(poller) = self.poller.take() {
+            poller.abort();
+            #[cfg(unix)]
+            unix::restore();
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use reqwest::{
+    Client, Url,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+};
+use std::collections::{HashSet, VecDeque};
+use tracing::{debug, instrument};
+
+use crate::error::ProgramError;
+use crate::progress::format_bytes;
+use crate::utils::{header_to_string_lossy, send_traced};
+
+/// Outcome of checking one URL.
+struct UrlStatus {
+    /// HTTP status code, or `None` if the request itself failed
+    code: Option<u16>,
+    final_url: String,
+    content_type: Option<String>,
+    content_length: Option<u64>,
+}
+
+impl UrlStatus {
+    fn is_ok(&self) -> bool {
+        self.code.is_some_and(|c| (200..300).contains(&c))
+    }
+
+    fn is_html(&self) -> bool {
+        self.content_type
+            .as_deref()
+            .is_some_and(|t| t.to_ascii_lowercase().starts_with("text/html"))
+    }
+}
+
+/// Checks that a URL is accessible without downloading it (`--spider`).
+///
+/// Prints the status code, the final URL after redirects, the content type and the size.
+///
+/// # Returns
+///
+/// * `Ok(())` if the server answers with a 2xx status.
+/// * `Err(ProgramError::Other)` if the request fails or returns any other status.
+#[instrument(skip(client, use_si))]
+pub async fn spider(client: &Client, url: &str, use_si: bool) -> Result<(), ProgramError> {
+    let status = check_url(client, url).await;
+
+    match status.code {
+        Some(code) => println!("Status: {}", code),
+        None => println!("Status: request failed"),
+    }
+    if status.final_url != url {
+        println!("Redirected to: {}", status.final_url);
+    }
+    println!(
+        "Content-Type: {}",
+        status.content_type.as_deref().unwrap_or("unknown")
+    );
+    match status.content_length {
+        Some(len) => println!("Size: {} ({} bytes)", format_bytes(len, use_si), len),
+        None => println!("Size: unknown"),
+    }
+
+    if status.is_ok() {
+        println!("Remote file exists.");
+        Ok(())
+    } else {
+        Err(ProgramError::Other(format!("{} is not accessible", url)))
+    }
+}
+
+/// Checks a URL and, recursively, the links of its HTML pages (`--spider-recursive`).
+///
+/// Only links on the same host as `url` are followed, each URL is checked once,
+/// and pages more than `max_depth` links away from `url` are not scanned.
+/// A table of status codes and URLs is printed at the end.
+///
+/// # Returns
+///
+/// * `Ok(())` if every URL answered with a 2xx status.
+/// * `Err(ProgramError::Other)` with the number of broken URLs otherwise.
+#[instrument(skip(client))]
+pub async fn spider_recursive(
+    client: &Client,
+    url: &str,
+    max_depth: usize,
+) -> Result<(), ProgramError> {
+    let start = Url::parse(url).map_err(|e| ProgramError::ArgNotValid(e.to_string()))?;
+
+    let mut visited = HashSet::from([start.to_string()]);
+    let mut queue = VecDeque::from([(start.clone(), 0)]);
+    let mut results = Vec::new();
+
+    while let Some((page, depth)) = queue.pop_front() {
+        let status = check_url(client, page.as_str()).await;
+
+        if status.is_ok() && status.is_html() && depth < max_depth {
+            let base = Url::parse(&status.final_url).unwrap_or_else(|_| page.clone());
+            // Redirects may leave the start host; do not crawl it
+            if base.host_str() == start.host_str() {
+                for link in fetch_links(client, &base).await {
+                    if link.host_str() == start.host_str() && visited.insert(link.to_string()) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+        }
+        results.push((status.code, page));
+    }
+
+    println!("{:<8}URL", "STATUS");
+    for (code, page) in &results {
+        let code = code.map_or_else(|| "ERR".to_string(), |c| c.to_string());
+        println!("{:<8}{}", code, page);
+    }
+
+    let broken = results
+        .iter()
+        .filter(|(code, _)| !code.is_some_and(|c| (200..300).contains(&c)))
+        .count();
+    if broken == 0 {
+        Ok(())
+    } else {
+        Err(ProgramError::Other(format!(
+            "{} of {} URLs are not accessible",
+            broken,
+            results.len()
+        )))
+    }
+}
+
+/// Sends a HEAD request and collects the response metadata.
+async fn check_url(client: &Client, url: &str) -> UrlStatus {
+    match send_traced(client.head(url)).await {
+        Ok(resp) => {
+            let header = |name| {
+                resp.headers()
+                    .get(name)
+                    .map(|v| header_to_string_lossy(v).into_owned())
+            };
+            UrlStatus {
+                code: Some(resp.status().as_u16()),
+                final_url: resp.url().to_string(),
+                content_type: header(CONTENT_TYPE),
+                content_length: header(CONTENT_LENGTH).and_then(|v| v.trim().parse().ok()),
+            }
+        }
+        Err(e) => {
+            debug!(url = %url, error = %e, "Spider request failed");
+            UrlStatus {
+                code: None,
+                final_url: url.to_string(),
+                content_type: None,
+                content_length: None,
+            }
+        }
+    }
+}
+
+/// Downloads an HTML page and returns its absolute `http(s)` links, without fragments.
+async fn fetch_links(client: &Client, page: &Url) -> Vec<Url> {
+    let body = match send_traced(client.get(page.clone())).await {
+        Ok(resp) => resp.text().await.unwrap_or_default(),
+        Err(e) => {
+            debug!(url = %page, error = %e, "Failed to fetch page for links");
+            return Vec::new();
+        }
+    };
+
+    extract_links(&body)
+        .into_iter()
+        .filter_map(|link| page.join(link).ok())
+        .filter(|link| matches!(link.scheme(), "http" | "https"))
+        .map(|mut link| {
+            link.set_fragment(None);
+            link
+        })
+        .collect()
+}
+
+/// Extracts the values of all `href` and `src` attributes from HTML.
+///
+/// This is a plain text scan rather than a full HTML parser, which is enough to
+/// find links for checking; values may be double-quoted, single-quoted or bare.
+///
+/// # Examples
+///
+/// * `<a href="a.html">` and `<img src='b.png'>` -> `["a.html", "b.png"]`
+fn extract_links(html: &str) -> Vec<&str> {
+    let lower = html.to_ascii_lowercase();
+    let mut links = Vec::new();
+
+    for attr in ["href", "src"] {
+        let mut pos = 0;
+        while let Some(found) = lower[pos..].find(attr) {
+            let mut i = pos + found + attr.len();
+            pos = i;
+            // Must be a whole attribute name followed by `=`
+            let preceded_ok = lower[..i - attr.len()]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_ascii_whitespace());
+            let rest = lower[i..].trim_start();
+            if !preceded_ok || !rest.starts_with('=') {
+                continue;
+            }
+            i = lower.len() - rest.len() + 1;
+            i += lower[i..].len() - lower[i..].trim_start().len();
+
+            let value = match html[i..].chars().next() {
+                Some(q @ ('"' | '\'')) => html[i + 1..].split(q).next(),
+                Some(_) => html[i..]
+                    .split(|c: char| c.is_ascii_whitespace() || c == '>')
+                    .next(),
+                None => None,
+            };
+            if let Some(value) = value.map(str::trim)
+                && !value.is_empty()
+                && !value.starts_with('#')
+            {
+                links.push(value);
+            }
+        }
+    }
+    links
+}
@@ -0,0 +1,78 @@
+use tracing::warn;
+
+use crate::types::DownloadSummary;
+
+/// Expands a `--write-out` template using the statistics of a finished download.
+///
+/// Variables use the curl syntax `%{name}`. Supported names are `size_download`,
+/// `speed_download` (bytes per second), `time_total` (seconds), `url_effective`,
+/// `filename_effective` and `http_code`. The escapes `\n` and `\t` are also expanded.
+/// Unknown variables are left untouched and reported with a warning.
+///
+/// # Examples
+///
+/// * `"%{size_download} bytes in %{time_total}s"` -> `"1048576 bytes in 0.512s"`
+pub fn expand_write_out(template: &str, summary: &DownloadSummary) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(pos) = rest.find(['%', '\\']) {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(escaped) = rest.strip_prefix('\\') {
+            match escaped.chars().next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('\\') => out.push('\\'),
+                _ => {
+                    out.push('\\');
+                    rest = escaped;
+                    continue;
+                }
+            }
+            rest = &escaped[1..];
+            continue;
+        }
+
+        let Some(end) = rest.strip_prefix("%{").and_then(|r| r.find('}')) else {
+            out.push('%');
+            rest = &rest[1..];
+            continue;
+        };
+
+        let name = &rest[2..2 + end];
+        match expand_variable(name, summary) {
+            Some(value) => out.push_str(&value),
+            None => {
+                warn!(variable = %name, "Unknown --write-out variable");
+                out.push_str(&rest[..end + 3]);
+            }
+        }
+        rest = &rest[end + 3..];
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Returns the value of a single `--write-out` variable, or `None` if it is unknown.
+fn expand_variable(name: &str, summary: &DownloadSummary) -> Option<String> {
+    let value = match name {
+        "size_download" => summary.size_download.to_string(),
+        "speed_download" => {
+            let speed = if summary.time_total > 0.0 {
+                summary.size_download as f64 / summary.time_total
+            } else {
+                0.0
+            };
+            format!("{:.0}", speed)
+        }
+        "time_total" => format!("{:.3}", summary.time_total),
+        "url_effective" => summary.url_effective.clone(),
+        "filename_effective" => summary.filename_effective.clone(),
+        "http_code" => summary.http_code.to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
@@ -0,0 +1,214 @@
+//! Minimal HTTP/1.1 server for tests, answering every request with a canned response.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request received by [`TestServer`].
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of header `name` (case-insensitive), if sent.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the inclusive range of a `Range: bytes=<start>-<end>` header.
+    pub fn range(&self) -> Option<(u64, u64)> {
+        let (start, end) = self
+            .header("range")?
+            .strip_prefix("bytes=")?
+            .split_once('-')?;
+        Some((start.parse().ok()?, end.parse().ok()?))
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Vec<u8> + Send + Sync;
+
+/// Server on a random local port; every connection carries one request.
+pub struct TestServer {
+    /// Base URL, e.g. `http://127.0.0.1:40123`
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    /// Starts a server answering each request with the raw response returned by `handler`.
+    pub async fn start(handler: impl Fn(&Request) -> Vec<u8> + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut stream = stream;
+                    if let Some(req) = read_request(&mut stream).await {
+                        let resp = handler(&req);
+                        log.lock().unwrap().push(req);
+                        let _ = stream.write_all(&resp).await;
+                        let _ = stream.shutdown().await;
+                    }
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// Requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Reads one request (head and `Content-Length` body) from the connection.
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let mut req = Request {
+        method,
+        path,
+        headers,
+        body: buf[head_end + 4..].to_vec(),
+    };
+    let len: usize = req
+        .header("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    while req.body.len() < len {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        req.body.extend_from_slice(&chunk[..n]);
+    }
+    Some(req)
+}
+
+/// Builds a raw response; `Content-Length` is added from `body` unless given in `headers`.
+///
+/// Pass an empty body with an explicit `Content-Length` to answer a HEAD request.
+pub fn response(status: u16, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut out = format!("HTTP/1.1 {} Test\r\nConnection: close\r\n", status);
+    for (name, value) in headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+    {
+        out.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    out.push_str("\r\n");
+    let mut out = out.into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+/// Serves `data` like a static file server with range support: HEAD, GET and
+/// `Range: bytes=<start>-<end>` requests (answered with 206 and `Content-Range`).
+pub fn serve_file(
+    data: Vec<u8>,
+    extra_headers: &'static [(&'static str, &'static str)],
+) -> impl Fn(&Request) -> Vec<u8> + Send + Sync + 'static {
+    move |req| {
+        let total = data.len() as u64;
+        let mut headers = vec![("Accept-Ranges", "bytes".to_string())];
+        headers.extend(extra_headers.iter().map(|(k, v)| (*k, v.to_string())));
+        let (status, body) = match req.range() {
+            Some((start, end)) if start < total => {
+                let end = end.min(total - 1);
+                headers.push((
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, end, total),
+                ));
+                (206, &data[start as usize..=end as usize])
+            }
+            Some(_) => {
+                headers.push(("Content-Range", format!("bytes */{}", total)));
+                (416, &data[..0])
+            }
+            None => (200, &data[..]),
+        };
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        if req.method == "HEAD" {
+            let len = body.len().to_string();
+            let mut headers = headers;
+            headers.push(("Content-Length", &len));
+            return response(status, &headers, b"");
+        }
+        response(status, &headers, body)
+    }
+}
+
+/// Directory for the files of one test, removed when dropped.
+pub struct TestDir(pub PathBuf);
+
+impl TestDir {
+    /// Creates a new, empty directory under the system temp directory.
+    ///
+    /// `create_dir` fails on an existing path, so a pre-created directory or link is never used.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "oxidown-test-{}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        std::fs::create_dir(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
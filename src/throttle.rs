@@ -0,0 +1,126 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+/// Token bucket used to enforce `--rate-limit` across all concurrent downloads.
+///
+/// Tokens (bytes) refill continuously at `refill_rate` per second and accumulate
+/// up to `capacity`, so short bursts run at full speed while the long-term
+/// average stays at the configured rate.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Maximum number of tokens (burst size in bytes)
+    capacity: u64,
+    /// Refill rate in bytes per second
+    refill_rate: u64,
+    /// Available tokens; negative after an oversized consume
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Burst size in bytes.
+    /// * `refill_rate` - Sustained rate in bytes per second (must be non-zero).
+    pub fn new(capacity: u64, refill_rate: u64) -> Self {
+        Self {
+            capacity,
+            refill_rate,
+            tokens: capacity as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes `bytes` tokens from the bucket.
+    ///
+    /// The tokens are always deducted; if the bucket runs into debt, the caller
+    /// must wait for the returned duration before receiving more data.
+    ///
+    /// # Returns
+    ///
+    /// * The time to sleep to honor the rate (`Duration::ZERO` if tokens were available).
+    pub fn consume(&mut self, bytes: u64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed * self.refill_rate as f64).min(self.capacity as f64);
+
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.refill_rate as f64)
+        }
+    }
+}
+
+/// Consumes `bytes` from a shared bucket and sleeps as long as required.
+pub async fn throttle(bucket: &Mutex<TokenBucket>, bytes: u64) {
+    let wait = bucket
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .consume(bytes);
+    if !wait.is_zero() {
+        sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIB: u64 = 1024;
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn burst_is_free_then_debt_is_paid_at_the_rate() {
+        let mut bucket = TokenBucket::new(512 * KIB, MIB);
+        assert_eq!(bucket.consume(512 * KIB), Duration::ZERO);
+
+        // Another 512 KiB at 1 MiB/s: half a second, less what refilled meanwhile
+        let wait = bucket.consume(512 * KIB);
+        assert!(
+            wait <= Duration::from_millis(500) && wait > Duration::from_millis(450),
+            "{wait:?}"
+        );
+    }
+
+    #[test]
+    fn tokens_accumulate_up_to_capacity() {
+        let mut bucket = TokenBucket::new(512 * KIB, MIB);
+        bucket.consume(512 * KIB);
+        // Ten seconds of refill at 1 MiB/s would be 10 MiB, but only the burst is kept
+        bucket.last_refill -= Duration::from_secs(10);
+
+        assert_eq!(bucket.consume(512 * KIB), Duration::ZERO);
+        assert!(bucket.consume(64 * KIB) > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn throughput_settles_at_the_rate() {
+        let bucket = Mutex::new(TokenBucket::new(512 * KIB, MIB));
+        let start = Instant::now();
+        for _ in 0..8 {
+            throttle(&bucket, 64 * KIB).await;
+        }
+        assert!(
+            start.elapsed() < Duration::from_millis(100),
+            "burst was throttled"
+        );
+
+        // 512 KiB more after the burst takes about half a second
+        for _ in 0..8 {
+            throttle(&bucket, 64 * KIB).await;
+        }
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(450) && elapsed < Duration::from_millis(900),
+            "{elapsed:?}"
+        );
+    }
+}
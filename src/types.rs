@@ -1,39 +1,303 @@
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use clap::{ArgAction, ArgGroup, Parser, Subcommand, ValueEnum};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, atomic::AtomicU32},
+    time::Duration,
+};
 
+use crate::utils::{parse_byte_range, parse_expect_header, parse_log_filter, parse_size_string};
+
+/// Group of the mutually exclusive proxy options (`--proxy`, `--proxy-mode`).
+const PROXY_GROUP: &str = "proxy_settings";
+
+/// Exit codes listed at the end of every `--help`.
+const EXIT_CODES_HELP: &str = "Exit codes:\n  0  Success\n  1  Other failure\n  2  Invalid argument\n  3  HTTP error\n  4  I/O error\n  5  Checksum mismatch\n  7  Timeout";
+
+/// Command line: a subcommand, or the download options directly (same as `download`).
 #[derive(Parser, Debug)]
 #[command(
     author,
     version,
-    about = "A blazing fast, multi-threaded file downloader written in Rust."
+    about = "A blazing fast, multi-threaded file downloader written in Rust.",
+    after_help = EXIT_CODES_HELP,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Download a file (the default when no subcommand is given)
+    #[command(after_help = EXIT_CODES_HELP)]
+    Download(Box<Args>),
+    /// Merge the part files of an interrupted download, without downloading
+    #[command(after_help = EXIT_CODES_HELP)]
+    Merge(MergeArgs),
+    /// Check a file against an expected checksum or a checksum list
+    #[command(after_help = EXIT_CODES_HELP)]
+    Verify(VerifyArgs),
+    /// Show what the server reports about a URL (size, range support, type)
+    /// without downloading it; accepts the download options
+    #[command(after_help = EXIT_CODES_HELP)]
+    Probe(Box<Args>),
+}
+
+/// Options of `oxidown merge`
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// File to merge into; parts are found as <output>.part<N>
+    #[arg(long, short = 'O')]
+    pub output: PathBuf,
+
+    /// Directory holding the part files (default: same dir as output)
+    #[arg(long)]
+    pub parts_dir: Option<PathBuf>,
+
+    /// File name prefix the part files were written with (default: <output file name>.part)
+    #[arg(long)]
+    pub temp_file_prefix: Option<String>,
+
+    /// File name suffix the part files were written with
+    #[arg(long, default_value = "", hide_default_value = true)]
+    pub temp_file_suffix: String,
+
+    /// Read buffer size in bytes
+    #[arg(long, default_value_t = 64 * 1024)]
+    pub merge_buffer_size: usize,
+
+    /// How the output file is opened (see `oxidown download --help`)
+    #[arg(long, value_enum, default_value_t = OutputMode::Create)]
+    pub output_mode: OutputMode,
+
+    /// Bypass the page cache while merging (Linux only)
+    #[arg(long)]
+    pub direct_io: bool,
+
+    /// Disable the progress bar
+    #[arg(long)]
+    pub no_progress: bool,
+}
+
+/// Options of `oxidown verify`
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    /// File to verify
+    #[arg(long, short = 'O')]
+    pub output: PathBuf,
+
+    /// Expected checksum (MD5, SHA-1, SHA-256 or SHA-512, detected by length)
+    #[arg(long, required_unless_present = "checksum_file")]
+    pub checksum: Option<String>,
+
+    /// Checksum list (sha256sum/md5sum format) containing the file's name
+    #[arg(long)]
+    pub checksum_file: Option<PathBuf>,
+}
+
+/// Download options, also accepted without the `download` subcommand
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new(PROXY_GROUP).multiple(false)))]
 pub struct Args {
-    /// Download URL
+    /// Download URL (not needed with --merge-only or --execute-plan)
+    #[arg(
+        required_unless_present_any = ["merge_only", "execute_plan"],
+        default_value = "",
+        hide_default_value = true
+    )]
     pub url: String,
 
     /// Output file path (if not provided, derived from URL)
     #[arg(long, short = 'O')]
     pub output: Option<PathBuf>,
 
+    /// Set the modification time of the output file to the server's Last-Modified date
+    #[arg(
+        long,
+        visible_alias = "preserve-mtime",
+        overrides_with = "no_use_server_timestamps"
+    )]
+    pub timestamp: bool,
+
+    /// Leave the modification time of the output file at the time it was written. This is
+    /// the default; the flag only cancels an earlier --timestamp
+    #[arg(long, overrides_with = "timestamp")]
+    pub no_use_server_timestamps: bool,
+
+    /// Set the modification time of the output file to this Unix timestamp (seconds)
+    #[arg(long, allow_negative_numbers = true, conflicts_with = "timestamp")]
+    pub set_mtime: Option<i64>,
+
+    /// How the output file is opened. There is no --no-clobber or --force: `exclusive`
+    /// refuses to overwrite, and the default `create` always overwrites. In `append` mode,
+    /// checksums cover the whole file, including what it held before
+    #[arg(long, value_enum, default_value_t = OutputMode::Create)]
+    pub output_mode: OutputMode,
+
+    /// Write the download to this already open file descriptor instead of a file (Unix only).
+    /// oxidown takes ownership of the descriptor and closes it when done
+    #[cfg(unix)]
+    #[arg(
+        long,
+        conflicts_with_all = ["output", "output_part", "range", "checksum", "auto_checksum", "checksum_file", "output_hash", "output_checksum_file", "save_plan", "execute_plan"]
+    )]
+    pub output_fd: Option<i32>,
+
     /// Number of concurrent downloads
     #[arg(long, default_value_t = 8)]
     pub threads: usize,
 
-    /// User-Agent to send in every request
-    #[arg(long, short = 'A', default_value = "oxidown/0.1.0")]
-    pub user_agent: String,
+    /// Maximum number of parts the file is split into. Unlike --threads, this only limits
+    /// the split count (e.g. for servers with per-client connection limits)
+    #[arg(long)]
+    pub max_parts: Option<usize>,
+
+    /// Maximum number of parts downloaded at once (default: --threads). Further parts
+    /// wait for a free slot, so a file can be split finer than the connection count.
+    /// Lower values bound the response data in flight, which may take longer overall
+    #[arg(long, visible_alias = "max-buffer-depth")]
+    pub max_concurrent_parts: Option<usize>,
+
+    /// User-Agent to send in every request, used verbatim (overrides --user-agent-template)
+    #[arg(long, short = 'A')]
+    pub user_agent: Option<String>,
+
+    /// User-Agent template; supports {version}, {os} and {arch}
+    #[arg(long, default_value = "oxidown/{version} ({os}/{arch})")]
+    pub user_agent_template: String,
 
     /// Per-part temp directory (default: same dir as output)
     #[arg(long)]
     pub temp_dir: Option<PathBuf>,
 
+    /// File name prefix of the temporary part files, which are named
+    /// <prefix><N><suffix> (default: <output file name>.part)
+    #[arg(long)]
+    pub temp_file_prefix: Option<String>,
+
+    /// File name suffix of the temporary part files, after the part index
+    #[arg(long, default_value = "", hide_default_value = true)]
+    pub temp_file_suffix: String,
+
+    /// Only download the part with this index (0-based) of the normal split, for debugging.
+    /// It is saved to --output if given, otherwise to the part's temp path
+    #[arg(long)]
+    pub output_part: Option<usize>,
+
+    /// List the part files left on disk when the download fails, for inspection
+    #[arg(long)]
+    pub keep_parts_on_error: bool,
+
+    /// List the part files of this download and whether each is complete, then exit
+    #[arg(long)]
+    pub parts_dir_info: bool,
+
+    /// Only download this inclusive, 0-based byte range (e.g. 1024-2047) into the output file
+    #[arg(long, value_parser = parse_byte_range, conflicts_with = "output_part")]
+    pub range: Option<(u64, u64)>,
+
+    /// How part sizes are chosen: equal, geo-inc (growing sizes), geo-dec (shrinking sizes)
+    #[arg(long, value_enum, default_value_t = PartStrategy::Equal)]
+    pub part_strategy: PartStrategy,
+
+    /// Size ratio between consecutive parts for the geo-inc and geo-dec strategies
+    #[arg(long, default_value_t = 2.0)]
+    pub part_geo_ratio: f64,
+
+    /// Read buffer size in bytes used when merging parts into the output file
+    #[arg(long, default_value_t = 64 * 1024)]
+    pub merge_buffer_size: usize,
+
+    /// Only merge the <output>.part<N> files found in --temp-dir into --output, without
+    /// downloading (recovers the parts of a crashed download)
+    #[arg(long, requires = "output")]
+    pub merge_only: bool,
+
+    /// Probe the server and save the resolved download (URL, output, split, proxy and
+    /// checksum) as a JSON plan instead of downloading, to .oxidown_plan or --save-plan=PATH
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".oxidown_plan",
+        conflicts_with_all = ["merge_only", "range", "output_part", "parts_dir_info", "spider", "spider_recursive"]
+    )]
+    pub save_plan: Option<PathBuf>,
+
+    /// Download according to a plan saved with --save-plan, without probing the server.
+    /// The plan replaces the URL, output, split, proxy and checksum options
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "url", "output", "save_plan", "merge_only", "threads", "max_parts", "max_concurrent_parts",
+            "part_strategy", "part_geo_ratio", "temp_dir", "temp_file_prefix", "temp_file_suffix",
+            "range", "output_part", "parts_dir_info", "proxy", "proxy_mode", "checksum",
+            "auto_checksum", "spider", "spider_recursive", "post_data", "post_json", "decompress",
+            "append_headers"
+        ]
+    )]
+    pub execute_plan: Option<PathBuf>,
+
+    /// Merge parts with O_DIRECT, bypassing the page cache (Linux only; the merge
+    /// buffer is rounded up to a multiple of 4 KiB)
+    #[arg(long)]
+    pub direct_io: bool,
+
     /// Log level (off, error, warn, info, debug, trace)
     #[arg(long, value_enum, default_value_t = LogLevel::Warn)]
     pub log_level: LogLevel,
 
-    /// Enable debug mode (sets log level to debug and enables detailed output)
-    #[arg(long, short = 'v')]
-    pub debug: bool,
+    /// Per-module log filter (e.g. oxidown::download=debug,warn); overrides --log-level
+    #[arg(long, value_parser = parse_log_filter)]
+    pub log_filter: Option<String>,
+
+    /// Enable debug mode (sets log level to debug and enables detailed output).
+    /// Repeat (-vv) for trace level
+    #[arg(long, short = 'v', action = ArgAction::Count)]
+    pub debug: u8,
+
+    /// Same as --log-level trace --debug (or -vv)
+    #[arg(long)]
+    pub very_verbose: bool,
+
+    /// Hide progress bars and spinners but keep log output
+    #[arg(long)]
+    pub no_progress: bool,
+
+    /// Hide progress bars and only log errors
+    #[arg(long, short = 'q', conflicts_with = "silent")]
+    pub quiet: bool,
+
+    /// Hide progress bars and all log output
+    #[arg(long, short = 's')]
+    pub silent: bool,
+
+    /// Show sizes in SI units (KB = 1000 bytes) instead of IEC units (KiB = 1024 bytes)
+    #[arg(long)]
+    pub si: bool,
+
+    /// Only check that the URL is accessible and print its status, type and size
+    #[arg(long)]
+    pub spider: bool,
+
+    /// Like --spider, but also check the links of HTML pages on the same host
+    #[arg(long)]
+    pub spider_recursive: bool,
+
+    /// How many links deep --spider-recursive follows from the start page
+    #[arg(long, default_value_t = 1, requires = "spider_recursive")]
+    pub spider_depth: usize,
+
+    /// Check GitHub for a newer release in the background and warn if one exists
+    #[arg(long)]
+    pub version_check: bool,
 
     /// Max retry attempts per part
     #[arg(long, default_value_t = 50)]
@@ -43,13 +307,251 @@ pub struct Args {
     #[arg(long, default_value_t = 1000)]
     pub retry_delay: u64,
 
-    /// Proxy URL (automatically enables --proxy-mode custom)
-    #[arg(long, short = 'x')]
+    /// Upper limit in milliseconds for the doubling retry delay
+    #[arg(long, default_value_t = 30_000)]
+    pub retry_delay_max: u64,
+
+    /// Connection attempts per part request before it counts as a failed part attempt
+    #[arg(long, default_value_t = 3)]
+    pub max_connection_attempts: u32,
+
+    /// Timeouts per part that are retried without using up a --retries attempt
+    #[arg(long, default_value_t = 5)]
+    pub max_timeouts_per_part: u32,
+
+    /// Expected checksum of the file (MD5, SHA-1, SHA-256 or SHA-512, detected by length)
+    #[arg(long)]
+    pub checksum: Option<String>,
+
+    /// Fetch `<URL>.sha256` (or `<URL>.md5`) and verify the download against it
+    #[arg(long)]
+    pub auto_checksum: bool,
+
+    /// Print the hash of the downloaded file to stdout (`<hash>  <filename>`)
+    #[arg(long, value_enum)]
+    pub output_hash: Option<HashAlgorithm>,
+
+    /// Append the hash of the downloaded file to this file in sha256sum format,
+    /// so it can be checked later with e.g. `sha256sum -c`
+    #[arg(long)]
+    pub output_checksum_file: Option<PathBuf>,
+
+    /// Hash algorithm used for --output-checksum-file
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+    pub output_checksum_algo: HashAlgorithm,
+
+    /// Verify the download against a checksum list (sha256sum/md5sum format)
+    #[arg(long)]
+    pub checksum_file: Option<PathBuf>,
+
+    /// Print a summary after the transfer; supports %{size_download}, %{speed_download},
+    /// %{time_total}, %{url_effective}, %{filename_effective} and %{http_code}
+    #[arg(long, short = 'w')]
+    pub write_out: Option<String>,
+
+    /// Restart the entire download up to N times if any part fails permanently
+    #[arg(long, default_value_t = 0)]
+    pub retry_all: u32,
+
+    /// Comma-separated HTTP error status codes that should be retried
+    #[arg(long, value_delimiter = ',', default_value = "408,429,500,502,503,504")]
+    pub retry_on: Vec<u16>,
+
+    /// Limit the total download rate, in bytes per second (suffixes k, m, g allowed)
+    #[arg(long, value_parser = parse_size_string)]
+    pub rate_limit: Option<u64>,
+
+    /// Burst size for --rate-limit, in bytes (default: one second of --rate-limit)
+    #[arg(long, requires = "rate_limit", value_parser = parse_size_string)]
+    pub rate_limit_burst: Option<u64>,
+
+    /// Fail unless the reported file size is at least this many bytes (e.g. 1M)
+    #[arg(long, value_parser = parse_size_string)]
+    pub min_filesize: Option<u64>,
+
+    /// Fail if the reported file size is larger than this many bytes (e.g. 2G)
+    #[arg(long, value_parser = parse_size_string)]
+    pub max_filesize: Option<u64>,
+
+    /// Abort the remaining parts as soon as one part fails (default)
+    #[arg(long, overrides_with = "no_fail_fast")]
+    pub fail_fast: bool,
+
+    /// Let all parts finish even if one of them fails
+    #[arg(long, overrides_with = "fail_fast")]
+    pub no_fail_fast: bool,
+
+    /// Force multi-part download even if the server does not advertise range support
+    #[arg(long)]
+    pub force_multipart: bool,
+
+    /// Send a POST request with this URL-encoded body (forces single-part download)
+    #[arg(long, conflicts_with = "post_json")]
+    pub post_data: Option<String>,
+
+    /// Send a POST request with this JSON body (forces single-part download)
+    #[arg(long)]
+    pub post_json: Option<String>,
+
+    /// Skip the download if the file was not modified since this date (RFC 2822 or RFC 3339)
+    #[arg(long)]
+    pub if_modified_since: Option<String>,
+
+    /// Skip the download if the server's ETag matches this value
+    #[arg(long)]
+    pub if_none_match: Option<String>,
+
+    /// Max probe attempts before giving up
+    #[arg(long, default_value_t = 3)]
+    pub probe_retries: u32,
+
+    /// Timeout in seconds for each probe request (HEAD and GET fallback)
+    #[arg(long, default_value_t = 10)]
+    pub probe_timeout: u64,
+
+    /// TCP connect timeout in seconds for probe requests (overrides --connect-timeout)
+    #[arg(long)]
+    pub probe_connect_timeout: Option<u64>,
+
+    /// Timeout in seconds for establishing each connection (0 = none).
+    /// Covers only the TCP/TLS connect, not the transfer
+    #[arg(long, default_value_t = 30)]
+    pub connect_timeout: u64,
+
+    /// Timeout in seconds between two reads of a response body (0 = none).
+    /// Restarts after every chunk, so it catches stalled transfers, not slow ones
+    #[arg(long, default_value_t = 0)]
+    pub read_timeout: u64,
+
+    /// Socket timeout in milliseconds for a single read or an unacknowledged send.
+    /// Lower-level than --read-timeout; sends are only covered on Linux (TCP_USER_TIMEOUT)
+    #[arg(long)]
+    pub socket_timeout: Option<u64>,
+
+    /// Proxy URL for all requests (cannot be combined with --proxy-mode)
+    #[arg(long, short = 'x', group = PROXY_GROUP)]
     pub proxy: Option<String>,
 
-    /// Proxy mode: auto (env), off (disable), custom (use --proxy)
-    #[arg(long, value_enum, default_value_t = ProxyMode::Auto)]
+    /// Proxy mode: auto (from the environment) or off (no proxy). Use --proxy for a custom one
+    #[arg(long, value_enum, default_value_t = ProxyMode::Auto, group = PROXY_GROUP)]
     pub proxy_mode: ProxyMode,
+
+    /// Send `Cache-Control: no-cache` and `Pragma: no-cache` to bypass CDN caches
+    #[arg(long, conflicts_with = "cache_control")]
+    pub no_http_cache: bool,
+
+    /// Custom Cache-Control request header value
+    #[arg(long)]
+    pub cache_control: Option<String>,
+
+    /// Accept request header value (e.g. application/octet-stream)
+    #[arg(long)]
+    pub accept: Option<String>,
+
+    /// Accept-Encoding request header value (e.g. gzip, identity, *)
+    #[arg(long)]
+    pub accept_encoding: Option<String>,
+
+    /// Decompress a gzip or deflate Content-Encoding while saving, even when the file
+    /// name suggests a compressed archive. Sends "Accept-Encoding: gzip, deflate" unless
+    /// --accept-encoding is given; such downloads are single-part, and checksums apply
+    /// to the decompressed file. Any other coding (e.g. xz, br, bzip2) fails the download
+    #[arg(long)]
+    pub decompress: bool,
+
+    /// Write the response headers at the start of the output file, before the data
+    /// (forces single-part download; checksums then cover the headers too)
+    #[arg(long)]
+    pub append_headers: bool,
+
+    /// Format of --append-headers: httpd ("# Name: Value" lines and a blank line) or json
+    #[arg(long, value_enum, default_value_t = HeadersFormat::Httpd, requires = "append_headers")]
+    pub headers_format: HeadersFormat,
+
+    /// Abort unless the server response has this header (e.g. "Content-Type: application/pdf").
+    /// Repeatable; checked on the probe response before the download starts
+    #[arg(long, value_parser = parse_expect_header)]
+    pub expect_header: Vec<(String, String)>,
+
+    /// HTTP protocol version: auto (ALPN), http10, http11, http2, http3 (not yet supported)
+    #[arg(long, value_enum, default_value_t = HttpVersion::Auto)]
+    pub http_version: HttpVersion,
+
+    /// Raw Cookie header value sent with every request (e.g. "name=value; name2=value2")
+    #[arg(long)]
+    pub cookies_string: Option<String>,
+
+    /// Bind outgoing connections to a network interface (e.g. eth0, en0; Unix only)
+    #[arg(long)]
+    pub interface: Option<String>,
+
+    /// Follow redirects to ftp:// URLs and download them over FTP
+    #[arg(long)]
+    pub follow_ftp: bool,
+
+    /// Idle time in seconds before the first TCP keepalive probe is sent
+    #[arg(long)]
+    pub keepalive_idle: Option<u64>,
+
+    /// Maximum number of unanswered TCP keepalive probes before the connection is dropped
+    #[arg(long)]
+    pub keepalive_count: Option<u32>,
+
+    /// Lowest TLS version to accept (1.0, 1.1, 1.2, 1.3)
+    #[arg(long, value_enum, default_value = "1.2")]
+    pub tls_min_version: TlsVersion,
+
+    /// Highest TLS version to accept (1.0, 1.1, 1.2, 1.3)
+    #[arg(long, value_enum, default_value = "1.3")]
+    pub tls_max_version: TlsVersion,
+
+    /// Allowed TLS cipher suites, separated by ':' (OpenSSL or IANA names).
+    /// Requires a build with the cipher-list feature
+    #[arg(long)]
+    pub cipher_list: Option<String>,
+
+    /// Verify the server certificate against this hostname instead of the URL host,
+    /// e.g. when downloading from an IP address. Requires a build with the cipher-list feature
+    #[arg(long, conflicts_with = "insecure")]
+    pub tls_hostname: Option<String>,
+
+    /// Skip TLS certificate verification (insecure, use only for testing)
+    #[arg(long, short = 'k')]
+    pub insecure: bool,
+
+    /// Deprecated wget-style spelling of --insecure
+    #[arg(long, hide = true)]
+    pub no_verify_ssl: bool,
+
+    /// Trust only the CA certificates in this PEM file instead of the system store
+    #[arg(long)]
+    pub cacert: Option<PathBuf>,
+
+    /// User name for HTTP authentication, sent only to the host of the URL
+    #[arg(long)]
+    pub auth_user: Option<String>,
+
+    /// Password for HTTP authentication
+    #[arg(long, requires = "auth_user")]
+    pub auth_password: Option<String>,
+
+    /// HTTP authentication scheme used with --auth-user (default: basic)
+    #[arg(long, value_enum, requires = "auth_user")]
+    pub http_auth_scheme: Option<AuthScheme>,
+
+    /// Keep idle pooled connections open for this many seconds (default: 90), so part
+    /// retries can reuse them instead of reconnecting
+    #[arg(long)]
+    pub pool_idle_timeout: Option<u64>,
+
+    /// Cache DNS lookups for this many seconds, then re-resolve (0 = use the OS resolver directly)
+    #[arg(long, default_value_t = 0)]
+    pub dns_cache_ttl: u64,
+
+    /// Log every HTTP request and response with headers (implies --log-level trace).
+    /// Authorization and cookie values are redacted
+    #[arg(long)]
+    pub trace_requests: bool,
 }
 
 #[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
@@ -69,17 +571,401 @@ pub struct Part {
     pub start: u64,
     pub end_inclusive: u64,
     pub path: PathBuf,
+    /// URL this part is downloaded from; empty for parts found on disk (`--merge-only`)
+    pub url: String,
+    /// Lowercase hex SHA-256 of the data received for this part
+    pub expected_hash: Option<String>,
+    /// Set if the download appended to a partial file; its hash is checked before merging
+    pub resumed: bool,
+}
+
+/// Where a cancelled parallel download stopped (`download_parts_parallel_with_cancel`)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartialResult {
+    /// Indices of the parts fully downloaded
+    pub completed_parts: Vec<usize>,
+    /// Indices of the parts stopped by the cancellation before they finished
+    pub interrupted_parts: Vec<usize>,
+    /// Indices of the parts that failed with an error of their own
+    pub failed_parts: Vec<usize>,
+    /// Bytes received across all parts
+    pub bytes_downloaded: u64,
+}
+
+/// Contents of the `.resume` file kept next to the part files of a download
+///
+/// Describes the split and the remote file the part files belong to, so that a later
+/// run only resumes parts that match (see `part::prepare_resume`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResumeState {
+    /// Size of the remote file
+    pub total_len: u64,
+    /// `ETag` of the remote file, if the server sent one
+    pub etag: Option<String>,
+    /// `Last-Modified` date of the remote file, if the server sent one
+    pub last_modified: Option<String>,
+    /// Byte range and file of each part, by part index
+    pub parts: Vec<ResumePart>,
+    /// Where the download stopped (empty while it is running)
+    pub progress: PartialResult,
+}
+
+/// One part recorded in a [`ResumeState`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResumePart {
+    pub start: u64,
+    pub end_inclusive: u64,
+    /// Name of the part file, in the directory of the state file
+    pub file_name: String,
+}
+
+/// A download resolved up to its split, saved with `--save-plan` (see `plan.rs`)
+///
+/// `--execute-plan` runs it without probing the server, possibly on another machine.
+#[derive(Clone, Debug)]
+pub struct DownloadPlan {
+    /// URL the parts are downloaded from, after redirects
+    pub url: String,
+    pub output: PathBuf,
+    /// Number of parts downloaded at once
+    pub threads: usize,
+    /// Size of the remote file
+    pub total_len: u64,
+    /// `ETag` of the remote file, if the server sent one
+    pub etag: Option<String>,
+    /// `Last-Modified` date of the remote file, if the server sent one
+    pub last_modified: Option<String>,
+    pub proxy_mode: ProxyMode,
+    /// Proxy URL, set with [`ProxyMode::Custom`]
+    pub proxy: Option<String>,
+    /// Expected checksum of the output file (lowercase hex) and its algorithm
+    pub checksum: Option<(HashAlgorithm, String)>,
+    /// The split, by part index
+    pub parts: Vec<Part>,
+}
+
+/// Result of a parallel download that can be cancelled
+#[derive(Debug)]
+pub enum ParallelOutcome {
+    /// Every part was downloaded; parts are in completion order with their hashes
+    Completed(Vec<Part>),
+    /// The download was cancelled before all parts finished
+    Cancelled(PartialResult),
+}
+
+/// How progress bars and byte counts are displayed
+#[derive(Clone, Copy, Debug)]
+pub struct ProgressOptions {
+    /// Whether to draw progress bars
+    pub show: bool,
+    /// Use SI units (1000-based) instead of IEC units (1024-based)
+    pub use_si: bool,
+}
+
+/// Settings used to build the HTTP client
+#[derive(Clone, Copy, Debug)]
+pub struct ClientOptions<'a> {
+    /// User-Agent string
+    pub user_agent: &'a str,
+    /// Proxy configuration mode
+    pub proxy_mode: ProxyMode,
+    /// Custom proxy URL (only used if `proxy_mode` is `Custom`)
+    pub proxy: Option<&'a str>,
+    /// Send `Cache-Control: no-cache` and `Pragma: no-cache`
+    pub no_http_cache: bool,
+    /// Custom `Cache-Control` value (ignored if `no_http_cache` is set)
+    pub cache_control: Option<&'a str>,
+    /// `Accept` header value (must look like a MIME type)
+    pub accept: Option<&'a str>,
+    /// `Accept-Encoding` header value
+    pub accept_encoding: Option<&'a str>,
+    /// Raw `Cookie` header value
+    pub cookies: Option<&'a str>,
+    /// Protocol version to use
+    pub http_version: HttpVersion,
+    /// Network interface whose address is used as the local address
+    pub interface: Option<&'a str>,
+    /// TCP connect timeout
+    pub connect_timeout: Option<Duration>,
+    /// Timeout between two reads of a response
+    pub read_timeout: Option<Duration>,
+    /// How long sent data may stay unacknowledged (`TCP_USER_TIMEOUT`, Linux only)
+    pub socket_timeout: Option<Duration>,
+    /// Stop at redirects to `ftp://` so they can be handed to the FTP client
+    pub follow_ftp: bool,
+    /// TCP keepalive idle time
+    pub keepalive_idle: Option<Duration>,
+    /// TCP keepalive probe count
+    pub keepalive_count: Option<u32>,
+    /// PEM bundle replacing the built-in root certificates
+    pub cacert: Option<&'a Path>,
+    /// Accept any server certificate
+    pub insecure: bool,
+    /// Lowest accepted TLS version
+    pub tls_min_version: TlsVersion,
+    /// Highest accepted TLS version
+    pub tls_max_version: TlsVersion,
+    /// Allowed cipher suites (`--cipher-list`)
+    pub cipher_list: Option<&'a str>,
+    /// Name server certificates are verified against (`--tls-hostname`)
+    pub tls_hostname: Option<&'a str>,
+    /// How long idle pooled connections are kept (`None` keeps reqwest's default)
+    pub pool_idle_timeout: Option<Duration>,
+    /// Lifetime of cached DNS lookups (`None` uses reqwest's default resolver)
+    pub dns_cache_ttl: Option<Duration>,
+    /// Raised to the length of each redirect chain followed, for `ProbeResult::redirect_count`
+    pub redirect_count: Option<&'a Arc<AtomicU32>>,
+}
+
+/// Per-part retry and failure handling settings
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts per part
+    pub max_retries: u32,
+    /// Base delay in milliseconds for exponential backoff
+    pub retry_delay_ms: u64,
+    /// Ceiling of the backoff in milliseconds
+    pub retry_delay_max_ms: u64,
+    /// Attempts to connect for each part request, retried after a fixed delay
+    pub max_connection_attempts: u32,
+    /// Timed out attempts per part that do not count against `max_retries`
+    pub max_timeouts_per_part: u32,
+    /// Connect timeout of the client, reported in timeout errors
+    pub connect_timeout: Option<Duration>,
+    /// Read timeout of the client, reported in timeout errors
+    pub read_timeout: Option<Duration>,
+    /// HTTP error status codes (4xx/5xx) that are worth retrying
+    pub retry_on: Vec<u16>,
+    /// Abort the remaining parts as soon as one part fails
+    pub fail_fast: bool,
+}
+
+/// State of a part's temporary file on disk (`--parts-dir-info`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartStatus {
+    /// File has exactly the expected size
+    Complete,
+    /// File exists with the given size, which differs from the expected one
+    Partial(u64),
+    /// File does not exist
+    Missing,
+}
+
+/// Request body sent instead of a plain GET (`--post-data` / `--post-json`)
+#[derive(Clone, Debug)]
+pub struct PostBody {
+    /// Value of the `Content-Type` header
+    pub content_type: &'static str,
+    pub data: String,
 }
 
 /// Result of probing server capabilities
+///
+/// Constructed through [`ProbeResultBuilder`], so new fields do not break construction sites.
+#[derive(Clone, Debug, Default)]
 pub struct ProbeResult {
     pub content_length: u64,
     pub accept_ranges: bool,
+    /// HTTP status code of the probe response
+    pub status_code: u16,
+    /// URL after following redirects
+    pub final_url: String,
+    /// Value of the `Content-Type` header, if any
+    pub content_type: Option<String>,
+    /// Value of the `Content-Disposition` header, if any
+    pub content_disposition: Option<String>,
+    /// Value of the `Content-Encoding` header, if any
+    pub content_encoding: Option<String>,
+    /// Value of the `Last-Modified` header, if any
+    pub last_modified: Option<String>,
+    /// Value of the `ETag` header, if any
+    pub etag: Option<String>,
+    /// Number of redirects followed to reach `final_url`
+    pub redirect_count: u32,
+}
+
+/// Chooses the output filename when `--output` is not given (`derive_filename`).
+#[derive(Debug, Default)]
+pub struct FilenameDeriver;
+
+/// Builder for [`ProbeResult`]; unset fields keep their default values
+#[derive(Debug, Default)]
+pub struct ProbeResultBuilder {
+    pub(crate) result: ProbeResult,
+}
+
+/// Statistics about a finished (or failed) download, used by `--write-out`
+#[derive(Debug, Default)]
+pub struct DownloadSummary {
+    /// Number of bytes downloaded
+    pub size_download: u64,
+    /// Total elapsed time in seconds
+    pub time_total: f64,
+    /// URL after following redirects
+    pub url_effective: String,
+    /// Path of the output file
+    pub filename_effective: String,
+    /// HTTP status code reported by the probe
+    pub http_code: u16,
 }
 
 #[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
 pub enum ProxyMode {
     Auto,
     Off,
+    /// Selected by `--proxy`, not by name
+    #[value(skip)]
     Custom,
 }
+
+/// Format of the response headers written by `--append-headers`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum HeadersFormat {
+    /// `# Name: Value` lines followed by a blank line
+    Httpd,
+    /// A JSON object on one line
+    Json,
+}
+
+/// How the output file is opened (`--output-mode`)
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputMode {
+    /// Create the file, or truncate it if it exists
+    Create,
+    /// Truncate an existing file; fail if there is none
+    Truncate,
+    /// Append to the file, creating it if it does not exist
+    Append,
+    /// Create a new file; fail if it already exists (O_EXCL)
+    Exclusive,
+}
+
+/// HTTP protocol version used by the client
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum HttpVersion {
+    /// HTTP/1.1 with HTTP/2 negotiated via TLS ALPN
+    Auto,
+    /// HTTP/1.0 semantics (HTTP/1 only, `Connection: close`, no connection reuse)
+    Http10,
+    /// HTTP/1.1 only
+    Http11,
+    /// HTTP/2 with prior knowledge
+    Http2,
+    /// HTTP/3 (reserved for future support)
+    Http3,
+}
+
+/// TLS protocol version bound (`--tls-min-version`, `--tls-max-version`)
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum TlsVersion {
+    #[value(name = "1.0")]
+    Tls10,
+    #[value(name = "1.1")]
+    Tls11,
+    #[value(name = "1.2")]
+    Tls12,
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+/// Part split strategy selected on the command line
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PartStrategy {
+    /// Equally sized parts
+    Equal,
+    /// Each part is `--part-geo-ratio` times larger than the previous one
+    GeoInc,
+    /// Each part is `--part-geo-ratio` times smaller than the previous one
+    GeoDec,
+}
+
+/// How the file is divided into parts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartSizeStrategy {
+    Equal,
+    /// Part sizes grow geometrically by `ratio`
+    GeometricIncrease {
+        ratio: f64,
+    },
+    /// Part sizes shrink geometrically by `ratio`
+    GeometricDecrease {
+        ratio: f64,
+    },
+}
+
+/// HTTP authentication schemes (`--http-auth-scheme`).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AuthScheme {
+    /// Sent with every request
+    Basic,
+    /// Answered after the server's 401 challenge (RFC 7616: MD5, SHA-256 and -sess)
+    Digest,
+    Ntlm,
+    Negotiate,
+}
+
+/// Credentials for the download host (`--auth-user`, `--auth-password`).
+pub struct HttpAuth {
+    pub scheme: AuthScheme,
+    pub user: String,
+    pub password: String,
+    /// Host the credentials are sent to; requests to other hosts go without them
+    pub host: String,
+    /// Latest Digest challenge, answered preemptively on the following requests
+    pub digest: Mutex<Option<DigestChallenge>>,
+}
+
+/// Parameters of a `WWW-Authenticate: Digest` challenge.
+#[derive(Clone, Debug)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    /// `algorithm` as sent by the server, echoed back in the response
+    pub algorithm_name: String,
+    /// Hash function of the algorithm (MD5 or SHA-256)
+    pub algorithm: HashAlgorithm,
+    /// `-sess` variant: the credentials hash also covers the nonces
+    pub session: bool,
+    /// Server offered `qop=auth`
+    pub qop_auth: bool,
+    /// Requests answered with this nonce so far (`nc`)
+    pub nonce_count: u32,
+}
+
+/// Hash algorithms supported for checksum verification.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    #[test]
+    fn proxy_and_proxy_mode_conflict() {
+        let err = Cli::try_parse_from([
+            "oxidown",
+            "--proxy",
+            "http://proxy:3128",
+            "--proxy-mode",
+            "off",
+            "http://example.com/file",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+
+        let cli = Cli::try_parse_from(["oxidown", "-x", "http://proxy:3128", "http://e.com/f"]);
+        assert_eq!(
+            cli.unwrap().args.proxy.as_deref(),
+            Some("http://proxy:3128")
+        );
+        let cli = Cli::try_parse_from(["oxidown", "--proxy-mode", "off", "http://e.com/f"]);
+        assert_eq!(cli.unwrap().args.proxy_mode, ProxyMode::Off);
+    }
+}
@@ -1,12 +1,40 @@
+use std::borrow::Cow;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
 use reqwest::{
-    Client, Proxy,
-    header::{HeaderMap, HeaderValue, USER_AGENT},
+    Certificate, Client, Proxy, Request, RequestBuilder, Response,
+    header::{
+        ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CACHE_CONTROL, CONNECTION, COOKIE, HeaderMap,
+        HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, PRAGMA, PROXY_AUTHORIZATION,
+        SET_COOKIE, USER_AGENT,
+    },
+    redirect::Policy,
+    tls,
+};
+use std::sync::{Arc, atomic::Ordering};
+use std::time::Duration;
+use tracing::{Level, debug, trace};
+use tracing_subscriber::{
+    EnvFilter, FmtSubscriber, filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt,
 };
-use tracing::{Level, debug};
-use tracing_subscriber::FmtSubscriber;
 
+use crate::auth;
+use crate::date::{format_http_date, parse_date};
+use crate::dns::CachingResolver;
 use crate::error::ProgramError;
-use crate::types::{LogLevel, ProxyMode};
+use crate::progress::{GIB, KIB, MIB, WarningCollector};
+use crate::types::{
+    ClientOptions, FilenameDeriver, HttpVersion, LogLevel, OutputMode, ProxyMode, TlsVersion,
+};
+
+/// Redirect limit when `--follow-ftp` replaces the default policy (same as reqwest's).
+const MAX_REDIRECTS: usize = 10;
+
+/// TCP keepalive defaults, matching reqwest's own defaults.
+const DEFAULT_KEEPALIVE_IDLE: Duration = Duration::from_secs(15);
+const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const DEFAULT_KEEPALIVE_COUNT: u32 = 3;
 
 /// Initializes the tracing subscriber for logging.
 ///
@@ -18,27 +46,28 @@ use crate::types::{LogLevel, ProxyMode};
 /// # Arguments
 ///
 /// * `level` - The desired log level (Off, Error, Warn, Info, Debug, Trace).
+/// * `filter` - Optional `EnvFilter` directives (e.g. `oxidown::http=trace,warn`); overrides `level`.
 /// * `debug_mode` - If true, forces level to at least DEBUG and enables detailed formatting.
-pub fn init_tracing(level: LogLevel, debug_mode: bool) {
-    let trace_level = if debug_mode {
-        if matches!(level, LogLevel::Trace) {
-            Level::TRACE
-        } else {
-            Level::DEBUG
-        }
-    } else {
-        match level {
-            LogLevel::Off => return,
-            LogLevel::Error => Level::ERROR,
-            LogLevel::Warn => Level::WARN,
-            LogLevel::Info => Level::INFO,
-            LogLevel::Debug => Level::DEBUG,
-            LogLevel::Trace => Level::TRACE,
-        }
+/// * `warnings` - Collector that receives a copy of every WARN event.
+pub fn init_tracing(
+    level: LogLevel,
+    filter: Option<String>,
+    debug_mode: bool,
+    warnings: &Arc<WarningCollector>,
+) {
+    let env_filter = match filter {
+        // Already checked by `parse_log_filter` at argument parsing
+        Some(directives) => EnvFilter::new(directives),
+        None => match level_for(level, debug_mode) {
+            Some(level) => {
+                EnvFilter::default().add_directive(LevelFilter::from_level(level).into())
+            }
+            None => return,
+        },
     };
 
     let builder = FmtSubscriber::builder()
-        .with_max_level(trace_level)
+        .with_env_filter(env_filter)
         .with_writer(std::io::stderr);
 
     if debug_mode {
@@ -46,38 +75,133 @@ pub fn init_tracing(level: LogLevel, debug_mode: bool) {
             .with_target(true)
             .with_file(true)
             .with_line_number(true)
+            .finish()
+            .with(warnings.layer())
             .init();
     } else {
         builder
             .with_target(false)
             .without_time()
             .with_level(true)
+            .finish()
+            .with(warnings.layer())
             .init();
     }
 }
 
+/// Maps a [`LogLevel`] to the maximum tracing level, or `None` when logging is off.
+fn level_for(level: LogLevel, debug_mode: bool) -> Option<Level> {
+    let level = if debug_mode {
+        if matches!(level, LogLevel::Trace) {
+            Level::TRACE
+        } else {
+            Level::DEBUG
+        }
+    } else {
+        match level {
+            LogLevel::Off => return None,
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Trace => Level::TRACE,
+        }
+    };
+    Some(level)
+}
+
+impl From<TlsVersion> for tls::Version {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls10 => tls::Version::TLS_1_0,
+            TlsVersion::Tls11 => tls::Version::TLS_1_1,
+            TlsVersion::Tls12 => tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => tls::Version::TLS_1_3,
+        }
+    }
+}
+
 /// Builds and configures the HTTP Client.
 ///
 /// Sets up the User-Agent, Proxy settings (auto, off, or custom), and other default headers.
 ///
+/// Each field of [`ClientOptions`] maps to a header or `ClientBuilder` setting.
+/// reqwest cannot emit HTTP/1.0 request lines, so `HttpVersion::Http10` restricts the
+/// client to HTTP/1, sends `Connection: close` and disables connection pooling.
+/// With `HttpVersion::Auto`, HTTP/1.1 is used with HTTP/2 negotiated via TLS ALPN.
+///
 /// # Arguments
 ///
-/// * `ua` - User-Agent string.
-/// * `proxy_mode` - Proxy configuration mode.
-/// * `proxy` - Optional custom proxy URL (only used if `proxy_mode` is `Custom`).
+/// * `options` - Client settings derived from the command line.
 ///
 /// # Returns
 ///
 /// * `Ok(Client)` - A configured reqwest Client.
-/// * `Err(ProgramError)` - If client configuration fails (e.g. invalid proxy URL).
-pub fn build_client(
-    ua: &str,
-    proxy_mode: ProxyMode,
-    proxy: Option<&str>,
-) -> Result<Client, ProgramError> {
+/// * `Err(ProgramError)` - If client configuration fails (e.g. invalid proxy URL or MIME type).
+pub fn build_client(options: &ClientOptions) -> Result<Client, ProgramError> {
+    let ClientOptions {
+        user_agent: ua,
+        proxy_mode,
+        proxy,
+        no_http_cache,
+        cache_control,
+        accept,
+        accept_encoding,
+        cookies,
+        http_version,
+        interface,
+        connect_timeout,
+        read_timeout,
+        socket_timeout,
+        follow_ftp,
+        keepalive_idle,
+        keepalive_count,
+        cacert,
+        insecure,
+        tls_min_version,
+        tls_max_version,
+        cipher_list,
+        tls_hostname,
+        pool_idle_timeout,
+        dns_cache_ttl,
+        redirect_count,
+    } = *options;
+
     let mut headers = HeaderMap::new();
     headers.insert(USER_AGENT, HeaderValue::from_str(ua)?);
 
+    let cache_control = if no_http_cache {
+        headers.insert(PRAGMA, HeaderValue::from_static("no-cache"));
+        Some("no-cache")
+    } else {
+        cache_control
+    };
+    if let Some(value) = cache_control {
+        headers.insert(CACHE_CONTROL, HeaderValue::from_str(value)?);
+        debug!(cache_control = %value, "Cache-Control header set");
+    }
+
+    if let Some(mime) = accept {
+        if !mime.contains('/') || mime.chars().any(char::is_whitespace) {
+            return Err(ProgramError::ArgNotValid(format!(
+                "invalid MIME type for --accept: {}",
+                mime
+            )));
+        }
+        headers.insert(ACCEPT, HeaderValue::from_str(mime)?);
+        trace!(accept = %mime, "Accept header set");
+    }
+    if let Some(encoding) = accept_encoding {
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(encoding)?);
+        trace!(accept_encoding = %encoding, "Accept-Encoding header set");
+    }
+    if let Some(cookies) = cookies {
+        // Allow the value to be pasted with its header name
+        let value = cookies.strip_prefix("Cookie:").unwrap_or(cookies).trim();
+        headers.insert(COOKIE, HeaderValue::from_str(value)?);
+        debug!("Cookie header set");
+    }
+
     debug!(
         user_agent = %ua,
         proxy_mode = ?proxy_mode,
@@ -85,8 +209,169 @@ pub fn build_client(
         "Building HTTP client"
     );
 
+    if http_version == HttpVersion::Http10 {
+        headers.insert(CONNECTION, HeaderValue::from_static("close"));
+    }
+
+    trace!("Default request headers:");
+    trace_headers(&headers);
+
     let mut builder = Client::builder().default_headers(headers);
 
+    match http_version {
+        HttpVersion::Auto => {}
+        HttpVersion::Http10 => {
+            builder = builder.http1_only().pool_max_idle_per_host(0);
+            debug!("HTTP/1.0 semantics forced");
+        }
+        HttpVersion::Http11 => {
+            builder = builder.http1_only();
+            debug!("HTTP/1.1 forced");
+        }
+        HttpVersion::Http2 => {
+            builder = builder.http2_prior_knowledge();
+            debug!("HTTP/2 forced");
+        }
+        HttpVersion::Http3 => {
+            return Err(ProgramError::ArgNotValid(
+                "HTTP/3 is not supported yet".to_string(),
+            ));
+        }
+    }
+
+    if let Some(timeout) = connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = read_timeout {
+        builder = builder.read_timeout(timeout);
+    }
+    if let Some(timeout) = socket_timeout {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        {
+            builder = builder.tcp_user_timeout(timeout);
+        }
+        #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+        debug!(
+            timeout = ?timeout,
+            "TCP_USER_TIMEOUT is not available, socket timeout only applies to reads"
+        );
+    }
+    debug!(
+        connect_timeout = ?connect_timeout,
+        read_timeout = ?read_timeout,
+        socket_timeout = ?socket_timeout,
+        "Timeouts configured"
+    );
+
+    let redirect_count = redirect_count.cloned();
+    builder = builder.redirect(Policy::custom(move |attempt| {
+        // reqwest cannot fetch ftp:// URLs; stop there and return the redirect itself
+        if follow_ftp && attempt.url().scheme() == "ftp" {
+            attempt.stop()
+        } else if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else {
+            // `previous` holds the URLs already requested, one per redirect so far
+            let followed = attempt.previous().len() as u32;
+            trace!(redirect = followed, location = %attempt.url(), "Following redirect");
+            if let Some(count) = &redirect_count {
+                count.fetch_max(followed, Ordering::Relaxed);
+            }
+            attempt.follow()
+        }
+    }));
+    if follow_ftp {
+        debug!("Redirects to FTP will be followed");
+    }
+
+    let keepalive_idle = keepalive_idle.unwrap_or(DEFAULT_KEEPALIVE_IDLE);
+    let keepalive_count = keepalive_count.unwrap_or(DEFAULT_KEEPALIVE_COUNT);
+    builder = builder
+        .tcp_keepalive(keepalive_idle)
+        .tcp_keepalive_interval(DEFAULT_KEEPALIVE_INTERVAL)
+        .tcp_keepalive_retries(keepalive_count);
+    debug!(
+        keepalive_idle = ?keepalive_idle,
+        keepalive_interval = ?DEFAULT_KEEPALIVE_INTERVAL,
+        keepalive_count = keepalive_count,
+        "TCP keepalive configured"
+    );
+
+    if let Some(path) = cacert {
+        let certs = load_ca_bundle(path)?;
+        debug!(cacert = ?path, count = certs.len(), "Using custom CA bundle");
+        builder = builder.tls_certs_only(certs);
+    }
+    if tls_min_version > tls_max_version {
+        return Err(ProgramError::ArgNotValid(
+            "tls-min-version must not be above tls-max-version".to_string(),
+        ));
+    }
+    // rustls implements TLS 1.2 and 1.3 only; lower minimums are simply never negotiated
+    if tls_max_version < TlsVersion::Tls12 {
+        return Err(ProgramError::ArgNotValid(
+            "tls-max-version must be 1.2 or 1.3 (TLS 1.0 and 1.1 are not supported)".to_string(),
+        ));
+    }
+    builder = builder
+        .tls_version_min(tls_min_version.into())
+        .tls_version_max(tls_max_version.into());
+    debug!(
+        tls_min_version = ?tls_min_version,
+        tls_max_version = ?tls_max_version,
+        "TLS version range configured"
+    );
+
+    if insecure {
+        builder = builder.tls_danger_accept_invalid_certs(true);
+        debug!("TLS certificate verification disabled");
+    }
+
+    // Replaces the TLS settings above with an equivalent rustls configuration
+    if cipher_list.is_some() || tls_hostname.is_some() {
+        #[cfg(feature = "cipher-list")]
+        {
+            builder = builder.tls_backend_preconfigured(crate::cipher::rustls_config(
+                cipher_list,
+                tls_hostname,
+                tls_min_version,
+                tls_max_version,
+                cacert,
+                insecure,
+                http_version,
+            )?);
+        }
+        #[cfg(not(feature = "cipher-list"))]
+        {
+            let option = if cipher_list.is_some() {
+                "--cipher-list"
+            } else {
+                "--tls-hostname"
+            };
+            return Err(ProgramError::ArgNotValid(format!(
+                "{} requires oxidown to be built with the cipher-list feature",
+                option
+            )));
+        }
+    }
+
+    // Pool activity ("reuse idle connection") is logged by hyper_util at DEBUG
+    if let Some(timeout) = pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+        debug!(pool_idle_timeout = ?timeout, "Connection pool idle timeout set");
+    }
+
+    if let Some(ttl) = dns_cache_ttl {
+        builder = builder.dns_resolver(Arc::new(CachingResolver::new(ttl)));
+        debug!(dns_cache_ttl = ?ttl, "DNS cache enabled");
+    }
+
+    if let Some(name) = interface {
+        let addr = resolve_interface_addr(name)?;
+        builder = builder.local_address(addr);
+        debug!(interface = %name, local_address = %addr, "Bound to network interface");
+    }
+
     match proxy_mode {
         ProxyMode::Auto => {}
         ProxyMode::Off => {
@@ -108,22 +393,949 @@ pub fn build_client(
     Ok(client)
 }
 
-/// Derives a filename from a URL path.
+/// Builds the conditional request headers sent with the probe.
+///
+/// # Arguments
+///
+/// * `if_modified_since` - RFC 2822 or RFC 3339 date, sent as an HTTP-date.
+/// * `if_none_match` - ETag value, sent verbatim.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` - If the date cannot be parsed.
+pub fn conditional_headers(
+    if_modified_since: Option<&str>,
+    if_none_match: Option<&str>,
+) -> Result<HeaderMap, ProgramError> {
+    let mut headers = HeaderMap::new();
+
+    if let Some(date) = if_modified_since {
+        let timestamp = parse_date(date).ok_or_else(|| {
+            ProgramError::ArgNotValid(format!("invalid date for --if-modified-since: {}", date))
+        })?;
+        let http_date = format_http_date(timestamp);
+        debug!(if_modified_since = %http_date, "Conditional request on modification date");
+        headers.insert(IF_MODIFIED_SINCE, HeaderValue::from_str(&http_date)?);
+    }
+    if let Some(etag) = if_none_match {
+        debug!(if_none_match = %etag, "Conditional request on ETag");
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+    }
+
+    Ok(headers)
+}
+
+/// Reads every certificate of a PEM bundle (`--cacert`).
+///
+/// # Returns
+///
+/// * `Err(ProgramError::Io)` - If the file cannot be read.
+/// * `Err(ProgramError::ArgNotValid)` - If a certificate is malformed or the file has none.
+fn load_ca_bundle(path: &Path) -> Result<Vec<Certificate>, ProgramError> {
+    let pem = std::fs::read(path)?;
+    let certs = Certificate::from_pem_bundle(&pem).map_err(|e| {
+        ProgramError::ArgNotValid(format!("invalid CA bundle {}: {}", path.display(), e))
+    })?;
+    if certs.is_empty() {
+        return Err(ProgramError::ArgNotValid(format!(
+            "no certificates found in CA bundle {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Resolves the IP address assigned to a network interface.
+///
+/// Walks the interface list returned by `getifaddrs` and returns the first IPv4 address
+/// of the named interface, falling back to its first IPv6 address.
+///
+/// # Returns
+///
+/// * `Ok(IpAddr)` - The address to bind outgoing connections to.
+/// * `Err(ProgramError::ArgNotValid)` - If the interface does not exist or has no address.
+#[cfg(unix)]
+fn resolve_interface_addr(name: &str) -> Result<IpAddr, ProgramError> {
+    use std::ffi::CStr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: `getifaddrs` initializes the list pointer on success; it is freed below.
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        return Err(ProgramError::Io(std::io::Error::last_os_error()));
+    }
+
+    let mut v4 = None;
+    let mut v6 = None;
+    let mut cursor = ifaddrs;
+    while !cursor.is_null() {
+        // SAFETY: `cursor` points into the list returned by `getifaddrs`, which stays valid
+        // until `freeifaddrs` is called.
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+
+        if entry.ifa_name.is_null() || entry.ifa_addr.is_null() {
+            continue;
+        }
+        // SAFETY: `ifa_name` is a valid NUL-terminated string for the lifetime of the list.
+        let entry_name = unsafe { CStr::from_ptr(entry.ifa_name) };
+        if entry_name.to_bytes() != name.as_bytes() {
+            continue;
+        }
+
+        // SAFETY: `ifa_addr` is non-null and `sa_family` tells us the concrete sockaddr type.
+        match i32::from(unsafe { (*entry.ifa_addr).sa_family }) {
+            libc::AF_INET if v4.is_none() => {
+                let sin = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in) };
+                v4 = Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                    sin.sin_addr.s_addr,
+                ))));
+            }
+            libc::AF_INET6 if v6.is_none() => {
+                let sin6 = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in6) };
+                v6 = Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)));
+            }
+            _ => {}
+        }
+    }
+
+    // SAFETY: `ifaddrs` was allocated by `getifaddrs` and is not used after this point.
+    unsafe { libc::freeifaddrs(ifaddrs) };
+
+    v4.or(v6).ok_or_else(|| {
+        ProgramError::ArgNotValid(format!("interface {} not found or has no address", name))
+    })
+}
+
+/// Resolves the IP address assigned to a network interface.
+///
+/// Interface lookup is only implemented for Unix platforms.
+#[cfg(not(unix))]
+fn resolve_interface_addr(name: &str) -> Result<IpAddr, ProgramError> {
+    Err(ProgramError::ArgNotValid(format!(
+        "--interface {} is not supported on this platform",
+        name
+    )))
+}
+
+/// Download script names that say nothing about the file they return.
+const GENERIC_FILENAMES: [&str; 6] = [
+    "download",
+    "download.php",
+    "get",
+    "get.php",
+    "index.php",
+    "file.php",
+];
+
+impl FilenameDeriver {
+    /// Derives the output path of a download.
+    ///
+    /// Sources are tried in order, skipping names that are empty, `.`/`..` or generic
+    /// script names such as `download.php`; path components in server-supplied names are
+    /// dropped so the file always lands in the current directory:
+    ///
+    /// 1. `explicit` (`--output`)
+    /// 2. `Content-Disposition: filename*=` (RFC 5987, UTF-8 or ISO-8859-1)
+    /// 3. `Content-Disposition: filename=`
+    /// 4. The last segment of the URL path
+    /// 5. A `filename`, `file` or `name` query parameter
+    /// 6. `<host>_download.bin`
+    /// 7. `index.html`
+    ///
+    /// # Examples
+    ///
+    /// * `http://example.com/file.zip` -> "file.zip"
+    /// * `http://example.com/get.php?file=a.iso` -> "a.iso"
+    /// * `http://example.com/dir/` -> "example.com_download.bin"
+    /// * `attachment; filename*=UTF-8''na%C3%AFve.txt` -> "naïve.txt"
+    pub fn derive_filename(
+        &self,
+        url: &str,
+        content_disposition: Option<&str>,
+        explicit: Option<PathBuf>,
+    ) -> PathBuf {
+        if let Some(path) = explicit {
+            return path;
+        }
+
+        let disposition = content_disposition.map(parse_content_disposition);
+        let url = reqwest::Url::parse(url).ok();
+        let candidates = [
+            disposition.as_ref().and_then(|(ext, _)| ext.clone()),
+            disposition.and_then(|(_, plain)| plain),
+            url.as_ref().and_then(|u| {
+                u.path_segments()
+                    .and_then(|mut s| s.next_back())
+                    .map(str::to_string)
+            }),
+            url.as_ref().and_then(|u| {
+                ["filename", "file", "name"].iter().find_map(|key| {
+                    u.query_pairs()
+                        .find(|(k, _)| k == key)
+                        .map(|(_, v)| v.into_owned())
+                })
+            }),
+            url.as_ref()
+                .and_then(|u| u.host_str().map(|h| format!("{}_download.bin", h))),
+        ];
+
+        for (source, name) in candidates.into_iter().enumerate() {
+            if let Some(name) = name.as_deref().and_then(usable_filename) {
+                debug!(
+                    filename = name,
+                    source = source + 2,
+                    "Derived output filename"
+                );
+                return PathBuf::from(name);
+            }
+        }
+        PathBuf::from("index.html")
+    }
+}
+
+/// Returns the last path component of `name` unless it is empty, `.`, `..` or generic.
+fn usable_filename(name: &str) -> Option<&str> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name).trim();
+    let generic = GENERIC_FILENAMES
+        .iter()
+        .any(|g| g.eq_ignore_ascii_case(name));
+    (!name.is_empty() && name != "." && name != ".." && !generic).then_some(name)
+}
+
+/// Extracts `(filename*, filename)` from a `Content-Disposition` value.
+///
+/// `filename*` is decoded from `charset'language'percent-encoded` (RFC 5987); other
+/// charsets than UTF-8 and ISO-8859-1 are ignored.
+///
+/// # Examples
+///
+/// * `attachment; filename="a b.txt"` -> `(None, Some("a b.txt"))`
+/// * `attachment; filename*=UTF-8''%E2%82%AC.txt` -> `(Some("€.txt"), None)`
+fn parse_content_disposition(value: &str) -> (Option<String>, Option<String>) {
+    let (mut extended, mut plain) = (None, None);
+    for param in split_outside_quotes(value, ';') {
+        let Some((key, val)) = param.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => extended = decode_ext_value(val.trim()),
+            "filename" => plain = Some(unquote(val.trim())),
+            _ => {}
+        }
+    }
+    (extended, plain)
+}
+
+/// Decodes an RFC 5987 `ext-value` such as `UTF-8'en'%C3%A4.txt`.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut fields = value.splitn(3, '\'');
+    let charset = fields.next()?.to_ascii_lowercase();
+    let encoded = fields.nth(1)?;
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%'
+            && let Some(hex) = tail.get(..2)
+            && let Ok(byte) = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16)
+        {
+            bytes.push(byte);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    match charset.as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        // Every ISO-8859-1 byte is the Unicode code point of the same value
+        "iso-8859-1" => Some(bytes.into_iter().map(char::from).collect()),
+        _ => None,
+    }
+}
+
+/// Splits a header value at `sep` where it is not inside a quoted-string.
 ///
-/// Extracts the last segment of the URL path. If the URL ends in a slash or has no
-/// path segments, returns "index.html".
+/// # Examples
+///
+/// * `a="x,y", b` split at `,` -> `["a=\"x,y\"", " b"]`
+pub fn split_outside_quotes(value: &str, sep: char) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+/// Removes the quotes and backslash escapes of a quoted-string; tokens are returned as is.
+pub fn unquote(value: &str) -> String {
+    let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+        return value.to_string();
+    };
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        out.push(if c == '\\' {
+            chars.next().unwrap_or(c)
+        } else {
+            c
+        });
+    }
+    out
+}
+
+/// Returns true if `path` is a block or character device (e.g. `/dev/sdb`).
+///
+/// On Windows, device namespace paths such as `\\.\PhysicalDrive0` are treated as devices.
+#[cfg(unix)]
+pub fn is_device(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path)
+        .map(|m| m.file_type().is_block_device() || m.file_type().is_char_device())
+        .unwrap_or(false)
+}
+
+/// Returns true if `path` is a block or character device (e.g. `/dev/sdb`).
+///
+/// On Windows, device namespace paths such as `\\.\PhysicalDrive0` are treated as devices.
+#[cfg(windows)]
+pub fn is_device(path: &Path) -> bool {
+    path.to_string_lossy().starts_with(r"\\.\")
+}
+
+/// Returns true if `path` is a block or character device (e.g. `/dev/sdb`).
+#[cfg(not(any(unix, windows)))]
+pub fn is_device(_path: &Path) -> bool {
+    false
+}
+
+/// Sets the modification time of `path` to `unix_secs` seconds since the Unix epoch
+/// (`--timestamp`, `--set-mtime`).
+///
+/// Goes through `File::set_modified`, which maps to `futimens` on Unix and
+/// `SetFileTime` on Windows; both need the file opened for writing. The access time
+/// is left unchanged.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::Io)` - If the file cannot be opened or the platform rejects the time.
+pub fn set_file_mtime(path: &Path, unix_secs: i64) -> Result<(), ProgramError> {
+    let offset = Duration::from_secs(unix_secs.unsigned_abs());
+    let mtime = if unix_secs >= 0 {
+        std::time::UNIX_EPOCH.checked_add(offset)
+    } else {
+        std::time::UNIX_EPOCH.checked_sub(offset)
+    }
+    .ok_or_else(|| ProgramError::ArgNotValid(format!("mtime {} is out of range", unix_secs)))?;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_modified(mtime)?;
+    debug!(path = ?path, mtime = unix_secs, "Modification time set");
+    Ok(())
+}
+
+/// Returns the open options for `--output-mode`.
+///
+/// Kept as `std` options so that callers can add platform flags such as `O_DIRECT`
+/// before opening; [`open_output_file`] wraps them for tokio.
+pub fn output_open_options(mode: OutputMode) -> std::fs::OpenOptions {
+    let mut options = std::fs::OpenOptions::new();
+    match mode {
+        OutputMode::Create => options.write(true).create(true).truncate(true),
+        OutputMode::Truncate => options.write(true).truncate(true),
+        OutputMode::Append => options.append(true).create(true),
+        OutputMode::Exclusive => options.write(true).create_new(true),
+    };
+    options
+}
+
+/// Opens (or creates) the output file as selected by `--output-mode`.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::Io)` - If the file cannot be opened; with `truncate` when it does
+///   not exist, with `exclusive` when it already does.
+pub async fn open_output_file(
+    path: &Path,
+    mode: OutputMode,
+) -> Result<tokio::fs::File, ProgramError> {
+    debug!(output = ?path, mode = ?mode, "Opening output file");
+    tokio::fs::OpenOptions::from(output_open_options(mode))
+        .open(path)
+        .await
+        .map_err(|e| output_mode_error(path, mode, e))
+}
+
+/// Checks up front that `path` can be opened with `mode`, so that a multi-part download
+/// does not fail only when merging.
+pub fn check_output_mode(path: &Path, mode: OutputMode) -> Result<(), ProgramError> {
+    let kind = match mode {
+        OutputMode::Truncate if !path.exists() => std::io::ErrorKind::NotFound,
+        OutputMode::Exclusive if path.exists() => std::io::ErrorKind::AlreadyExists,
+        _ => return Ok(()),
+    };
+    Err(output_mode_error(path, mode, kind.into()))
+}
+
+/// Adds the path and mode to the errors `--output-mode` is expected to cause.
+pub fn output_mode_error(path: &Path, mode: OutputMode, e: std::io::Error) -> ProgramError {
+    let reason = match (mode, e.kind()) {
+        (OutputMode::Truncate, std::io::ErrorKind::NotFound) => "does not exist",
+        (OutputMode::Exclusive, std::io::ErrorKind::AlreadyExists) => "already exists",
+        _ => return e.into(),
+    };
+    ProgramError::Io(std::io::Error::new(
+        e.kind(),
+        format!(
+            "{} {} (--output-mode {})",
+            path.display(),
+            reason,
+            format!("{:?}", mode).to_ascii_lowercase()
+        ),
+    ))
+}
+
+/// Expands a `--user-agent-template`.
+///
+/// `{version}` is the crate version, `{os}` and `{arch}` come from `std::env::consts`.
+/// Other text, including unknown `{...}` variables, is kept as is.
+///
+/// # Examples
+///
+/// * `"oxidown/{version} ({os}/{arch})"` -> `"oxidown/0.1.0 (linux/x86_64)"`
+pub fn expand_user_agent_template(template: &str) -> String {
+    template
+        .replace("{version}", env!("CARGO_PKG_VERSION"))
+        .replace("{os}", std::env::consts::OS)
+        .replace("{arch}", std::env::consts::ARCH)
+}
+
+/// Validates `--log-filter` directives, keeping them as a string for [`init_tracing`].
+///
+/// # Examples
+///
+/// * `"oxidown::download=debug,warn"` -> ok
+/// * `"oxidown=loud"` -> error (unknown level)
+pub fn parse_log_filter(s: &str) -> Result<String, String> {
+    EnvFilter::builder()
+        .parse(s)
+        .map(|_| s.to_string())
+        .map_err(|e| format!("invalid filter directives: {}", e))
+}
+
+/// Parses a byte count with an optional binary suffix (`k`, `m`, `g`, case-insensitive).
+///
+/// # Examples
+///
+/// * `"512"` -> `512`
+/// * `"64k"` -> `65536`
+/// * `"1M"` -> `1048576`
+/// * `"1.5g"` -> error (whole numbers only)
+pub fn parse_size_string(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.as_bytes().last().map(u8::to_ascii_lowercase) {
+        Some(b'k') => (&s[..s.len() - 1], KIB),
+        Some(b'm') => (&s[..s.len() - 1], MIB),
+        Some(b'g') => (&s[..s.len() - 1], GIB),
+        _ => (s, 1),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("expected a byte count like 512, 64k or 1M, got {}", s))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size too large: {}", s))
+}
+
+/// Parses a `<Name>: <Value>` header assertion (`--expect-header`).
+///
+/// # Examples
+///
+/// * `"Content-Type: application/pdf"` -> `("Content-Type", "application/pdf")`
+/// * `"Content-Type"` -> error (missing `:`)
+pub fn parse_expect_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected <Name>: <Value>, got {}", s))?;
+    let name = name.trim();
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name: {}", name))?;
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// Parses an inclusive `<start>-<end>` byte range (`--range`).
+///
+/// # Examples
+///
+/// * `"0-1023"` -> `(0, 1023)`
+/// * `"100-99"` -> error (start after end)
+pub fn parse_byte_range(s: &str) -> Result<(u64, u64), String> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expected <start>-<end>, got {}", s))?;
+    let start: u64 = start
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid range start: {}", start))?;
+    let end: u64 = end
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid range end: {}", end))?;
+    if start > end {
+        return Err(format!("range start {} is after end {}", start, end));
+    }
+    Ok((start, end))
+}
+
+/// Headers whose values are never logged.
+const REDACTED_HEADERS: [HeaderName; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+/// Logs headers at TRACE level, one event per header.
+///
+/// Credentials (`Authorization`, `Cookie`, ...) are replaced by `[REDACTED]`.
+pub fn trace_headers(headers: &HeaderMap) {
+    for (name, value) in headers {
+        if REDACTED_HEADERS.contains(name) {
+            trace!(header_name = %name, header_value = "[REDACTED]");
+        } else {
+            trace!(header_name = %name, header_value = %header_to_string_lossy(value));
+        }
+    }
+}
+
+/// Sends a request, logging it and its response at TRACE level (`--trace-requests`).
+///
+/// Requests to the host of `--auth-user` carry its credentials; a Digest challenge
+/// (401) is answered by sending the request once more.
+/// Headers set as client defaults are logged once by [`build_client`] instead.
+pub async fn send_traced(builder: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, req) = builder.build_split();
+    let mut req = req?;
+    let Some(auth) = auth::for_request(&req) else {
+        return execute_traced(&client, req).await;
+    };
+
+    req = auth::authorize(&client, req, auth)?;
+    // Bodies are in memory, so only streams could fail to clone
+    let retry = req.try_clone();
+    let resp = execute_traced(&client, req).await?;
+    match retry {
+        Some(retry) if auth::accept_challenge(auth, &resp) => {
+            debug!(url = %retry.url(), "Retrying request with Digest credentials");
+            execute_traced(&client, auth::authorize(&client, retry, auth)?).await
+        }
+        _ => Ok(resp),
+    }
+}
+
+/// Executes one request with TRACE logging, see [`send_traced`].
+async fn execute_traced(client: &Client, req: Request) -> reqwest::Result<Response> {
+    trace!(method = %req.method(), url = %req.url(), "Sending request");
+    trace_headers(req.headers());
+
+    let resp = client.execute(req).await?;
+    trace!(status = %resp.status(), url = %resp.url(), "Response received");
+    trace_headers(resp.headers());
+    Ok(resp)
+}
+
+/// Converts a header value to a string, replacing invalid UTF-8 sequences.
+///
+/// HTTP/1.1 allows ISO-8859-1 header values, which `HeaderValue::to_str` rejects.
 ///
 /// # Examples
 ///
-/// * `http://example.com/file.zip` -> "file.zip"
-/// * `http://example.com/dir/` -> "index.html"
-pub fn get_filename_from_url(url_str: &str) -> String {
-    if let Ok(url) = reqwest::Url::parse(url_str)
-        && let Some(mut segments) = url.path_segments()
-        && let Some(last) = segments.next_back()
-        && !last.is_empty()
-    {
-        return last.to_string();
-    }
-    "index.html".to_string()
+/// * `b"bytes"` -> "bytes"
+/// * `b"caf\xe9"` -> "caf\u{FFFD}"
+pub fn header_to_string_lossy(val: &HeaderValue) -> Cow<'_, str> {
+    String::from_utf8_lossy(val.as_bytes())
+}
+
+/// Quotes `s` as a JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestDir, TestServer, response};
+
+    fn options(http_version: HttpVersion) -> ClientOptions<'static> {
+        ClientOptions {
+            user_agent: "oxidown-test",
+            proxy_mode: ProxyMode::Off,
+            proxy: None,
+            no_http_cache: false,
+            cache_control: None,
+            accept: None,
+            accept_encoding: None,
+            cookies: None,
+            http_version,
+            interface: None,
+            connect_timeout: None,
+            read_timeout: None,
+            socket_timeout: None,
+            follow_ftp: false,
+            keepalive_idle: None,
+            keepalive_count: None,
+            cacert: None,
+            insecure: false,
+            tls_min_version: TlsVersion::Tls12,
+            tls_max_version: TlsVersion::Tls13,
+            cipher_list: None,
+            tls_hostname: None,
+            pool_idle_timeout: None,
+            dns_cache_ttl: None,
+            redirect_count: None,
+        }
+    }
+
+    #[test]
+    fn client_builds_for_each_http_version() {
+        for version in [
+            HttpVersion::Auto,
+            HttpVersion::Http10,
+            HttpVersion::Http11,
+            HttpVersion::Http2,
+        ] {
+            build_client(&options(version)).unwrap();
+        }
+        let err = build_client(&options(HttpVersion::Http3)).unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn client_sends_default_headers() {
+        let server = TestServer::start(|_| response(200, &[], b"ok")).await;
+        let client = build_client(&ClientOptions {
+            user_agent: "oxidown-test/1.0",
+            no_http_cache: true,
+            accept: Some("application/octet-stream"),
+            cookies: Some("Cookie: session=abc; theme=dark"),
+            ..options(HttpVersion::Auto)
+        })
+        .unwrap();
+        client.get(&server.url).send().await.unwrap();
+
+        let req = &server.requests()[0];
+        assert_eq!(req.header("user-agent"), Some("oxidown-test/1.0"));
+        assert_eq!(req.header("accept"), Some("application/octet-stream"));
+        assert_eq!(req.header("cache-control"), Some("no-cache"));
+        assert_eq!(req.header("pragma"), Some("no-cache"));
+        assert_eq!(req.header("cookie"), Some("session=abc; theme=dark"));
+    }
+
+    #[tokio::test]
+    async fn proxy_off_connects_directly() {
+        let server = TestServer::start(|_| response(200, &[], b"ok")).await;
+        let proxy = TestServer::start(|_| response(200, &[], b"proxied")).await;
+
+        let direct = build_client(&options(HttpVersion::Auto)).unwrap();
+        let body = direct.get(format!("{}/file", server.url)).send().await;
+        assert_eq!(body.unwrap().text().await.unwrap(), "ok");
+        assert_eq!(server.requests()[0].path, "/file");
+
+        // A proxy receives the absolute URL instead
+        let proxied = build_client(&ClientOptions {
+            proxy_mode: ProxyMode::Custom,
+            proxy: Some(&proxy.url),
+            ..options(HttpVersion::Auto)
+        })
+        .unwrap();
+        let body = proxied.get("http://example.invalid/file").send().await;
+        assert_eq!(body.unwrap().text().await.unwrap(), "proxied");
+        assert_eq!(proxy.requests()[0].path, "http://example.invalid/file");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn unknown_interfaces_are_rejected() {
+        // Also the error of the platforms without interface lookup
+        let err = resolve_interface_addr("oxidown-no-such-if0").unwrap_err();
+        assert!(
+            matches!(&err, ProgramError::ArgNotValid(msg) if msg.contains("oxidown-no-such-if0")),
+            "{err}"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn loopback_interface_resolves() {
+        assert_eq!(
+            resolve_interface_addr("lo").unwrap(),
+            IpAddr::from([127, 0, 0, 1])
+        );
+    }
+
+    #[test]
+    fn client_builds_for_each_tls_version_range() {
+        let versions = [
+            TlsVersion::Tls10,
+            TlsVersion::Tls11,
+            TlsVersion::Tls12,
+            TlsVersion::Tls13,
+        ];
+        for min in versions {
+            for max in versions {
+                let result = build_client(&ClientOptions {
+                    tls_min_version: min,
+                    tls_max_version: max,
+                    ..options(HttpVersion::Auto)
+                });
+                // rustls has no TLS 1.0 or 1.1, so the range must reach 1.2
+                match result {
+                    Ok(_) => assert!(min <= max && max >= TlsVersion::Tls12, "{min:?}-{max:?}"),
+                    Err(ProgramError::ArgNotValid(msg)) if min > max => {
+                        assert!(msg.contains("tls-min-version"), "{msg}")
+                    }
+                    Err(ProgramError::ArgNotValid(msg)) => {
+                        assert!(max < TlsVersion::Tls12, "{min:?}-{max:?}: {msg}")
+                    }
+                    Err(e) => panic!("{min:?}-{max:?}: {e}"),
+                }
+            }
+        }
+    }
+
+    /// Self-signed certificate for `CN=oxidown-test`
+    const TEST_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBhTCCASugAwIBAgIUKr0HyCgeGIz3ERMhUrhM4NgsVQowCgYIKoZIzj0EAwIw
+FzEVMBMGA1UEAwwMb3hpZG93bi10ZXN0MCAXDTI2MTAxNDE2MzEzM1oYDzIxMjYw
+OTIwMTYzMTMzWjAXMRUwEwYDVQQDDAxveGlkb3duLXRlc3QwWTATBgcqhkjOPQIB
+BggqhkjOPQMBBwNCAARSY6WYVsnSR7lavno1Vaxu1gS3zOnB3sxQaMizLzDZHAhV
+eW+JMPahQ9zmc+GGvyAMBP681laUbxnbhj0neeldo1MwUTAdBgNVHQ4EFgQUfFoA
+ZwYtYH7OF6VkFmgKf0JOG9AwHwYDVR0jBBgwFoAUfFoAZwYtYH7OF6VkFmgKf0JO
+G9AwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiEAmNeIiatV1g+q
+i5zA2NupjQCxOvlopFKU1ahfessczVsCIG7bHPlBEBzPDR9pOH7HlhEsZIAqjIio
+YP01WvX45IyS
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn ca_bundle_loads_and_rejects_bad_files() {
+        let dir = TestDir::new("cacert");
+        let path = dir.join("ca.pem");
+
+        // Two copies of the certificate with text around them, as in system bundles
+        std::fs::write(&path, format!("# test CA\n{TEST_CA}\n{TEST_CA}")).unwrap();
+        assert_eq!(load_ca_bundle(&path).unwrap().len(), 2);
+        let client = build_client(&ClientOptions {
+            cacert: Some(&path),
+            ..options(HttpVersion::Auto)
+        });
+        assert!(client.is_ok());
+
+        std::fs::write(&path, "").unwrap();
+        let err = load_ca_bundle(&path).unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+        assert!(err.to_string().contains("no certificates"), "{err}");
+
+        let broken = TEST_CA.replace("MIIB", "M!IB");
+        std::fs::write(&path, broken).unwrap();
+        let err = load_ca_bundle(&path).unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+
+        let err = load_ca_bundle(&dir.join("missing.pem")).unwrap_err();
+        assert!(matches!(err, ProgramError::Io(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn http10_closes_connections() {
+        let server = TestServer::start(|_| response(200, &[], b"ok")).await;
+        for (version, connection) in [
+            (HttpVersion::Http10, Some("close")),
+            (HttpVersion::Http11, None),
+        ] {
+            let client = build_client(&options(version)).unwrap();
+            let resp = client.get(&server.url).send().await.unwrap();
+            assert_eq!(resp.version(), reqwest::Version::HTTP_11);
+            let requests = server.requests();
+            let connection_header = requests.last().unwrap().header("connection");
+            assert_eq!(connection_header, connection, "{version:?}");
+        }
+    }
+
+    #[test]
+    fn user_agent_template_is_expanded() {
+        let ua = expand_user_agent_template("oxidown/{version} ({os}/{arch}) {other}");
+        assert_eq!(
+            ua,
+            format!(
+                "oxidown/{} ({}/{}) {{other}}",
+                env!("CARGO_PKG_VERSION"),
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )
+        );
+        assert!(ua.contains(env!("CARGO_PKG_VERSION")));
+        assert_eq!(expand_user_agent_template("fixed"), "fixed");
+    }
+
+    #[test]
+    fn log_filters_are_validated() {
+        for filter in [
+            "warn",
+            "oxidown::download=debug,warn",
+            "oxidown=trace,reqwest=off",
+            "oxidown::http[probe]=trace",
+        ] {
+            assert_eq!(parse_log_filter(filter).as_deref(), Ok(filter));
+        }
+        for filter in ["oxidown=loud", "=debug", "oxidown[=trace"] {
+            let err = parse_log_filter(filter).unwrap_err();
+            assert!(
+                err.starts_with("invalid filter directives"),
+                "{filter}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn header_values_are_converted_lossily() {
+        let latin1 = HeaderValue::from_bytes(b"attachment; filename=\"caf\xe9.txt\"").unwrap();
+        assert_eq!(
+            header_to_string_lossy(&latin1),
+            "attachment; filename=\"caf\u{FFFD}.txt\""
+        );
+        assert_eq!(
+            header_to_string_lossy(&HeaderValue::from_static("bytes")),
+            "bytes"
+        );
+    }
+
+    fn derive(url: &str, disposition: Option<&str>) -> PathBuf {
+        FilenameDeriver.derive_filename(url, disposition, None)
+    }
+
+    #[test]
+    fn filename_from_explicit_output() {
+        let explicit = Some(PathBuf::from("dir/out.bin"));
+        let path = FilenameDeriver.derive_filename(
+            "http://example.com/file.zip",
+            Some("attachment; filename=a.txt"),
+            explicit,
+        );
+        assert_eq!(path, PathBuf::from("dir/out.bin"));
+    }
+
+    #[test]
+    fn filename_from_extended_content_disposition() {
+        let disposition = "attachment; filename=\"plain.txt\"; filename*=UTF-8''na%C3%AFve.txt";
+        assert_eq!(
+            derive("http://example.com/file.zip", Some(disposition)),
+            PathBuf::from("naïve.txt")
+        );
+        assert_eq!(
+            derive(
+                "http://example.com/file.zip",
+                Some("attachment; filename*=iso-8859-1'en'caf%E9.txt")
+            ),
+            PathBuf::from("café.txt")
+        );
+    }
+
+    #[test]
+    fn filename_from_plain_content_disposition() {
+        for (disposition, expected) in [
+            ("attachment; filename=\"a b.txt\"", "a b.txt"),
+            ("attachment; filename=report.pdf", "report.pdf"),
+            // Unknown charset in filename*, and a path from the server
+            (
+                "attachment; filename*=KOI8-R''%C1.txt; filename=\"../../etc/x.txt\"",
+                "x.txt",
+            ),
+        ] {
+            assert_eq!(
+                derive("http://example.com/file.zip", Some(disposition)),
+                PathBuf::from(expected),
+                "{disposition}"
+            );
+        }
+    }
+
+    #[test]
+    fn filename_from_url_path() {
+        assert_eq!(
+            derive("http://example.com/dist/file.zip?v=2", None),
+            PathBuf::from("file.zip")
+        );
+        // An empty Content-Disposition name is skipped
+        assert_eq!(
+            derive(
+                "http://example.com/file.zip",
+                Some("attachment; filename=\"\"")
+            ),
+            PathBuf::from("file.zip")
+        );
+    }
+
+    #[test]
+    fn filename_from_query_parameter() {
+        for (url, expected) in [
+            ("http://example.com/get.php?filename=a.iso", "a.iso"),
+            ("http://example.com/download?file=b.iso", "b.iso"),
+            ("http://example.com/index.php?id=1&name=c.iso", "c.iso"),
+        ] {
+            assert_eq!(derive(url, None), PathBuf::from(expected), "{url}");
+        }
+    }
+
+    #[test]
+    fn filename_from_host() {
+        assert_eq!(
+            derive("http://example.com/dir/", None),
+            PathBuf::from("example.com_download.bin")
+        );
+        assert_eq!(
+            derive("http://example.com/download.php?id=1", None),
+            PathBuf::from("example.com_download.bin")
+        );
+    }
+
+    #[test]
+    fn filename_fallback() {
+        assert_eq!(derive("not a url", None), PathBuf::from("index.html"));
+        assert_eq!(derive("data:,hello", None), PathBuf::from("index.html"));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("a \"q\" \\ b\tc\r\n\u{1}"),
+            "\"a \\\"q\\\" \\\\ b\\tc\\r\\n\\u0001\""
+        );
+        assert_eq!(json_string("naïve"), "\"naïve\"");
+    }
 }
@@ -0,0 +1,650 @@
+use aws_lc_rs::digest;
+use reqwest::{Client, Response};
+use std::path::Path;
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use tracing::{debug, info, instrument};
+
+use crate::client_trait::HttpClient;
+use crate::error::ProgramError;
+use crate::types::HashAlgorithm;
+
+/// Read buffer size used when hashing files.
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Largest checksum sidecar accepted by [`fetch_checksum_sidecar`]; a digest line is far shorter.
+const MAX_SIDECAR_SIZE: usize = 64 * 1024;
+
+impl HashAlgorithm {
+    /// Detects the algorithm from the length of a hex-encoded digest.
+    ///
+    /// 32 chars -> MD5, 40 -> SHA-1, 64 -> SHA-256, 128 -> SHA-512.
+    pub fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(HashAlgorithm::Md5),
+            40 => Some(HashAlgorithm::Sha1),
+            64 => Some(HashAlgorithm::Sha256),
+            128 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// Incrementally computes a digest over streamed data.
+pub enum HashAccumulator {
+    Md5(Md5),
+    Digest(digest::Context),
+}
+
+impl HashAccumulator {
+    /// Creates an empty accumulator for the given algorithm.
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => HashAccumulator::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => {
+                HashAccumulator::Digest(digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY))
+            }
+            HashAlgorithm::Sha256 => HashAccumulator::Digest(digest::Context::new(&digest::SHA256)),
+            HashAlgorithm::Sha512 => HashAccumulator::Digest(digest::Context::new(&digest::SHA512)),
+        }
+    }
+
+    /// Feeds more data into the digest.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            HashAccumulator::Md5(md5) => md5.update(data),
+            HashAccumulator::Digest(ctx) => ctx.update(data),
+        }
+    }
+
+    /// Finishes the digest and returns it as lowercase hex.
+    pub fn finish_hex(self) -> String {
+        match self {
+            HashAccumulator::Md5(md5) => to_hex(&md5.finish()),
+            HashAccumulator::Digest(ctx) => to_hex(ctx.finish().as_ref()),
+        }
+    }
+}
+
+/// Computes several hex digests of a file in a single pass.
+///
+/// Returns one digest per entry of `algorithms`, in the same order.
+#[instrument(fields(path = ?path))]
+pub async fn hash_file(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+) -> Result<Vec<String>, ProgramError> {
+    let mut hashers: Vec<_> = algorithms
+        .iter()
+        .map(|&a| HashAccumulator::new(a))
+        .collect();
+    update_from_file(path, &mut hashers).await?;
+
+    Ok(hashers
+        .into_iter()
+        .map(HashAccumulator::finish_hex)
+        .collect())
+}
+
+/// Feeds the whole contents of a file into each of `hashers`.
+pub async fn update_from_file(
+    path: &Path,
+    hashers: &mut [HashAccumulator],
+) -> Result<(), ProgramError> {
+    let mut file = File::open(path).await?;
+    let mut buf = vec![0u8; HASH_BUFFER_SIZE];
+
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buf[..n]);
+        }
+    }
+}
+
+/// Parses an expected hex digest and detects its algorithm from the length.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` if the digest is not valid hex of a known length.
+pub fn parse_expected(expected: &str) -> Result<(HashAlgorithm, String), ProgramError> {
+    let expected = expected.trim().to_ascii_lowercase();
+    HashAlgorithm::from_hex_len(expected.len())
+        .filter(|_| expected.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(|algorithm| (algorithm, expected.clone()))
+        .ok_or_else(|| ProgramError::ArgNotValid(format!("invalid checksum: {}", expected)))
+}
+
+/// Looks up the expected digest of `filename` in a checksum list.
+///
+/// Accepts the GNU coreutils format produced by `sha256sum`, `md5sum` and friends:
+/// `<hash>  <filename>` or `<hash> *<filename>` (binary mode). Blank lines and `#` comments
+/// are ignored. Entries are matched on the file name only, so `./dist/file.zip`
+/// matches `file.zip`.
+pub fn parse_checksum_file(contents: &str, filename: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        let name = name.trim_start();
+        let name = name.strip_prefix('*').unwrap_or(name);
+        let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+
+        (base == filename).then(|| hash.to_string())
+    })
+}
+
+/// Reads a checksum file and returns the entry for the output file name.
+///
+/// # Returns
+///
+/// * `Err(ProgramError::ArgNotValid)` if the file name has no entry in the checksum file.
+async fn lookup_checksum_file(output: &Path, checksum_file: &Path) -> Result<String, ProgramError> {
+    let contents = fs::read_to_string(checksum_file).await?;
+    let filename = output
+        .file_name()
+        .ok_or_else(|| ProgramError::ArgNotValid("output has no file name".to_string()))?
+        .to_string_lossy();
+
+    parse_checksum_file(&contents, &filename).ok_or_else(|| {
+        ProgramError::ArgNotValid(format!(
+            "{} not found in checksum file {:?}",
+            filename, checksum_file
+        ))
+    })
+}
+
+/// Runs all verifications requested for a finished download.
+///
+/// All digests (expected checksums and `--output-hash`) are computed while reading
+/// the file only once.
+///
+/// # Arguments
+///
+/// * `output` - The downloaded file.
+/// * `checksum` - Expected hex digest (`--checksum` or a discovered sidecar).
+/// * `checksum_file` - Checksum list to look the output file up in (`--checksum-file`).
+/// * `output_hashes` - Algorithms whose digests should be returned (`--output-hash`,
+///   `--output-checksum-algo`).
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - The hex digests for `output_hashes`, in the same order.
+/// * `Err(ProgramError::ChecksumMismatch)` - If any expected digest does not match.
+pub async fn verify_download(
+    output: &Path,
+    checksum: Option<&str>,
+    checksum_file: Option<&Path>,
+    output_hashes: &[HashAlgorithm],
+) -> Result<Vec<String>, ProgramError> {
+    let mut expected = Vec::new();
+    if let Some(checksum) = checksum {
+        expected.push(parse_expected(checksum)?);
+    }
+    if let Some(checksum_file) = checksum_file {
+        expected.push(parse_expected(
+            &lookup_checksum_file(output, checksum_file).await?,
+        )?);
+    }
+
+    let mut algorithms = Vec::new();
+    for algorithm in expected
+        .iter()
+        .map(|(a, _)| *a)
+        .chain(output_hashes.iter().copied())
+    {
+        if !algorithms.contains(&algorithm) {
+            algorithms.push(algorithm);
+        }
+    }
+    if algorithms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    debug!(algorithms = ?algorithms, "Hashing downloaded file");
+    let digests = hash_file(output, &algorithms).await?;
+    let digest_of = |algorithm: HashAlgorithm| {
+        let idx = algorithms.iter().position(|&a| a == algorithm);
+        idx.map(|i| digests[i].clone()).unwrap_or_default()
+    };
+
+    for (algorithm, expected) in expected {
+        let actual = digest_of(algorithm);
+        if actual != expected {
+            return Err(ProgramError::ChecksumMismatch { expected, actual });
+        }
+        info!(algorithm = ?algorithm, "Checksum verified");
+    }
+
+    Ok(output_hashes.iter().map(|&a| digest_of(a)).collect())
+}
+
+/// Formats a checksum list entry in GNU coreutils format (`<hash>  <filename>`, two
+/// spaces for text mode), as printed by `--output-hash`.
+pub fn checksum_line(hash: &str, filename: &str) -> String {
+    format!("{}  {}", hash, filename)
+}
+
+/// Appends a [`checksum_line`] to a checksum list (`--output-checksum-file`).
+///
+/// The list can be verified with `sha256sum -c` and friends. The file is created if
+/// needed; appending lets several downloads share one list.
+pub async fn write_checksum_file(
+    path: &Path,
+    hash: &str,
+    filename: &str,
+) -> Result<(), ProgramError> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(format!("{}\n", checksum_line(hash, filename)).as_bytes())
+        .await?;
+    // tokio writes in the background; without a flush the line may be lost on drop
+    file.flush().await?;
+    debug!(path = ?path, filename = filename, "Checksum written");
+    Ok(())
+}
+
+/// Looks for a checksum sidecar next to the download URL.
+///
+/// Tries `<url>.sha256` first and then `<url>.md5`. Each candidate is read into memory
+/// (at most [`MAX_SIDECAR_SIZE`] bytes) and looked up with [`parse_checksum_file`] under
+/// the file name of `url`; a sidecar holding only a digest uses the first token of its
+/// first non-empty line.
+///
+/// # Returns
+///
+/// * `Ok(Some(hash))` if a sidecar with a valid digest was found.
+/// * `Ok(None)` if no sidecar exists (every candidate returned an HTTP error status).
+/// * `Err(ProgramError)` on network failures or a sidecar larger than the limit.
+#[instrument(skip(client), fields(url = %url))]
+pub async fn fetch_checksum_sidecar(
+    client: &Client,
+    url: &str,
+) -> Result<Option<String>, ProgramError> {
+    let filename = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.path_segments()?.next_back().map(str::to_string))
+        .unwrap_or_default();
+
+    for ext in ["sha256", "md5"] {
+        let sidecar_url = format!("{}.{}", url, ext);
+        debug!(sidecar = %sidecar_url, "Trying checksum sidecar");
+
+        let resp = HttpClient::get(client, &sidecar_url, None).await?;
+        if !resp.status().is_success() {
+            debug!(
+                code = resp.status().as_u16(),
+                "Checksum sidecar not available"
+            );
+            continue;
+        }
+        let contents = read_capped(resp, MAX_SIDECAR_SIZE).await?;
+
+        let hash = parse_checksum_file(&contents, &filename)
+            .or_else(|| {
+                contents
+                    .lines()
+                    .find_map(|line| line.split_whitespace().next().map(str::to_string))
+            })
+            .filter(|hash| {
+                HashAlgorithm::from_hex_len(hash.len()).is_some()
+                    && hash.bytes().all(|b| b.is_ascii_hexdigit())
+            });
+        if let Some(hash) = hash {
+            info!(sidecar = %sidecar_url, checksum = %hash, "Discovered checksum");
+            return Ok(Some(hash));
+        }
+        debug!(sidecar = %sidecar_url, "Checksum sidecar has no valid digest");
+    }
+
+    Ok(None)
+}
+
+/// Reads a response body as (lossy) UTF-8, failing once it exceeds `limit` bytes.
+async fn read_capped(mut resp: Response, limit: usize) -> Result<String, ProgramError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(ProgramError::Other(format!(
+                "checksum sidecar {} is larger than {} bytes",
+                resp.url(),
+                limit
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Formats bytes as lowercase hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Streaming MD5 (RFC 1321), kept for legacy `MD5SUMS` files.
+///
+/// MD5 is not collision resistant; it is only used to compare against published checksums.
+pub struct Md5 {
+    state: [u32; 4],
+    buffer: [u8; 64],
+    buffer_len: usize,
+    total_len: u64,
+}
+
+impl Md5 {
+    const S: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+
+    const K: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+
+    pub fn new() -> Self {
+        Md5 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: [0; 64],
+            buffer_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len = self.total_len.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let take = (64 - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len < 64 {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+
+        let mut chunks = data.chunks_exact(64);
+        for block in &mut chunks {
+            self.compress(block.try_into().expect("64-byte chunk"));
+        }
+        let rest = chunks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    pub fn finish(mut self) -> [u8; 16] {
+        let bit_len = self.total_len.wrapping_mul(8);
+
+        let mut padding = [0u8; 72];
+        padding[0] = 0x80;
+        let pad_len = if self.buffer_len < 56 {
+            56 - self.buffer_len
+        } else {
+            120 - self.buffer_len
+        };
+        self.update(&padding[..pad_len]);
+        self.update(&bit_len.to_le_bytes());
+
+        let mut out = [0u8; 16];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut m = [0u32; 16];
+        for (word, bytes) in m.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().expect("4-byte chunk"));
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(Self::K[i])
+                .wrapping_add(m[g])
+                .rotate_left(Self::S[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestDir, TestServer, response};
+
+    const SHA256: &str = "a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3";
+
+    /// Digests of `abc`, the first test vector of each algorithm's specification
+    const ABC_MD5: &str = "900150983cd24fb0d6963f7d28e17f72";
+    const ABC_SHA1: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const ABC_SHA512: &str = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn sidecar_is_looked_up_by_file_name() {
+        let body = format!("{}  other.bin\n{}  file.bin\n", "0".repeat(64), SHA256);
+        let server = TestServer::start(move |req| match req.path.as_str() {
+            "/dist/file.bin.sha256" => response(200, &[], body.as_bytes()),
+            _ => response(404, &[], b""),
+        })
+        .await;
+
+        let hash = fetch_checksum_sidecar(&client(), &format!("{}/dist/file.bin", server.url))
+            .await
+            .unwrap();
+        assert_eq!(hash.as_deref(), Some(SHA256));
+    }
+
+    #[tokio::test]
+    async fn sidecar_falls_back_to_md5_with_a_bare_digest() {
+        let server = TestServer::start(|req| match req.path.as_str() {
+            "/file.bin.md5" => response(200, &[], b"\n900150983cd24fb0d6963f7d28e17f72\n"),
+            _ => response(404, &[], b""),
+        })
+        .await;
+
+        let hash = fetch_checksum_sidecar(&client(), &format!("{}/file.bin", server.url))
+            .await
+            .unwrap();
+        assert_eq!(hash.as_deref(), Some("900150983cd24fb0d6963f7d28e17f72"));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn missing_sidecar_is_none() {
+        let server = TestServer::start(|_| response(404, &[], b"")).await;
+        let hash = fetch_checksum_sidecar(&client(), &format!("{}/file.bin", server.url))
+            .await
+            .unwrap();
+        assert_eq!(hash, None);
+    }
+
+    #[tokio::test]
+    async fn oversized_sidecar_is_rejected() {
+        let server =
+            TestServer::start(|_| response(200, &[], &vec![b'a'; MAX_SIDECAR_SIZE + 1])).await;
+        let result = fetch_checksum_sidecar(&client(), &format!("{}/file.bin", server.url)).await;
+        assert!(result.is_err());
+    }
+
+    /// Writes `abc` to `file.bin` and `list` to `SUMS` in `dir`.
+    fn abc_with_list(dir: &TestDir, list: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+        let (file, sums) = (dir.join("file.bin"), dir.join("SUMS"));
+        std::fs::write(&file, b"abc").unwrap();
+        std::fs::write(&sums, list).unwrap();
+        (file, sums)
+    }
+
+    #[test]
+    fn checksum_file_formats() {
+        let sha256sums = format!(
+            "# release checksums\n\n{}  other.bin\n{}  ./dist/file.bin\n",
+            ABC_SHA1, ABC_SHA256
+        );
+        assert_eq!(
+            parse_checksum_file(&sha256sums, "file.bin").as_deref(),
+            Some(ABC_SHA256)
+        );
+        assert_eq!(
+            parse_checksum_file(&sha256sums, "other.bin").as_deref(),
+            Some(ABC_SHA1)
+        );
+
+        // md5sum binary mode, and Windows paths
+        let md5sums = format!("{} *file.bin\n{} *dist\\other.bin\n", ABC_MD5, ABC_MD5);
+        assert_eq!(
+            parse_checksum_file(&md5sums, "file.bin").as_deref(),
+            Some(ABC_MD5)
+        );
+        assert_eq!(
+            parse_checksum_file(&md5sums, "other.bin").as_deref(),
+            Some(ABC_MD5)
+        );
+
+        assert_eq!(parse_checksum_file(&sha256sums, "missing.bin"), None);
+        assert_eq!(parse_checksum_file(&sha256sums, "release"), None);
+        assert_eq!(parse_checksum_file(ABC_SHA256, "file.bin"), None);
+    }
+
+    #[tokio::test]
+    async fn checksum_file_verifies_each_algorithm() {
+        let dir = TestDir::new("sums-good");
+        for hash in [ABC_MD5, ABC_SHA1, ABC_SHA256, ABC_SHA512] {
+            let (file, sums) = abc_with_list(&dir, &format!("{}  file.bin\n", hash));
+            verify_download(&file, None, Some(&sums), &[])
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn checksum_file_mismatch() {
+        let dir = TestDir::new("sums-bad");
+        let (file, sums) = abc_with_list(&dir, &format!("{}  file.bin\n", "0".repeat(64)));
+        let err = verify_download(&file, None, Some(&sums), &[])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ProgramError::ChecksumMismatch { actual, .. } if actual == ABC_SHA256),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn checksum_file_without_entry() {
+        let dir = TestDir::new("sums-missing");
+        let (file, sums) = abc_with_list(&dir, &format!("{}  other.bin\n", ABC_SHA256));
+        let err = verify_download(&file, None, Some(&sums), &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+
+        let (file, sums) = abc_with_list(&dir, "not-a-hash  file.bin\n");
+        let err = verify_download(&file, None, Some(&sums), &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProgramError::ArgNotValid(_)), "{err}");
+    }
+
+    #[test]
+    fn checksum_line_format() {
+        assert_eq!(
+            checksum_line(ABC_SHA256, "dist/file.bin"),
+            format!("{}  dist/file.bin", ABC_SHA256)
+        );
+    }
+
+    #[tokio::test]
+    async fn output_hashes_of_known_content() {
+        let dir = TestDir::new("output-hash");
+        let (file, _) = abc_with_list(&dir, "");
+        let digests = verify_download(
+            &file,
+            None,
+            None,
+            &[
+                HashAlgorithm::Sha256,
+                HashAlgorithm::Sha512,
+                HashAlgorithm::Md5,
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(digests, [ABC_SHA256, ABC_SHA512, ABC_MD5]);
+
+        // The digest checked against --checksum is reused for the output hash
+        let digests = verify_download(
+            &file,
+            Some(&ABC_SHA256.to_uppercase()),
+            None,
+            &[HashAlgorithm::Sha256],
+        )
+        .await
+        .unwrap();
+        assert_eq!(digests, [ABC_SHA256]);
+    }
+
+    #[tokio::test]
+    async fn output_checksum_file_is_appended_to() {
+        let dir = TestDir::new("output-checksum-file");
+        let list = dir.join("SHA256SUMS");
+        write_checksum_file(&list, ABC_SHA256, "a.bin")
+            .await
+            .unwrap();
+        write_checksum_file(&list, ABC_SHA256, "b.bin")
+            .await
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&list).unwrap();
+        assert_eq!(contents, format!("{0}  a.bin\n{0}  b.bin\n", ABC_SHA256));
+        assert_eq!(
+            parse_checksum_file(&contents, "b.bin").as_deref(),
+            Some(ABC_SHA256)
+        );
+    }
+}
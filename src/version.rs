@@ -0,0 +1,95 @@
+use reqwest::Client;
+use std::time::Duration;
+use tracing::{debug, instrument, warn};
+
+use crate::error::ProgramError;
+use crate::utils::send_traced;
+
+/// GitHub API endpoint describing the latest published release.
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/mengguyi/Oxidown/releases/latest";
+
+/// Upper bound for the whole check, so a slow API never holds up the exit.
+const VERSION_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Warns if the latest GitHub release is newer than the running version (`--version-check`).
+///
+/// Failures are only logged at debug level: the check is advisory and must never
+/// affect the download or the exit code.
+///
+/// # Arguments
+///
+/// * `client` - HTTP client configured like the download client (proxy, TLS, User-Agent).
+#[instrument(skip(client))]
+pub async fn check_latest_version(client: Client) {
+    let current = env!("CARGO_PKG_VERSION");
+    match tokio::time::timeout(VERSION_CHECK_TIMEOUT, fetch_latest_tag(&client)).await {
+        Ok(Ok(tag)) => {
+            let latest = tag.trim_start_matches(['v', 'V']);
+            if is_newer(latest, current) {
+                warn!(
+                    "A new version ({}) is available. Run oxidown --self-update to upgrade.",
+                    latest
+                );
+            } else {
+                debug!(latest = latest, current = current, "Oxidown is up to date");
+            }
+        }
+        Ok(Err(e)) => debug!(error = %e, "Version check failed"),
+        Err(_) => debug!("Version check timed out"),
+    }
+}
+
+/// Fetches the `tag_name` of the latest release.
+async fn fetch_latest_tag(client: &Client) -> Result<String, ProgramError> {
+    let resp = send_traced(client.get(LATEST_RELEASE_URL)).await?;
+    if !resp.status().is_success() {
+        return Err(ProgramError::HttpStatus {
+            code: resp.status().as_u16(),
+            url: LATEST_RELEASE_URL.to_string(),
+        });
+    }
+    let body = resp.text().await?;
+    json_string_field(&body, "tag_name")
+        .ok_or_else(|| ProgramError::Other("release response has no tag_name".to_string()))
+}
+
+/// Extracts the string value of `"key": "value"` from a JSON document.
+///
+/// This is deliberately minimal (no nesting awareness), which is enough for the
+/// flat `tag_name` field of the releases API.
+///
+/// # Examples
+///
+/// * `{"tag_name": "v0.2.0"}`, `tag_name` -> `Some("v0.2.0")`
+/// * `{"name": "x"}`, `tag_name` -> `None`
+fn json_string_field(json: &str, key: &str) -> Option<String> {
+    let needle = format!("\"{}\"", key);
+    let after_key = &json[json.find(&needle)? + needle.len()..];
+    let value = after_key.trim_start().strip_prefix(':')?.trim_start();
+    let value = value.strip_prefix('"')?;
+
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(out),
+            '\\' => out.push(chars.next()?),
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+/// Compares dotted numeric versions (`0.10.1` > `0.9.3`); pre-release suffixes are ignored.
+fn is_newer(latest: &str, current: &str) -> bool {
+    fn numbers(version: &str) -> Vec<u64> {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|n| n.parse().unwrap_or(0))
+            .collect()
+    }
+    numbers(latest) > numbers(current)
+}
@@ -0,0 +1,23 @@
+//! Exit status of the oxidown binary, as listed in `--help`.
+
+use std::process::Command;
+
+#[test]
+fn invalid_argument_exits_with_2() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oxidown"))
+        .args(["--threads", "many", "http://127.0.0.1:9/file"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--threads"));
+}
+
+#[test]
+fn rejected_option_value_exits_with_2() {
+    // Parsed fine by clap, rejected by oxidown itself before any request
+    let output = Command::new(env!("CARGO_BIN_EXE_oxidown"))
+        .args(["--threads", "0", "http://127.0.0.1:9/file"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
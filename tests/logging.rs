@@ -0,0 +1,90 @@
+//! What the oxidown binary prints on stderr during a download.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Serves `body` to every request on a random local port, without range support.
+fn serve(body: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            if !request_line.starts_with("HEAD") {
+                let _ = stream.write_all(body);
+            }
+        }
+    });
+    url
+}
+
+/// Downloads from `url` into a fresh temp file with the extra `args`.
+fn download(url: &str, name: &str, args: &[&str]) -> (Output, PathBuf) {
+    let output =
+        std::env::temp_dir().join(format!("oxidown-test-{}-{}.txt", std::process::id(), name));
+    let _ = std::fs::remove_file(&output);
+    let result = Command::new(env!("CARGO_BIN_EXE_oxidown"))
+        .args(args)
+        .arg("--output")
+        .arg(&output)
+        .arg(url)
+        .output()
+        .unwrap();
+    (result, output)
+}
+
+#[test]
+fn log_level_off_prints_nothing() {
+    let url = serve(b"hello");
+    let (result, output) = download(&url, "off", &["--log-level", "off"]);
+    let saved = std::fs::read(&output);
+    let _ = std::fs::remove_file(&output);
+
+    assert!(result.status.success(), "{result:?}");
+    assert_eq!(saved.unwrap(), b"hello");
+    assert_eq!(String::from_utf8_lossy(&result.stderr), "");
+    assert_eq!(String::from_utf8_lossy(&result.stdout), "");
+}
+
+#[test]
+fn disabled_verification_is_always_announced() {
+    let url = serve(b"hello");
+    let (result, output) = download(&url, "no-verify", &["--no-verify-ssl"]);
+    let _ = std::fs::remove_file(&output);
+
+    assert!(result.status.success(), "{result:?}");
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("--no-verify-ssl is deprecated; use --insecure"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("WARNING: TLS certificate verification is DISABLED. This is insecure."),
+        "{stderr}"
+    );
+
+    // Printed even when logging is off
+    let (result, output) = download(&url, "insecure", &["--insecure", "--log-level", "off"]);
+    let _ = std::fs::remove_file(&output);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert_eq!(
+        stderr.trim_end(),
+        "WARNING: TLS certificate verification is DISABLED. This is insecure."
+    );
+}